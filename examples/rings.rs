@@ -0,0 +1,29 @@
+use valora::prelude::*;
+
+struct Rings {
+    count: usize,
+}
+
+#[valora::main(size = 512, fps = 30)]
+impl Artist for Rings {
+    fn setup(_gpu: Gpu, _world: World, rng: &mut StdRng) -> Result<Self> {
+        Ok(Rings {
+            count: rng.gen_range(3, 12),
+        })
+    }
+
+    fn paint(&mut self, ctx: Context, canvas: &mut Canvas) {
+        canvas.set_color(LinSrgb::new(1., 1., 1.));
+        canvas.paint(Filled(ctx.world));
+
+        canvas.set_color(LinSrgb::new(0.1, 0.1, 0.3));
+        let max_radius = ctx.world.width.min(ctx.world.height) / 2.;
+        for i in 1..=self.count {
+            let radius = max_radius * i as f32 / (self.count + 1) as f32;
+            canvas.paint(Stroked {
+                element: Ellipse::circle(ctx.world.center(), radius),
+                width: 2.,
+            });
+        }
+    }
+}
@@ -1,4 +1,5 @@
 /// Spatial attributes.
+mod bounding_box;
 mod center;
 
-pub use self::center::*;
+pub use self::{bounding_box::*, center::*};
@@ -0,0 +1,98 @@
+use crate::{
+    forms::{Ellipse, Ngon, Polygon, Rect, RoundedRect, Sector, Star},
+    path::bounding_box,
+    FlatIterPath, P2, V2,
+};
+use lyon_path::PathEvent;
+
+/// A trait for objects which occupy a bounded region of space.
+pub trait BoundingBox {
+    /// Returns the smallest axis aligned rectangle containing the object, or `None` if the object
+    /// is empty.
+    fn bounding_box(&self) -> Option<Rect>;
+}
+
+impl BoundingBox for Rect {
+    fn bounding_box(&self) -> Option<Rect> {
+        Some(*self)
+    }
+}
+
+impl BoundingBox for RoundedRect {
+    fn bounding_box(&self) -> Option<Rect> {
+        Some(self.rect)
+    }
+}
+
+impl BoundingBox for Ellipse {
+    fn bounding_box(&self) -> Option<Rect> {
+        let (sin, cos) = self.phase.radians.sin_cos();
+        let half_extent = V2::new(
+            (self.radii.x * cos).hypot(self.radii.y * sin),
+            (self.radii.x * sin).hypot(self.radii.y * cos),
+        );
+        Some(Rect {
+            bottom_left: self.center - half_extent,
+            width: half_extent.x * 2.,
+            height: half_extent.y * 2.,
+        })
+    }
+}
+
+/// Bounds the whole circle, which contains the sector.
+impl BoundingBox for Sector {
+    fn bounding_box(&self) -> Option<Rect> {
+        Some(Rect {
+            bottom_left: self.center - V2::new(self.radius, self.radius),
+            width: self.radius * 2.,
+            height: self.radius * 2.,
+        })
+    }
+}
+
+impl BoundingBox for Polygon {
+    fn bounding_box(&self) -> Option<Rect> {
+        Polygon::bounding_box(self)
+    }
+}
+
+impl BoundingBox for Star {
+    fn bounding_box(&self) -> Option<Rect> {
+        extent(self.vertices())
+    }
+}
+
+impl BoundingBox for Ngon {
+    fn bounding_box(&self) -> Option<Rect> {
+        extent(*self)
+    }
+}
+
+impl<P> BoundingBox for P
+where
+    P: Iterator<Item = PathEvent> + Clone,
+{
+    fn bounding_box(&self) -> Option<Rect> {
+        bounding_box(self.clone())
+    }
+}
+
+fn extent(vertices: impl Iterator<Item = P2>) -> Option<Rect> {
+    bounding_box(FlatIterPath::new(vertices, /*closed=*/ true))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Angle;
+
+    #[test]
+    fn rotated_ellipse_bounds() {
+        let ellipse = Ellipse::new(P2::new(1., 2.), V2::new(4., 1.))
+            .with_phase(Angle::radians(std::f32::consts::FRAC_PI_2));
+        let bounds = ellipse.bounding_box().unwrap();
+        assert!((bounds.width - 2.).abs() < 1e-5);
+        assert!((bounds.height - 8.).abs() < 1e-5);
+        assert!((bounds.bottom_left - P2::new(0., -2.)).length() < 1e-5);
+    }
+}
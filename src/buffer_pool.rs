@@ -0,0 +1,152 @@
+//! Reuse of uploaded geometry across frames.
+
+use crate::{gpu::GpuVertex, Result};
+use glium::{backend::Facade, index::PrimitiveType, IndexBuffer, VertexBuffer};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
+
+/// The default maximum number of geometries kept uploaded by a `BufferPool`.
+pub const DEFAULT_BUFFER_POOL_CAPACITY: usize = 256;
+
+/// Vertices and indices uploaded to the GPU.
+pub struct GeometryBuffers {
+    pub vertices: VertexBuffer<GpuVertex>,
+    pub indices: IndexBuffer<u32>,
+}
+
+struct PoolEntry {
+    buffers: Rc<GeometryBuffers>,
+    /// The geometry the buffers hold, to tell it apart from other geometry with the same hash.
+    vertices: Vec<GpuVertex>,
+    indices: Vec<u32>,
+    last_used: u64,
+}
+
+impl PoolEntry {
+    fn holds(&self, vertices: &[GpuVertex], indices: &[u32]) -> bool {
+        self.indices == indices
+            && self.vertices.len() == vertices.len()
+            && self
+                .vertices
+                .iter()
+                .zip(vertices)
+                .all(|(a, b)| vertex_bits(a).eq(vertex_bits(b)))
+    }
+}
+
+/// A cache of uploaded geometry keyed by a hash of its contents, so geometry which is the same as
+/// in a recent frame is not uploaded again.
+///
+/// When full, the least recently used geometry is evicted.
+pub struct BufferPool {
+    capacity: usize,
+    clock: u64,
+    entries: HashMap<u64, PoolEntry>,
+    /// The key of each entry by when it was last used, oldest first.
+    by_last_use: BTreeMap<u64, u64>,
+}
+
+impl BufferPool {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            clock: 0,
+            entries: HashMap::new(),
+            by_last_use: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the maximum number of geometries kept, evicting any beyond it. A capacity of zero
+    /// disables pooling.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > self.capacity {
+            self.evict();
+        }
+    }
+
+    /// Returns buffers holding the geometry, uploading it only if it is not pooled.
+    pub fn upload<F: Facade + ?Sized>(
+        &mut self,
+        facade: &F,
+        vertices: &[GpuVertex],
+        indices: &[u32],
+    ) -> Result<Rc<GeometryBuffers>> {
+        let upload = || -> Result<Rc<GeometryBuffers>> {
+            Ok(Rc::new(GeometryBuffers {
+                vertices: VertexBuffer::new(facade, vertices)?,
+                indices: IndexBuffer::new(facade, PrimitiveType::TrianglesList, indices)?,
+            }))
+        };
+        if self.capacity == 0 {
+            return upload();
+        }
+
+        self.clock += 1;
+        let key = geometry_hash(vertices, indices);
+        if let Some(entry) = self.entries.get_mut(&key) {
+            self.by_last_use.remove(&entry.last_used);
+            self.by_last_use.insert(self.clock, key);
+            entry.last_used = self.clock;
+            if entry.holds(vertices, indices) {
+                return Ok(entry.buffers.clone());
+            }
+            // Different geometry with the same hash replaces the pooled geometry.
+            self.remove(key);
+        }
+
+        let buffers = upload()?;
+        if self.entries.len() >= self.capacity {
+            self.evict();
+        }
+        self.entries.insert(
+            key,
+            PoolEntry {
+                buffers: buffers.clone(),
+                vertices: vertices.to_vec(),
+                indices: indices.to_vec(),
+                last_used: self.clock,
+            },
+        );
+        self.by_last_use.insert(self.clock, key);
+        Ok(buffers)
+    }
+
+    fn evict(&mut self) {
+        if let Some((_, key)) = self.by_last_use.iter().next() {
+            let key = *key;
+            self.remove(key);
+        }
+    }
+
+    fn remove(&mut self, key: u64) {
+        if let Some(entry) = self.entries.remove(&key) {
+            self.by_last_use.remove(&entry.last_used);
+        }
+    }
+}
+
+fn geometry_hash(vertices: &[GpuVertex], indices: &[u32]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    vertices.len().hash(&mut hasher);
+    for vertex in vertices {
+        for bits in vertex_bits(vertex) {
+            bits.hash(&mut hasher);
+        }
+    }
+    indices.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns the bits of each component of the vertex, so that vertices compare and hash the same
+/// exactly when they were built from the same values.
+fn vertex_bits(vertex: &GpuVertex) -> impl Iterator<Item = u32> + '_ {
+    vertex
+        .vpos
+        .iter()
+        .chain(vertex.vcol.iter())
+        .map(|component| component.to_bits())
+}
@@ -1,96 +1,437 @@
 //! A painting surface.
 
-use crate::{gpu::Shader, paint::Paint, Angle, Element, Method, P2, V2};
-use lyon_path::Builder;
+use crate::{
+    forms::DEFAULT_MITER_LIMIT,
+    gpu::{
+        Drawable, Gpu, GradientGeometry, Instanced, Layer, Shader, INSTANCE_TRANSFORM_ATTRIBUTE,
+    },
+    paint::Paint,
+    path::flatten,
+    text::Font,
+    Angle, BoundingBox, Element, Method, Rect, Result, Transform, P2, V2,
+};
+use failure::bail;
+use lyon_path::{geom::Arc, Builder, Path};
+use lyon_tessellation::{LineCap, LineJoin};
 use palette::{Alpha, IntoColor, LinSrgb, LinSrgba};
 
+/// The default maximum depth of the stack used by `Canvas::save()`.
+pub const DEFAULT_STATE_STACK_LIMIT: usize = 64;
+
+/// The way colors painted in a layer are combined with the colors beneath the layer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// Colors are painted over those beneath.
+    #[default]
+    Normal = 0,
+    /// Colors are multiplied with those beneath, darkening them.
+    Multiply = 1,
+    /// Inverted colors are multiplied with those beneath, lightening them.
+    Screen = 2,
+    /// Multiplies dark colors beneath and screens light colors beneath.
+    Overlay = 3,
+    /// Colors are added to those beneath.
+    Add = 4,
+}
+
+/// The shape at an end of a stroked path.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum StrokeCap {
+    /// The stroke ends flat at the end of the path.
+    #[default]
+    Butt,
+    /// The stroke ends flat, extended past the end of the path by half the stroke width.
+    Square,
+    /// The stroke ends with a semicircle around the end of the path.
+    Round,
+    /// The stroke ends with a triangular arrowhead whose tip is the end of the path.
+    Arrow { head_length: f32, head_width: f32 },
+}
+
+/// The shape where segments of a stroked path meet.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StrokeJoin {
+    /// The outer edges of the segments are extended until they meet, clipped where the miter
+    /// would be longer than `limit` times the stroke width, as with SVG's `stroke-miterlimit`.
+    Miter { limit: f32 },
+    /// The corner is cut off flat. This is the cheapest to tessellate.
+    Bevel,
+    /// The corner is rounded, which suits organic paths at the cost of more vertices.
+    Round,
+}
+
+impl Default for StrokeJoin {
+    fn default() -> Self {
+        StrokeJoin::Miter {
+            limit: DEFAULT_MITER_LIMIT,
+        }
+    }
+}
+
+impl StrokeJoin {
+    /// The join and miter limit the tessellator should use. It cannot miter below a limit of 1.
+    fn line_join(self) -> (LineJoin, f32) {
+        match self {
+            StrokeJoin::Miter { limit } => (LineJoin::MiterClip, limit.max(1.)),
+            StrokeJoin::Bevel => (LineJoin::Bevel, DEFAULT_MITER_LIMIT),
+            StrokeJoin::Round => (LineJoin::Round, DEFAULT_MITER_LIMIT),
+        }
+    }
+}
+
+impl StrokeCap {
+    /// An arrowhead of the given length whose sides meet at its tip at the given angle in
+    /// degrees.
+    pub fn arrow_with_angle(head_length: f32, head_angle_deg: f32) -> Self {
+        StrokeCap::Arrow {
+            head_length,
+            head_width: 2. * head_length * (head_angle_deg.to_radians() / 2.).tan(),
+        }
+    }
+
+    /// The cap the tessellator should put on the stroke body. Arrowheads are painted separately,
+    /// so the body ends flat beneath them.
+    fn line_cap(self) -> LineCap {
+        match self {
+            StrokeCap::Butt | StrokeCap::Arrow { .. } => LineCap::Butt,
+            StrokeCap::Square => LineCap::Square,
+            StrokeCap::Round => LineCap::Round,
+        }
+    }
+}
+
 /// A painting surface.
+///
+/// Methods return the canvas so calls chain, as in `canvas.move_to(a).line_to(b).stroke()`.
 pub struct Canvas {
+    gpu: Gpu,
     path: Builder,
+    state: State,
+    state_stack: Vec<State>,
+    state_stack_limit: usize,
+    scale: f32,
+    transform_stack: Vec<Transform>,
+    position: P2,
+    drawables: Vec<Drawable>,
+    layer_stack: Vec<OpenLayer>,
+    /// The region of output space outside which paths are not painted.
+    viewport: Option<Rect>,
+    /// The output space regions painting is clipped to, each within the one before it.
+    clip_stack: Vec<Rect>,
+    /// The output space extent of the points and control points of the current path.
+    extent: Option<(P2, P2)>,
+    /// Whether paths painted now are tessellated in parallel, as within `draw_batch_par()` with
+    /// the `parallel` feature.
+    parallel: bool,
+}
+
+/// A layer which is being painted, holding the drawables of the canvas beneath it.
+struct OpenLayer {
+    opacity: f32,
+    blend: BlendMode,
+    /// The output space path the layer is masked to, if it was begun by `push_mask()`.
+    mask: Option<Path>,
+    beneath: Vec<Drawable>,
+}
+
+/// The drawing state of a canvas which is snapshotted by `Canvas::save()`.
+#[derive(Clone)]
+struct State {
     shader: Shader,
     color: LinSrgba,
     stroke_width: f32,
-    scale: f32,
-    elements: Vec<Element>,
+    start_cap: StrokeCap,
+    end_cap: StrokeCap,
+    join: StrokeJoin,
+    transform: Transform,
+    blend: BlendMode,
+    fill_shader: Option<Shader>,
 }
 
 impl Canvas {
-    pub(crate) fn new(default_shader: Shader, scale: f32) -> Self {
+    pub(crate) fn new(gpu: Gpu, default_shader: Shader, scale: f32) -> Self {
         Self {
+            gpu,
             path: Builder::new(),
-            shader: default_shader,
-            color: Alpha::<LinSrgb, _>::new(1., 1., 1., 1.),
+            state: State {
+                shader: default_shader,
+                color: Alpha::<LinSrgb, _>::new(1., 1., 1., 1.),
+                stroke_width: 1.,
+                start_cap: StrokeCap::Butt,
+                end_cap: StrokeCap::Butt,
+                join: StrokeJoin::default(),
+                transform: Transform::identity(),
+                blend: BlendMode::Normal,
+                fill_shader: None,
+            },
+            state_stack: vec![],
+            state_stack_limit: DEFAULT_STATE_STACK_LIMIT,
             scale,
-            stroke_width: 1.,
-            elements: vec![],
+            transform_stack: vec![],
+            position: P2::origin(),
+            drawables: vec![],
+            layer_stack: vec![],
+            viewport: None,
+            clip_stack: vec![],
+            extent: None,
+            parallel: false,
         }
     }
 
     /// Paints an element.
-    pub fn paint(&mut self, element: impl Paint) {
+    pub fn paint(&mut self, element: impl Paint) -> &mut Self {
         element.paint(self);
+        self
+    }
+
+    /// Paints each element of the batch, tessellating the paths they paint concurrently on
+    /// rayon's worker threads when the canvas is rendered.
+    ///
+    /// Elements are painted in order with the canvas state as it is and as they change it, just
+    /// as with `paint()`, and render the same. This is worth it for batches of many paths which
+    /// are slow to tessellate, such as long strokes; for simple paths the threads cost more than
+    /// they save.
+    #[cfg(feature = "parallel")]
+    pub fn draw_batch_par<P: Paint>(&mut self, batch: impl IntoIterator<Item = P>) -> &mut Self {
+        let parallel = std::mem::replace(&mut self.parallel, true);
+        for element in batch {
+            element.paint(self);
+        }
+        self.parallel = parallel;
+        self
     }
 
     /// Sets the current color.
-    pub fn set_color(&mut self, color: impl IntoColor) {
-        self.color = Alpha::from(color.into_rgb());
+    pub fn set_color(&mut self, color: impl IntoColor) -> &mut Self {
+        self.state.color = Alpha::from(color.into_rgb());
+        self
     }
 
     /// Sets the current color.
-    pub fn set_color_alpha(&mut self, color: impl IntoColor, alpha: f32) {
-        self.color = Alpha {
+    pub fn set_color_alpha(&mut self, color: impl IntoColor, alpha: f32) -> &mut Self {
+        self.state.color = Alpha {
             color: color.into_rgb(),
             alpha,
         };
+        self
     }
 
     /// Stats a new path at the given point.
-    pub fn move_to(&mut self, dest: P2) {
+    pub fn move_to(&mut self, dest: P2) -> &mut Self {
         self.path = Builder::new();
-        self.path.move_to(dest * self.scale);
+        self.extent = None;
+        let dest_out = self.include(dest);
+        self.path.move_to(dest_out);
+        self.position = dest;
+        self
+    }
+
+    /// Starts a new subpath of the current path at the given point, keeping the subpaths
+    /// before it. Where subpaths overlap fills alternate between inside and outside, so a subpath
+    /// within another cuts a hole in it.
+    pub fn begin_subpath(&mut self, dest: P2) -> &mut Self {
+        let dest_out = self.include(dest);
+        self.path.move_to(dest_out);
+        self.position = dest;
+        self
     }
 
     /// Adds a line to the current path which ends at the given point.
-    pub fn line_to(&mut self, dest: P2) {
-        self.path.line_to(dest * self.scale);
+    pub fn line_to(&mut self, dest: P2) -> &mut Self {
+        let dest_out = self.include(dest);
+        self.path.line_to(dest_out);
+        self.position = dest;
+        self
     }
 
     /// Adds a quadratic bezier curve to the current path with the given control and end points.
-    pub fn quadratic_to(&mut self, ctrl: P2, end: P2) {
-        self.path
-            .quadratic_bezier_to(ctrl * self.scale, end * self.scale);
+    pub fn quadratic_to(&mut self, ctrl: P2, end: P2) -> &mut Self {
+        let (ctrl, end_out) = (self.include(ctrl), self.include(end));
+        self.path.quadratic_bezier_to(ctrl, end_out);
+        self.position = end;
+        self
     }
 
     /// Adds a cubic bezier curve to the current path with the given control and end points.
-    pub fn cubic_to(&mut self, ctrl0: P2, ctrl1: P2, end: P2) {
-        self.path
-            .cubic_bezier_to(ctrl0 * self.scale, ctrl1 * self.scale, end * self.scale);
+    pub fn cubic_to(&mut self, ctrl0: P2, ctrl1: P2, end: P2) -> &mut Self {
+        let (ctrl0, ctrl1, end_out) = (self.include(ctrl0), self.include(ctrl1), self.include(end));
+        self.path.cubic_bezier_to(ctrl0, ctrl1, end_out);
+        self.position = end;
+        self
     }
 
     /// Adds an arc segment to the path.
-    pub fn arc(&mut self, center: P2, radii: V2, sweep: Angle, phase: Angle) {
-        self.path
-            .arc(center * self.scale, radii * self.scale, sweep, phase);
+    ///
+    /// The arc begins at the angle of the current point around the center.
+    pub fn arc(&mut self, center: P2, radii: V2, sweep: Angle, phase: Angle) -> &mut Self {
+        let arc = Arc {
+            center,
+            radii,
+            start_angle: (self.position - center).angle_from_x_axis() - phase,
+            sweep_angle: sweep,
+            x_rotation: phase,
+        };
+
+        if (arc.from() - self.position).square_length() > 0.01 {
+            self.line_to(arc.from());
+        }
+
+        arc.for_each_quadratic_bezier(&mut |curve| {
+            self.quadratic_to(curve.ctrl, curve.to);
+        });
+        self
     }
 
     /// Closes the current path.
-    pub fn close_path(&mut self) {
-        self.path.close()
+    pub fn close_path(&mut self) -> &mut Self {
+        self.path.close();
+        self
     }
 
     /// Sets the width of lines drawn with the `stroke()`.
-    pub fn set_stroke_width(&mut self, stroke_width: f32) {
-        self.stroke_width = stroke_width * self.scale;
+    pub fn set_stroke_width(&mut self, stroke_width: f32) -> &mut Self {
+        self.state.stroke_width = stroke_width * self.scale;
+        self
+    }
+
+    /// Sets the caps on the start and end of paths drawn with `stroke()`. Closed paths have no
+    /// caps.
+    pub fn set_stroke_caps(&mut self, start: StrokeCap, end: StrokeCap) -> &mut Self {
+        self.state.start_cap = start;
+        self.state.end_cap = end;
+        self
+    }
+
+    /// Sets the joins between segments of paths drawn with `stroke()`. The default is a miter
+    /// with a limit of `DEFAULT_MITER_LIMIT`.
+    pub fn set_stroke_join(&mut self, join: StrokeJoin) -> &mut Self {
+        self.state.join = join;
+        self
     }
 
     /// Paints the current path by filling the region inside the path.
-    pub fn fill(&mut self) {
+    pub fn fill(&mut self) -> &mut Self {
+        if self.cull(0.) {
+            return self;
+        }
         self.push_element(Method::Fill);
+        self
     }
 
     /// Paints the current path by stroking the path.
-    pub fn stroke(&mut self) {
-        self.push_element(Method::Stroke(self.stroke_width));
+    pub fn stroke(&mut self) -> &mut Self {
+        let (start_cap, end_cap) = (self.state.start_cap, self.state.end_cap);
+        let head_size = |cap| match cap {
+            StrokeCap::Arrow {
+                head_length,
+                head_width,
+            } => head_length.max(head_width / 2.) * self.scale,
+            _ => 0.,
+        };
+        let (join, miter_limit) = self.state.join.line_join();
+        // Miters are clipped at half the limit times the stroke width from the corner.
+        let margin = (self.state.stroke_width * miter_limit / 2.)
+            .max(head_size(start_cap))
+            .max(head_size(end_cap));
+        if self.cull(margin) {
+            return self;
+        }
+
+        let method = Method::Stroke {
+            width: self.state.stroke_width,
+            start_cap: start_cap.line_cap(),
+            end_cap: end_cap.line_cap(),
+            join,
+            miter_limit,
+        };
+        let has_arrow = |cap| matches!(cap, StrokeCap::Arrow { .. });
+        if !has_arrow(start_cap) && !has_arrow(end_cap) {
+            self.push_element(method);
+            return self;
+        }
+
+        // Cut the arrowheads' lengths off the ends of the path so the stroke ends at their bases.
+        let path = std::mem::replace(&mut self.path, Builder::new()).build();
+        self.extent = None;
+        let mut body = Builder::new();
+        let mut heads = vec![];
+        for (mut points, closed) in flatten(path.iter(), 0.05) {
+            if !closed {
+                if let StrokeCap::Arrow {
+                    head_length,
+                    head_width,
+                } = start_cap
+                {
+                    points.reverse();
+                    heads.extend(self.trim_arrow_head(&mut points, head_length, head_width));
+                    points.reverse();
+                }
+                if let StrokeCap::Arrow {
+                    head_length,
+                    head_width,
+                } = end_cap
+                {
+                    heads.extend(self.trim_arrow_head(&mut points, head_length, head_width));
+                }
+            }
+
+            if points.len() > 1 {
+                body.move_to(points[0]);
+                for p in &points[1..] {
+                    body.line_to(*p);
+                }
+                if closed {
+                    body.close();
+                }
+            }
+        }
+
+        let shader = self.state.shader.clone();
+        let paths = std::iter::once((body, method))
+            .chain(heads.into_iter().map(|head| (head, Method::Fill)))
+            .collect();
+        self.push_elements(shader, paths);
+        self
+    }
+
+    /// Paints a line of text in the current color, left aligned and starting on its baseline at
+    /// `position`. `size` is the height of a line of the text.
+    ///
+    /// Text is rasterized at the output resolution, so it will blur if enlarged by the active
+    /// transform.
+    pub fn draw_text(
+        &mut self,
+        text: &str,
+        position: P2,
+        size: f32,
+        font: &Font,
+    ) -> Result<&mut Self> {
+        let coverage = match font.rasterize(text, size * self.scale) {
+            Some(coverage) => coverage,
+            None => return Ok(self),
+        };
+
+        // The bitmap's rows run down from its top, but the canvas y axis points up.
+        let corner = position + V2::new(coverage.left as f32, -coverage.top as f32) / self.scale;
+        let x_axis = V2::new(coverage.width as f32, 0.) / self.scale;
+        let y_axis = V2::new(0., -(coverage.height as f32)) / self.scale;
+
+        let origin = self.project(corner);
+        let shader = self.gpu.text_shader(
+            coverage,
+            origin,
+            self.project(corner + x_axis) - origin,
+            self.project(corner + y_axis) - origin,
+        )?;
+
+        let mut quad = Builder::new();
+        quad.move_to(origin);
+        quad.line_to(self.project(corner + x_axis));
+        quad.line_to(self.project(corner + x_axis + y_axis));
+        quad.line_to(self.project(corner + y_axis));
+        quad.close();
+        self.push_elements(shader, vec![(quad, Method::Fill)]);
+        Ok(self)
     }
 
     /// Sets the current shader used to shade rastered paths.
@@ -98,28 +439,554 @@ impl Canvas {
     /// Changing shaders requires making a new draw call to the GPU and tearing down some state.
     /// Changing shaders 0-10 times per frame is likely to be fast enough. Changing shaders 500
     /// times per frame will be slow.
-    pub fn set_shader(&mut self, shader: Shader) {
-        self.shader = shader;
+    pub fn set_shader(&mut self, shader: Shader) -> &mut Self {
+        self.state.shader = shader;
+        self
     }
 
-    fn push_element(&mut self, raster_method: Method) {
-        let mut path = Builder::new();
-        std::mem::swap(&mut self.path, &mut path);
-
-        self.elements.push(Element {
-            path,
-            color: self.color,
-            shader: self.shader.clone(),
-            raster_method,
+    /// Makes the given transform active, on top of the currently active transform, until the
+    /// next call to `pop_transform()`.
+    ///
+    /// All points given to path methods are transformed by the active transform.
+    pub fn push_transform(&mut self, transform: Transform) -> &mut Self {
+        self.transform_stack.push(self.state.transform);
+        self.state.transform = transform.post_transform(&self.state.transform);
+        self
+    }
+
+    /// Restores the transform which was active before the last call to `push_transform()`.
+    pub fn pop_transform(&mut self) -> Result<&mut Self> {
+        match self.transform_stack.pop() {
+            Some(transform) => {
+                self.state.transform = transform;
+                Ok(self)
+            }
+            None => bail!("Popped a transform from an empty transform stack"),
+        }
+    }
+
+    /// Clips painting to the rectangle, within any clip already active, until the next call to
+    /// `pop_clip_rect()`. The rectangle is transformed by the active transform, and clips to the
+    /// bounds of the result.
+    ///
+    /// Clipping is done by the GPU per draw call, so it costs little.
+    pub fn push_clip_rect(&mut self, rect: Rect) -> &mut Self {
+        let corners = rect.vertices().map(|p| self.project(p));
+        let (mut min, mut max) = corners.fold(
+            (P2::new(f32::MAX, f32::MAX), P2::new(f32::MIN, f32::MIN)),
+            |(min, max), p| (min.min(p), max.max(p)),
+        );
+        if let Some(outer) = self.clip_stack.last() {
+            min = min.max(outer.bottom_left);
+            max = max.min(outer.bottom_left + V2::new(outer.width, outer.height));
+        }
+        self.clip_stack.push(Rect {
+            bottom_left: min,
+            width: (max.x - min.x).max(0.),
+            height: (max.y - min.y).max(0.),
         });
+        self
+    }
+
+    /// Restores the clip which was active before the last call to `push_clip_rect()`.
+    pub fn pop_clip_rect(&mut self) -> Result<&mut Self> {
+        match self.clip_stack.pop() {
+            Some(_) => Ok(self),
+            None => bail!("Popped a clip rect from an empty clip stack"),
+        }
+    }
+
+    /// Translates the active transform.
+    pub fn translate(&mut self, translation: V2) -> &mut Self {
+        self.state.transform = Transform::create_translation(translation.x, translation.y)
+            .post_transform(&self.state.transform);
+        self
+    }
+
+    /// Rotates the active transform around the origin.
+    pub fn rotate(&mut self, theta: Angle) -> &mut Self {
+        self.state.transform =
+            Transform::create_rotation(theta).post_transform(&self.state.transform);
+        self
+    }
+
+    /// Scales the active transform from the origin.
+    pub fn scale(&mut self, x: f32, y: f32) -> &mut Self {
+        self.state.transform = Transform::create_scale(x, y).post_transform(&self.state.transform);
+        self
+    }
+
+    /// Fills the next path painted with `fill()` with a linear gradient from `start_color` at
+    /// `start` to `end_color` at `end`, after which fills revert to the current shader.
+    ///
+    /// The gradient is positioned in the same coordinate space as paths.
+    pub fn set_fill_gradient_linear(
+        &mut self,
+        start: P2,
+        end: P2,
+        start_color: LinSrgba,
+        end_color: LinSrgba,
+    ) -> &mut Self {
+        self.set_fill_gradient_linear_stops(start, end, vec![(0., start_color), (1., end_color)]);
+        self
+    }
+
+    /// Like `set_fill_gradient_linear()`, but with any number of `(offset, color)` stops, where
+    /// offsets in [0, 1] are positions between `start` and `end`.
+    ///
+    /// Only the first `MAX_GRADIENT_STOPS` stops by offset are used.
+    pub fn set_fill_gradient_linear_stops(
+        &mut self,
+        start: P2,
+        end: P2,
+        stops: Vec<(f32, LinSrgba)>,
+    ) -> &mut Self {
+        if stops.is_empty() {
+            return self;
+        }
+
+        let gradient = GradientGeometry::Linear {
+            start: self.project(start),
+            end: self.project(end),
+        };
+        self.state.fill_shader = Some(self.gpu.gradient_shader(gradient, stops));
+        self
+    }
+
+    /// Fills the next path painted with `fill()` with a radial gradient from `inner_color` at
+    /// `center` to `outer_color` at `radius` from the center and beyond, after which fills revert
+    /// to the current shader.
+    pub fn set_fill_gradient_radial(
+        &mut self,
+        center: P2,
+        radius: f32,
+        inner_color: LinSrgba,
+        outer_color: LinSrgba,
+    ) -> &mut Self {
+        self.set_fill_gradient_radial_stops(
+            center,
+            radius,
+            vec![(0., inner_color), (1., outer_color)],
+        );
+        self
+    }
+
+    /// Like `set_fill_gradient_radial()`, but with any number of `(offset, color)` stops, where
+    /// offsets in [0, 1] are distances from the center as a fraction of `radius`.
+    ///
+    /// Only the first `MAX_GRADIENT_STOPS` stops by offset are used.
+    pub fn set_fill_gradient_radial_stops(
+        &mut self,
+        center: P2,
+        radius: f32,
+        stops: Vec<(f32, LinSrgba)>,
+    ) -> &mut Self {
+        if stops.is_empty() {
+            return self;
+        }
+
+        // Measure the radius after transformation along the x axis.
+        let projected_center = self.project(center);
+        let projected_radius =
+            (self.project(center + V2::new(radius, 0.)) - projected_center).length();
+        let gradient = GradientGeometry::Radial {
+            center: projected_center,
+            radius: projected_radius,
+        };
+        self.state.fill_shader = Some(self.gpu.gradient_shader(gradient, stops));
+        self
+    }
+
+    /// Sets the blend mode of the next path painted with `fill()` or `stroke()`, after which the
+    /// blend mode reverts to `BlendMode::Normal`.
+    ///
+    /// `Normal` and `Add` are cheap. The other modes need to read the colors beneath the path, so
+    /// the path is painted in its own layer as if with `push_layer()`.
+    pub fn set_blend_mode(&mut self, blend: BlendMode) -> &mut Self {
+        self.state.blend = blend;
+        self
+    }
+
+    /// Saves the current drawing state (color, shader, stroke width, caps and join, transform and
+    /// blend mode) so that it can be restored with `restore()`.
+    ///
+    /// Fails if the number of saved states would exceed the stack limit.
+    pub fn save(&mut self) -> Result<&mut Self> {
+        if self.state_stack.len() >= self.state_stack_limit {
+            bail!(
+                "Saved more than {} canvas states; is restore() missing?",
+                self.state_stack_limit
+            );
+        }
+
+        self.state_stack.push(self.state.clone());
+        Ok(self)
+    }
+
+    /// Restores the drawing state saved by the last call to `save()`.
+    pub fn restore(&mut self) -> Result<&mut Self> {
+        match self.state_stack.pop() {
+            Some(state) => {
+                self.state = state;
+                Ok(self)
+            }
+            None => bail!("Restored a canvas state when none were saved"),
+        }
+    }
+
+    /// Sets the maximum number of states which may be saved at once with `save()`. The default
+    /// is `DEFAULT_STATE_STACK_LIMIT`.
+    pub fn set_state_stack_limit(&mut self, limit: usize) -> &mut Self {
+        self.state_stack_limit = limit;
+        self
+    }
+
+    /// Begins a new layer. Everything painted until the matching `pop_layer()` is rendered
+    /// together, and then composited onto what is beneath it with the given opacity and blend
+    /// mode.
+    ///
+    /// Each layer requires rendering to an intermediate buffer, so layers are much more expensive
+    /// than changing colors or shaders.
+    pub fn push_layer(&mut self, opacity: f32, blend: BlendMode) -> &mut Self {
+        self.layer_stack.push(OpenLayer {
+            opacity,
+            blend,
+            mask: None,
+            beneath: std::mem::take(&mut self.drawables),
+        });
+        self
+    }
+
+    /// Ends the layer begun by the last call to `push_layer()`.
+    pub fn pop_layer(&mut self) -> Result<&mut Self> {
+        match self.layer_stack.last() {
+            Some(layer) if layer.mask.is_none() => Ok(self.close_layer()),
+            Some(_) => bail!("Popped a layer when the last one pushed was a mask"),
+            None => bail!("Popped a layer when none were pushed"),
+        }
+    }
+
+    /// Masks painting to the inside of the element's path, transformed by the active transform,
+    /// until the matching `pop_mask()`. Masks nest, each within the one before it.
+    ///
+    /// The element is painted only for its path, so pass a shape such as an `Ellipse` rather
+    /// than a `Filled` one. Like a layer, a mask requires rendering to intermediate buffers: the
+    /// path is drawn into the stencil buffer of one, and painting until `pop_mask()` colors only
+    /// the pixels it covers.
+    pub fn push_mask(&mut self, mask: &impl Paint) -> &mut Self {
+        let pending = std::mem::replace(&mut self.path, Builder::new());
+        let position = self.position;
+        let extent = self.extent.take();
+        mask.paint(self);
+        let mask = std::mem::replace(&mut self.path, pending).build();
+        self.position = position;
+        self.extent = extent;
+
+        self.layer_stack.push(OpenLayer {
+            opacity: 1.,
+            blend: BlendMode::Normal,
+            mask: Some(mask),
+            beneath: std::mem::take(&mut self.drawables),
+        });
+        self
+    }
+
+    /// Ends the mask begun by the last call to `push_mask()`.
+    pub fn pop_mask(&mut self) -> Result<&mut Self> {
+        match self.layer_stack.last() {
+            Some(layer) if layer.mask.is_some() => Ok(self.close_layer()),
+            Some(_) => bail!("Popped a mask when the last one pushed was a layer"),
+            None => bail!("Popped a mask when none were pushed"),
+        }
+    }
+
+    /// Ends the last layer or mask pushed, if any.
+    fn close_layer(&mut self) -> &mut Self {
+        if let Some(layer) = self.layer_stack.pop() {
+            let drawables = std::mem::replace(&mut self.drawables, layer.beneath);
+            self.drawables.push(Drawable::Layer(Layer {
+                opacity: layer.opacity,
+                blend: layer.blend,
+                mask: layer.mask,
+                drawables,
+            }));
+        }
+        self
+    }
+
+    /// Paints the element without transformation or scaling and returns the path it produced,
+    /// leaving the canvas as it was.
+    pub(crate) fn capture_path(&mut self, element: &impl Paint) -> Path {
+        let transform = std::mem::replace(&mut self.state.transform, Transform::identity());
+        let scale = std::mem::replace(&mut self.scale, 1.);
+        let path = std::mem::replace(&mut self.path, Builder::new());
+        let position = self.position;
+        let extent = self.extent.take();
+
+        element.paint(self);
+
+        let captured = std::mem::replace(&mut self.path, path).build();
+        self.state.transform = transform;
+        self.scale = scale;
+        self.position = position;
+        self.extent = extent;
+        captured
+    }
+
+    /// Skips painting paths which lie entirely outside the viewport, given in coordinate space
+    /// regardless of the active transform, so they are never tessellated. This is worthwhile when
+    /// much of what is painted falls outside the painting. `None` disables culling, which is the
+    /// default.
+    pub fn set_culling(&mut self, viewport: Option<Rect>) -> &mut Self {
+        self.viewport = viewport.map(|viewport| Rect {
+            bottom_left: viewport.bottom_left * self.scale,
+            width: viewport.width * self.scale,
+            height: viewport.height * self.scale,
+        });
+        self
+    }
+
+    /// Returns whether the element would be painted within the viewport set by `set_culling()`
+    /// with the active transform. Everything is visible when culling is disabled.
+    pub fn is_visible(&self, element: &impl BoundingBox) -> bool {
+        let viewport = match self.viewport {
+            Some(viewport) => viewport,
+            None => return true,
+        };
+        let bounds = match element.bounding_box() {
+            Some(bounds) => bounds,
+            None => return false,
+        };
+        let corners = bounds.vertices().map(|p| self.project(p));
+        let (min, max) = corners.fold(
+            (P2::new(f32::MAX, f32::MAX), P2::new(f32::MIN, f32::MIN)),
+            |(min, max), p| (min.min(p), max.max(p)),
+        );
+        intersects(viewport, (min, max), 0.)
+    }
+
+    /// Fills the path of the shape once for each transform, in a single draw call. The
+    /// transforms are applied in coordinate space, before the active transform.
+    ///
+    /// This is much faster than painting the shape repeatedly when drawing many copies of it,
+    /// such as particles. The shader must read each instance's transform from a `mat3`
+    /// attribute named `a_instance_transform`, as `shaders::builtins::instanced()` does; this
+    /// fails otherwise.
+    pub fn draw_instanced(
+        &mut self,
+        shader: &Shader,
+        shape: &impl Paint,
+        transforms: &[Transform],
+    ) -> Result<&mut Self> {
+        if !shader.is_instanced() {
+            bail!(
+                "Shaders for draw_instanced() must declare the {} attribute",
+                INSTANCE_TRANSFORM_ATTRIBUTE
+            );
+        }
+
+        let pending = std::mem::replace(&mut self.path, Builder::new());
+        let position = self.position;
+        let extent = self.extent.take();
+        shape.paint(self);
+        let path = std::mem::replace(&mut self.path, pending);
+        self.position = position;
+        self.extent = extent;
+
+        let projection = self.projection();
+        let unprojection = match projection.inverse() {
+            Some(unprojection) => unprojection,
+            None => return Ok(self),
+        };
+        let transforms = transforms
+            .iter()
+            .map(|t| unprojection.post_transform(t).post_transform(&projection))
+            .collect();
+
+        let color = self.state.color;
+        let clip = self.clip_stack.last().copied();
+        let instanced = |blend| {
+            Drawable::Instanced(Instanced {
+                element: Element {
+                    path: path.build(),
+                    color,
+                    raster_method: Method::Fill,
+                    shader: shader.clone(),
+                    blend,
+                    clip,
+                    parallel: false,
+                },
+                transforms,
+            })
+        };
+        let instanced = match std::mem::take(&mut self.state.blend) {
+            blend @ BlendMode::Normal | blend @ BlendMode::Add => instanced(blend),
+            blend => Drawable::Layer(Layer {
+                opacity: 1.,
+                blend,
+                mask: None,
+                drawables: vec![instanced(BlendMode::Normal)],
+            }),
+        };
+        self.drawables.push(instanced);
+
+        Ok(self)
+    }
+
+    /// What has been painted so far, outside any open layers.
+    pub(crate) fn drawables(&self) -> &[Drawable] {
+        &self.drawables
+    }
+
+    /// Fills the next path painted with `fill()` with the shader.
+    pub(crate) fn set_fill_shader(&mut self, shader: Shader) {
+        self.state.fill_shader = Some(shader);
+    }
+
+    /// The transform from coordinate space to output space.
+    pub(crate) fn projection(&self) -> Transform {
+        self.state.transform.post_scale(self.scale, self.scale)
+    }
+
+    pub(crate) fn gpu(&self) -> &Gpu {
+        &self.gpu
+    }
+
+    fn project(&self, p: P2) -> P2 {
+        self.state.transform.transform_point(p) * self.scale
+    }
+
+    /// Projects a point of the current path, extending the path's extent to include it.
+    fn include(&mut self, p: P2) -> P2 {
+        let p = self.project(p);
+        self.extent = Some(match self.extent {
+            Some((min, max)) => (min.min(p), max.max(p)),
+            None => (p, p),
+        });
+        p
+    }
+
+    /// Discards the current path if culling is enabled and it lies further than `margin` outside
+    /// the viewport. Returns whether it was discarded.
+    ///
+    /// One-shot state is consumed as if the path had been painted.
+    fn cull(&mut self, margin: f32) -> bool {
+        let visible = match (self.viewport, self.extent) {
+            (Some(viewport), Some(extent)) => intersects(viewport, extent, margin),
+            _ => true,
+        };
+        if !visible {
+            self.path = Builder::new();
+            self.extent = None;
+            self.state.fill_shader = None;
+            self.state.blend = BlendMode::Normal;
+        }
+        !visible
+    }
+
+    /// Shortens an output space polyline by the length of an arrowhead at its end, and returns
+    /// the path of the arrowhead, pointing along the polyline's final direction.
+    fn trim_arrow_head(
+        &self,
+        points: &mut Vec<P2>,
+        head_length: f32,
+        head_width: f32,
+    ) -> Option<Builder> {
+        let tip = *points.last()?;
+        let direction = points
+            .windows(2)
+            .rev()
+            .map(|segment| segment[1] - segment[0])
+            .find(|v| v.square_length() > 0.)?
+            .normalize();
+        let (head_length, head_width) = (head_length * self.scale, head_width * self.scale);
+
+        let mut left = head_length;
+        while points.len() > 1 {
+            let end = points[points.len() - 1];
+            let previous = points[points.len() - 2];
+            let length = (end - previous).length();
+            if length > left {
+                points.pop();
+                points.push(end.lerp(previous, left / length));
+                break;
+            }
+            left -= length;
+            points.pop();
+        }
+
+        let base = tip - direction * head_length;
+        let wing = V2::new(-direction.y, direction.x) * head_width / 2.;
+        let mut head = Builder::new();
+        head.move_to(tip);
+        head.line_to(base + wing);
+        head.line_to(base - wing);
+        head.close();
+        Some(head)
+    }
+
+    fn push_element(&mut self, raster_method: Method) {
+        let path = std::mem::replace(&mut self.path, Builder::new());
+        self.extent = None;
+        let shader = match raster_method {
+            Method::Fill => self.state.fill_shader.take(),
+            Method::Stroke { .. } => None,
+        }
+        .unwrap_or_else(|| self.state.shader.clone());
+        self.push_elements(shader, vec![(path, raster_method)]);
+    }
+
+    /// Pushes paths painted together with the current color and the one-shot blend mode.
+    fn push_elements(&mut self, shader: Shader, paths: Vec<(Builder, Method)>) {
+        let requested_blend = std::mem::take(&mut self.state.blend);
+        let color = self.state.color;
+        let clip = self.clip_stack.last().copied();
+        let parallel = self.parallel;
+        let elements = |blend| {
+            paths.into_iter().map(move |(path, raster_method)| {
+                Drawable::Element(Element {
+                    path: path.build(),
+                    color,
+                    shader: shader.clone(),
+                    raster_method,
+                    blend,
+                    clip,
+                    parallel,
+                })
+            })
+        };
+
+        match requested_blend {
+            blend @ BlendMode::Normal | blend @ BlendMode::Add => {
+                self.drawables.extend(elements(blend))
+            }
+            blend => self.drawables.push(Drawable::Layer(Layer {
+                opacity: 1.,
+                blend,
+                mask: None,
+                drawables: elements(BlendMode::Normal).collect(),
+            })),
+        }
     }
 }
 
+/// Returns whether the output space extent, grown by `margin`, overlaps the viewport.
+fn intersects(viewport: Rect, (min, max): (P2, P2), margin: f32) -> bool {
+    min.x - margin <= viewport.bottom_left.x + viewport.width
+        && max.x + margin >= viewport.bottom_left.x
+        && min.y - margin <= viewport.bottom_left.y + viewport.height
+        && max.y + margin >= viewport.bottom_left.y
+}
+
 impl IntoIterator for Canvas {
-    type Item = Element;
+    type Item = Drawable;
     type IntoIter = std::vec::IntoIter<Self::Item>;
 
-    fn into_iter(self) -> Self::IntoIter {
-        self.elements.into_iter()
+    /// Any layers or masks left open are closed.
+    fn into_iter(mut self) -> Self::IntoIter {
+        while !self.layer_stack.is_empty() {
+            self.close_layer();
+        }
+        self.drawables.into_iter()
     }
 }
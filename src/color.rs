@@ -0,0 +1,450 @@
+//! Color models and palettes.
+//!
+//! Unlike palette's `Hsl` and `Hsv`, which are defined over linear RGB, the `Hsl` and `Hsv` here
+//! are defined over sRGB encoded components, as color pickers and most other software present them.
+//!
+//! `Oklch` is perceptually uniform, so ramps and palettes built by interpolating it look evenly
+//! spaced.
+//!
+//! Colors are exchanged as arrays of linear components, as the canvas paints with.
+
+use crate::Result;
+use failure::bail;
+use palette::{
+    encoding::{srgb::Srgb, TransferFn},
+    white_point::D65,
+    IntoColor, LinSrgb, Xyz,
+};
+use std::borrow::Cow;
+
+/// A color of the HSL model.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Hsl {
+    /// The hue in degrees, in [0, 360).
+    pub h: f32,
+    /// The saturation, in [0, 1].
+    pub s: f32,
+    /// The lightness, in [0, 1].
+    pub l: f32,
+}
+
+/// A color of the HSV model.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Hsv {
+    /// The hue in degrees, in [0, 360).
+    pub h: f32,
+    /// The saturation, in [0, 1].
+    pub s: f32,
+    /// The value, in [0, 1].
+    pub v: f32,
+}
+
+impl Hsl {
+    /// Creates a color, wrapping the hue into [0, 360) and clamping the other components into
+    /// [0, 1].
+    pub fn new(h: f32, s: f32, l: f32) -> Self {
+        Self {
+            h: wrap_hue(h),
+            s: s.clamp(0., 1.),
+            l: l.clamp(0., 1.),
+        }
+    }
+
+    /// Returns the color with its hue rotated by the given degrees.
+    pub fn rotate_hue(self, degrees: f32) -> Self {
+        Self::new(self.h + degrees, self.s, self.l)
+    }
+}
+
+impl Hsv {
+    /// Creates a color, wrapping the hue into [0, 360) and clamping the other components into
+    /// [0, 1].
+    pub fn new(h: f32, s: f32, v: f32) -> Self {
+        Self {
+            h: wrap_hue(h),
+            s: s.clamp(0., 1.),
+            v: v.clamp(0., 1.),
+        }
+    }
+
+    /// Returns the color with its hue rotated by the given degrees.
+    pub fn rotate_hue(self, degrees: f32) -> Self {
+        Self::new(self.h + degrees, self.s, self.v)
+    }
+}
+
+/// Converts to linear RGBA, with an alpha of 1.
+impl From<Hsl> for [f32; 4] {
+    fn from(hsl: Hsl) -> Self {
+        let hsv = Hsv::from(hsl);
+        hsv.into()
+    }
+}
+
+/// Converts from linear RGBA, ignoring alpha.
+impl From<[f32; 4]> for Hsl {
+    fn from(rgba: [f32; 4]) -> Self {
+        Hsl::from(Hsv::from(rgba))
+    }
+}
+
+/// Converts to linear RGBA, with an alpha of 1.
+impl From<Hsv> for [f32; 4] {
+    fn from(hsv: Hsv) -> Self {
+        let Hsv { h, s, v } = Hsv::new(hsv.h, hsv.s, hsv.v);
+        let chroma = v * s;
+        let sector = h / 60.;
+        let x = chroma * (1. - (sector % 2. - 1.).abs());
+        let (r, g, b) = match sector as u32 {
+            0 => (chroma, x, 0.),
+            1 => (x, chroma, 0.),
+            2 => (0., chroma, x),
+            3 => (0., x, chroma),
+            4 => (x, 0., chroma),
+            _ => (chroma, 0., x),
+        };
+        let m = v - chroma;
+        [decode(r + m), decode(g + m), decode(b + m), 1.]
+    }
+}
+
+/// Converts from linear RGBA, ignoring alpha.
+impl From<[f32; 4]> for Hsv {
+    fn from(rgba: [f32; 4]) -> Self {
+        let [r, g, b] = [encode(rgba[0]), encode(rgba[1]), encode(rgba[2])];
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let chroma = max - min;
+
+        let h = if chroma <= 0. {
+            0.
+        } else if max == r {
+            60. * ((g - b) / chroma)
+        } else if max == g {
+            60. * ((b - r) / chroma + 2.)
+        } else {
+            60. * ((r - g) / chroma + 4.)
+        };
+        let s = if max <= 0. { 0. } else { chroma / max };
+
+        Hsv::new(h, s, max)
+    }
+}
+
+impl From<Hsl> for Hsv {
+    fn from(hsl: Hsl) -> Self {
+        let v = hsl.l + hsl.s * hsl.l.min(1. - hsl.l);
+        let s = if v <= 0. { 0. } else { 2. * (1. - hsl.l / v) };
+        Hsv::new(hsl.h, s, v)
+    }
+}
+
+impl From<Hsv> for Hsl {
+    fn from(hsv: Hsv) -> Self {
+        let l = hsv.v * (1. - hsv.s / 2.);
+        let s = if l <= 0. || l >= 1. {
+            0.
+        } else {
+            (hsv.v - l) / l.min(1. - l)
+        };
+        Hsl::new(hsv.h, s, l)
+    }
+}
+
+/// Allows passing colors straight to `Canvas::set_color()`.
+impl IntoColor<D65, f32> for Hsl {
+    fn into_xyz(self) -> Xyz<D65, f32> {
+        Hsv::from(self).into_xyz()
+    }
+}
+
+/// Allows passing colors straight to `Canvas::set_color()`.
+impl IntoColor<D65, f32> for Hsv {
+    fn into_xyz(self) -> Xyz<D65, f32> {
+        let [r, g, b, _] = <[f32; 4]>::from(self);
+        Xyz::from(LinSrgb::new(r, g, b))
+    }
+}
+
+/// A color of the OKLCH model, the polar form of Björn Ottosson's Oklab.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Oklch {
+    /// The perceived lightness, in [0, 1].
+    pub l: f32,
+    /// The chroma, from 0 for gray up to about 0.37 for the most saturated sRGB colors.
+    pub c: f32,
+    /// The hue in degrees, in [0, 360).
+    pub h_deg: f32,
+}
+
+impl Oklch {
+    /// Creates a color, wrapping the hue into [0, 360).
+    pub fn new(l: f32, c: f32, h_deg: f32) -> Self {
+        Self {
+            l,
+            c: c.max(0.),
+            h_deg: wrap_hue(h_deg),
+        }
+    }
+
+    /// Interpolates between colors, with `t` of 0 giving `a` and 1 giving `b`. Hue takes the
+    /// shorter way around the hue circle.
+    pub fn lerp(a: &Self, b: &Self, t: f32) -> Self {
+        let mut dh = b.h_deg - a.h_deg;
+        if dh > 180. {
+            dh -= 360.;
+        } else if dh < -180. {
+            dh += 360.;
+        }
+        Self::new(
+            a.l + (b.l - a.l) * t,
+            a.c + (b.c - a.c) * t,
+            a.h_deg + dh * t,
+        )
+    }
+}
+
+/// Converts to linear sRGB. Colors outside the sRGB gamut have components outside [0, 1].
+impl From<Oklch> for [f32; 3] {
+    fn from(oklch: Oklch) -> Self {
+        let h = oklch.h_deg.to_radians();
+        let (l, a, b) = (oklch.l, oklch.c * h.cos(), oklch.c * h.sin());
+
+        let l_ = (l + 0.396_337_78 * a + 0.215_803_76 * b).powi(3);
+        let m_ = (l - 0.105_561_346 * a - 0.063_854_17 * b).powi(3);
+        let s_ = (l - 0.089_484_18 * a - 1.291_485_5 * b).powi(3);
+
+        [
+            4.076_741_7 * l_ - 3.307_711_6 * m_ + 0.230_969_94 * s_,
+            -1.268_438 * l_ + 2.609_757_4 * m_ - 0.341_319_38 * s_,
+            -0.004_196_086_3 * l_ - 0.703_418_6 * m_ + 1.707_614_7 * s_,
+        ]
+    }
+}
+
+/// Converts from linear sRGB.
+impl From<[f32; 3]> for Oklch {
+    fn from(rgb: [f32; 3]) -> Self {
+        let [r, g, b] = rgb;
+        let l_ = (0.412_221_47 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+        let m_ = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+        let s_ = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+
+        let l = 0.210_454_26 * l_ + 0.793_617_8 * m_ - 0.004_072_047 * s_;
+        let a = 1.977_998_5 * l_ - 2.428_592_2 * m_ + 0.450_593_7 * s_;
+        let b = 0.025_904_037 * l_ + 0.782_771_77 * m_ - 0.808_675_77 * s_;
+
+        Oklch::new(l, a.hypot(b), b.atan2(a).to_degrees())
+    }
+}
+
+/// Allows passing colors straight to `Canvas::set_color()`.
+impl IntoColor<D65, f32> for Oklch {
+    fn into_xyz(self) -> Xyz<D65, f32> {
+        let [r, g, b] = <[f32; 3]>::from(self);
+        Xyz::from(LinSrgb::new(r, g, b))
+    }
+}
+
+/// An ordered list of linear RGBA colors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    colors: Cow<'static, [[f32; 4]]>,
+}
+
+impl Palette {
+    /// The viridis colormap, from dark blue through green to yellow.
+    pub const VIRIDIS: Palette = Palette {
+        colors: Cow::Borrowed(&[
+            [0.057805, 0.000304, 0.088656, 1.0],
+            [0.06301, 0.026241, 0.198069, 1.0],
+            [0.043735, 0.084376, 0.258183, 1.0],
+            [0.025187, 0.168269, 0.270498, 1.0],
+            [0.015209, 0.283149, 0.262251, 1.0],
+            [0.021219, 0.423268, 0.215861, 1.0],
+            [0.111932, 0.584078, 0.122139, 1.0],
+            [0.417885, 0.715694, 0.029557, 1.0],
+            [0.982251, 0.799103, 0.0185, 1.0],
+        ]),
+    };
+
+    /// The inferno colormap, from black through purple and orange to pale yellow.
+    pub const INFERNO: Palette = Palette {
+        colors: Cow::Borrowed(&[
+            [0.0, 0.0, 0.001214, 1.0],
+            [0.01096, 0.003677, 0.052861, 1.0],
+            [0.068478, 0.003677, 0.147027, 1.0],
+            [0.187821, 0.011612, 0.152926, 1.0],
+            [0.376262, 0.025187, 0.116971, 1.0],
+            [0.62396, 0.057805, 0.061246, 1.0],
+            [0.846873, 0.141263, 0.0185, 1.0],
+            [0.964686, 0.327778, 0.001821, 1.0],
+            [0.930111, 0.637597, 0.046665, 1.0],
+            [0.973445, 1.0, 0.371238, 1.0],
+        ]),
+    };
+
+    /// The cool colormap, from cyan to magenta.
+    pub const COOL: Palette = Palette {
+        colors: Cow::Borrowed(&[[0.0, 1.0, 1.0, 1.0], [1.0, 0.0, 1.0, 1.0]]),
+    };
+
+    /// Parses a palette in the hex format Lospec exports: one sRGB hex color per line, with an
+    /// optional `#`. The paint.net format, with `AARRGGBB` colors and `;` comments, is also
+    /// accepted.
+    pub fn from_lospec(source: &str) -> Result<Palette> {
+        let mut colors = vec![];
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+
+            let hex = line.trim_start_matches('#');
+            // `from_str_radix()` alone would accept a leading sign.
+            let digits =
+                (hex.len() == 6 || hex.len() == 8) && hex.bytes().all(|b| b.is_ascii_hexdigit());
+            let value = match u32::from_str_radix(hex, 16) {
+                Ok(value) if digits => value,
+                _ => bail!("Line {} of palette is not a hex color: {:?}", i + 1, line),
+            };
+            let byte = |shift: u32| decode(((value >> shift) & 0xff) as f32 / 255.);
+            let alpha = if hex.len() == 8 {
+                (value >> 24) as f32 / 255.
+            } else {
+                1.
+            };
+            colors.push([byte(16), byte(8), byte(0), alpha]);
+        }
+
+        if colors.is_empty() {
+            bail!("Palette has no colors");
+        }
+        Ok(Palette::from(colors))
+    }
+
+    /// The number of colors in the palette.
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    /// Whether the palette has no colors.
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    /// The colors of the palette, in order.
+    pub fn colors(&self) -> &[[f32; 4]] {
+        &self.colors
+    }
+
+    /// Returns the color at the index. Panics if the index is out of range.
+    pub fn get(&self, i: usize) -> [f32; 4] {
+        self.colors[i]
+    }
+
+    /// Returns the color at the index, wrapping around past the end. Panics if the palette is
+    /// empty.
+    pub fn cycle(&self, i: usize) -> [f32; 4] {
+        self.colors[i % self.colors.len()]
+    }
+
+    /// Samples the palette as a gradient through its colors, with `t` of 0 giving the first and
+    /// 1 the last. `t` is clamped into [0, 1]. Panics if the palette is empty.
+    pub fn sample(&self, t: f32) -> [f32; 4] {
+        let last = self.colors.len() - 1;
+        let position = t.clamp(0., 1.) * last as f32;
+        let i = (position as usize).min(last.saturating_sub(1));
+        let (a, b) = (self.colors[i], self.colors[(i + 1).min(last)]);
+        let t = position - i as f32;
+
+        let mut color = [0.; 4];
+        for (c, channel) in color.iter_mut().enumerate() {
+            *channel = a[c] + (b[c] - a[c]) * t;
+        }
+        color
+    }
+}
+
+impl From<Vec<[f32; 4]>> for Palette {
+    fn from(colors: Vec<[f32; 4]>) -> Self {
+        Self {
+            colors: Cow::Owned(colors),
+        }
+    }
+}
+
+fn wrap_hue(h: f32) -> f32 {
+    let h = h.rem_euclid(360.);
+    // Tiny negative hues round up to exactly 360.
+    if h >= 360. {
+        0.
+    } else {
+        h
+    }
+}
+
+fn encode(linear: f32) -> f32 {
+    <Srgb as TransferFn>::from_linear(linear.clamp(0., 1.))
+}
+
+fn decode(encoded: f32) -> f32 {
+    <Srgb as TransferFn>::into_linear(encoded.clamp(0., 1.))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_close(a: [f32; 4], b: [f32; 4]) {
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert!((a - b).abs() < 1e-4, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn primaries() {
+        assert_close(Hsl::new(0., 1., 0.5).into(), [1., 0., 0., 1.]);
+        assert_close(Hsl::new(120., 1., 0.5).into(), [0., 1., 0., 1.]);
+        assert_close(Hsv::new(240., 1., 1.).into(), [0., 0., 1., 1.]);
+        assert_close(Hsv::new(-360., 0., 1.).into(), [1., 1., 1., 1.]);
+    }
+
+    #[test]
+    fn round_trips() {
+        let rgba = [0.7, 0.2, 0.05, 1.];
+        assert_close(Hsl::from(rgba).into(), rgba);
+        assert_close(Hsv::from(rgba).into(), rgba);
+        assert_eq!(Hsl::new(350., 0.5, 0.5).rotate_hue(20.).h, 10.);
+    }
+
+    #[test]
+    fn oklch_round_trips() {
+        let white = Oklch::from([1., 1., 1.]);
+        assert!((white.l - 1.).abs() < 1e-3 && white.c < 1e-3);
+
+        let [r, g, b] = <[f32; 3]>::from(Oklch::from([0.7, 0.2, 0.05]));
+        assert_close([r, g, b, 1.], [0.7, 0.2, 0.05, 1.]);
+    }
+
+    #[test]
+    fn palette_access() {
+        let palette = Palette::from_lospec("; comment\n#000000\nffffff\n").unwrap();
+        assert_eq!(palette.len(), 2);
+        assert_eq!(palette.cycle(3), [1., 1., 1., 1.]);
+        assert_close(palette.sample(0.25), [0.25, 0.25, 0.25, 1.]);
+        assert_close(Palette::COOL.sample(1.), [1., 0., 1., 1.]);
+        assert!(Palette::from_lospec("#12345").is_err());
+        assert!(Palette::from_lospec("+fffff").is_err());
+        assert!(Palette::from_lospec("#+f00ff").is_err());
+    }
+
+    #[test]
+    fn oklch_lerp_takes_shorter_hue_arc() {
+        let a = Oklch::new(0.5, 0.1, 350.);
+        let b = Oklch::new(0.7, 0.1, 30.);
+        let mid = Oklch::lerp(&a, &b, 0.5);
+        assert!((mid.h_deg - 10.).abs() < 1e-4);
+        assert!((mid.l - 0.6).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,144 @@
+//! Easing functions for animation.
+//!
+//! Each maps progress `t` in [0, 1] to eased progress, starting at 0 and ending at 1. Progress
+//! outside [0, 1] is clamped.
+
+use std::f32::consts::PI;
+
+/// An easing function, to choose one at runtime or store one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Easing {
+    /// Progress is unchanged.
+    #[default]
+    Linear,
+    InQuad,
+    OutQuad,
+    InOutCubic,
+    InExpo,
+    OutBounce,
+    InOutElastic,
+    SmoothStep,
+}
+
+impl Easing {
+    /// Eases the progress with this function.
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => clamp(t),
+            Easing::InQuad => ease_in_quad(t),
+            Easing::OutQuad => ease_out_quad(t),
+            Easing::InOutCubic => ease_in_out_cubic(t),
+            Easing::InExpo => ease_in_expo(t),
+            Easing::OutBounce => ease_out_bounce(t),
+            Easing::InOutElastic => ease_in_out_elastic(t),
+            Easing::SmoothStep => smooth_step(t),
+        }
+    }
+}
+
+/// Accelerates from rest.
+pub fn ease_in_quad(t: f32) -> f32 {
+    let t = clamp(t);
+    t * t
+}
+
+/// Decelerates to rest.
+pub fn ease_out_quad(t: f32) -> f32 {
+    let t = clamp(t);
+    t * (2. - t)
+}
+
+/// Accelerates from rest to halfway, then decelerates to rest.
+pub fn ease_in_out_cubic(t: f32) -> f32 {
+    let t = clamp(t);
+    if t < 0.5 {
+        4. * t * t * t
+    } else {
+        1. - (-2. * t + 2.).powi(3) / 2.
+    }
+}
+
+/// Accelerates exponentially from rest.
+pub fn ease_in_expo(t: f32) -> f32 {
+    let t = clamp(t);
+    if t == 0. {
+        0.
+    } else {
+        2f32.powf(10. * t - 10.)
+    }
+}
+
+/// Decelerates as if bouncing to rest.
+pub fn ease_out_bounce(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+
+    let t = clamp(t);
+    if t < 1. / D {
+        N * t * t
+    } else if t < 2. / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984_375
+    }
+}
+
+/// Winds up and springs past the ends as if on an elastic, so unlike the others it leaves
+/// [0, 1] along the way.
+pub fn ease_in_out_elastic(t: f32) -> f32 {
+    const PERIOD: f32 = 2. * PI / 4.5;
+
+    let t = clamp(t);
+    if t == 0. || t == 1. {
+        t
+    } else if t < 0.5 {
+        -(2f32.powf(20. * t - 10.) * ((20. * t - 11.125) * PERIOD).sin()) / 2.
+    } else {
+        2f32.powf(-20. * t + 10.) * ((20. * t - 11.125) * PERIOD).sin() / 2. + 1.
+    }
+}
+
+/// Hermite interpolation, with zero slope at both ends.
+pub fn smooth_step(t: f32) -> f32 {
+    let t = clamp(t);
+    t * t * (3. - 2. * t)
+}
+
+/// Interpolates linearly from `a` to `b` by `t`, clamped to [0, 1].
+pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * clamp(t)
+}
+
+fn clamp(t: f32) -> f32 {
+    t.clamp(0., 1.)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn easings_span_unit_interval() {
+        let easings: [fn(f32) -> f32; 7] = [
+            ease_in_quad,
+            ease_out_quad,
+            ease_in_out_cubic,
+            ease_in_expo,
+            ease_out_bounce,
+            ease_in_out_elastic,
+            smooth_step,
+        ];
+        for ease in &easings {
+            assert_eq!(ease(-1.), 0.);
+            assert!((ease(1.) - 1.).abs() < 1e-6);
+            assert!((ease(2.) - 1.).abs() < 1e-6);
+        }
+        assert_eq!(lerp(2., 4., 0.25), 2.5);
+        assert_eq!(lerp(2., 4., 1.5), 4.);
+    }
+}
@@ -0,0 +1,138 @@
+//! Flow fields for tracing curves.
+
+use crate::{Rect, Result, P2, V2};
+use failure::bail;
+use noise::{NoiseFn, Perlin, Seedable};
+use std::f32::consts::PI;
+
+/// A field of directions, given as the angle in radians from the positive x axis at each point.
+pub struct FlowField {
+    angle: Box<dyn Fn(P2) -> f32>,
+}
+
+impl FlowField {
+    pub fn new(angle: impl Fn(P2) -> f32 + 'static) -> Self {
+        Self {
+            angle: Box::new(angle),
+        }
+    }
+
+    /// Creates a field whose angles follow Perlin noise sampled at points multiplied by `scale`,
+    /// so smaller scales give broader currents. Noise in [-1, 1] is mapped to angles in
+    /// [-2π, 2π].
+    pub fn from_perlin(scale: f32, seed: u32) -> Self {
+        let perlin = Perlin::new().set_seed(seed);
+        Self::new(move |p| {
+            perlin.get([f64::from(p.x * scale), f64::from(p.y * scale)]) as f32 * 2. * PI
+        })
+    }
+
+    /// Creates a field from angles at the centers of the cells of a grid over the bounds, given
+    /// row by row from the bottom row and from the left within each row. Directions between
+    /// centers are interpolated bilinearly, and held at the nearest center outside them.
+    ///
+    /// Fails if the number of angles is not `cols * rows`, or if there are none.
+    pub fn from_grid(angles: &[f32], cols: u32, rows: u32, bounds: Rect) -> Result<Self> {
+        let (cols, rows) = (cols as usize, rows as usize);
+        if angles.is_empty() || angles.len() != cols * rows {
+            bail!(
+                "A {}x{} flow field grid needs {} angles; got {}",
+                cols,
+                rows,
+                cols * rows,
+                angles.len()
+            );
+        }
+
+        // Angles are interpolated as unit vectors, so that they do not sweep the long way around
+        // between angles on either side of ±π.
+        let directions: Vec<V2> = angles
+            .iter()
+            .map(|angle| V2::new(angle.cos(), angle.sin()))
+            .collect();
+        Ok(Self::new(move |p| {
+            // The position in cells, with cell centers at whole numbers.
+            let cell = |v: f32, start: f32, size: f32, count: usize| {
+                (((v - start) / size * count as f32 - 0.5).max(0.)).min((count - 1) as f32)
+            };
+            let x = cell(p.x, bounds.bottom_left.x, bounds.width, cols);
+            let y = cell(p.y, bounds.bottom_left.y, bounds.height, rows);
+            let (col, row) = (x.floor() as usize, y.floor() as usize);
+            let (next_col, next_row) = ((col + 1).min(cols - 1), (row + 1).min(rows - 1));
+            let (tx, ty) = (x - col as f32, y - row as f32);
+
+            let at = |col: usize, row: usize| directions[row * cols + col];
+            let bottom = at(col, row).lerp(at(next_col, row), tx);
+            let top = at(col, next_row).lerp(at(next_col, next_row), tx);
+            let direction = bottom.lerp(top, ty);
+            direction.y.atan2(direction.x)
+        }))
+    }
+
+    /// Returns the angle of the field at the point.
+    pub fn angle_at(&self, p: P2) -> f32 {
+        (self.angle)(p)
+    }
+
+    /// Traces the path of a particle carried along the field from `start` with the fourth order
+    /// Runge-Kutta method, taking up to `max_steps` steps of `step_size`.
+    ///
+    /// The path begins with `start` and has a point after each step. Tracing stops early if the
+    /// field is not finite. Paint the path with `polyline()`, or `smooth_polyline()` for curves
+    /// between the points.
+    pub fn integrate(&self, start: P2, step_size: f32, max_steps: usize) -> Vec<P2> {
+        let direction = |p: P2| {
+            let angle = self.angle_at(p);
+            V2::new(angle.cos(), angle.sin())
+        };
+
+        let mut points = Vec::with_capacity(max_steps + 1);
+        points.push(start);
+        let mut p = start;
+        for _ in 0..max_steps {
+            let k1 = direction(p);
+            let k2 = direction(p + k1 * (step_size / 2.));
+            let k3 = direction(p + k2 * (step_size / 2.));
+            let k4 = direction(p + k3 * step_size);
+            let next = p + (k1 + k2 * 2. + k3 * 2. + k4) * (step_size / 6.);
+            if !next.x.is_finite() || !next.y.is_finite() {
+                break;
+            }
+            points.push(next);
+            p = next;
+        }
+        points
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn integrates_around_a_vortex() {
+        // Every direction is tangent to a circle around the origin.
+        let vortex = FlowField::new(|p| p.y.atan2(p.x) + PI / 2.);
+        let path = vortex.integrate(P2::new(1., 0.), 0.1, 63);
+
+        assert_eq!(path.len(), 64);
+        assert!(path
+            .iter()
+            .all(|p| (p.to_vector().length() - 1.).abs() < 1e-3));
+        assert!((*path.last().unwrap() - P2::new(1., 0.)).length() < 0.05);
+    }
+
+    #[test]
+    fn grid_fields_interpolate_directions() {
+        let bounds = Rect {
+            bottom_left: P2::new(0., 0.),
+            width: 2.,
+            height: 1.,
+        };
+        let field = FlowField::from_grid(&[PI - 0.1, -PI + 0.1], 2, 1, bounds).unwrap();
+
+        assert!((field.angle_at(P2::new(1., 0.5)).abs() - PI).abs() < 1e-5);
+        assert!((field.angle_at(P2::new(-5., 0.5)) - (PI - 0.1)).abs() < 1e-5);
+        assert!(FlowField::from_grid(&[0.; 3], 2, 1, bounds).is_err());
+    }
+}
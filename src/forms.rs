@@ -2,7 +2,13 @@
 
 mod ellipse;
 mod ngon;
+mod parametric;
 mod polygon;
 mod rect;
+mod rounded_rect;
+mod sector;
+mod star;
 
-pub use self::{ellipse::*, ngon::*, polygon::*, rect::*};
+pub use self::{
+    ellipse::*, ngon::*, parametric::*, polygon::*, rect::*, rounded_rect::*, sector::*, star::*,
+};
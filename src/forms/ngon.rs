@@ -1,6 +1,7 @@
 // Regular ngons.
 
-use crate::{Angle, Canvas, FlatIterPath, Paint, Rotate, Scale, Translate, P2, V2};
+use crate::{Angle, Canvas, FlatIterPath, Paint, Result, Rotate, Scale, Translate, P2, V2};
+use failure::bail;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Ngon {
@@ -22,6 +23,22 @@ impl Ngon {
         }
     }
 
+    /// Returns a regular polygon whose first vertex is at `rotation` counter-clockwise from the
+    /// positive x axis.
+    ///
+    /// Fails if there are fewer than three sides.
+    pub fn regular(center: P2, circumradius: f32, sides: usize, rotation: Angle) -> Result<Self> {
+        if sides < 3 {
+            bail!("A regular polygon needs at least 3 sides; got {}", sides);
+        }
+
+        // Ngon phases are measured clockwise from the positive y axis.
+        Ok(Self {
+            phase: Angle::frac_pi_2() - rotation,
+            ..Self::new(center, sides, circumradius)
+        })
+    }
+
     pub fn triangle(center: P2, radius: f32) -> Self {
         Self::new(center, 3, radius)
     }
@@ -90,6 +107,6 @@ impl Rotate for Ngon {
 
 impl Paint for Ngon {
     fn paint(&self, comp: &mut Canvas) {
-        comp.paint(FlatIterPath::new(*self, /*closed=*/ true))
+        comp.paint(FlatIterPath::new(*self, /*closed=*/ true));
     }
 }
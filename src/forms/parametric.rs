@@ -0,0 +1,76 @@
+//! Parametric curves.
+
+use crate::{Canvas, Paint, P2};
+
+/// The default number of segments a `ParametricCurve` is sampled into.
+pub const DEFAULT_PARAMETRIC_RESOLUTION: u32 = 256;
+
+/// An open curve traced by a function from a parameter `t` to points, painted as straight lines
+/// between `f` sampled at `resolution` uniform steps from `t_start` to `t_end`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ParametricCurve<F> {
+    pub f: F,
+    pub t_start: f32,
+    pub t_end: f32,
+    pub resolution: u32,
+}
+
+impl<F: Fn(f32) -> P2> ParametricCurve<F> {
+    /// Creates a curve with the default resolution, `DEFAULT_PARAMETRIC_RESOLUTION`.
+    pub fn new(f: F, t_start: f32, t_end: f32) -> Self {
+        Self {
+            f,
+            t_start,
+            t_end,
+            resolution: DEFAULT_PARAMETRIC_RESOLUTION,
+        }
+    }
+
+    /// Returns the curve sampled in the given number of steps instead.
+    pub fn with_resolution(self, resolution: u32) -> Self {
+        Self { resolution, ..self }
+    }
+
+    /// Returns the points the curve is sampled at, which include both ends.
+    pub fn samples(&self) -> impl Iterator<Item = P2> + '_ {
+        let steps = self.resolution.max(1);
+        (0..=steps).map(move |i| {
+            let t = self.t_start + (self.t_end - self.t_start) * (i as f32 / steps as f32);
+            (self.f)(t)
+        })
+    }
+}
+
+impl<F: Fn(f32) -> P2> Paint for ParametricCurve<F> {
+    fn paint(&self, canvas: &mut Canvas) {
+        let mut samples = self.samples();
+        if let Some(start) = samples.next() {
+            canvas.move_to(start);
+        }
+        for p in samples {
+            canvas.line_to(p);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn samples_include_both_ends() {
+        let curve = ParametricCurve::new(|t| P2::new(t, t * t), -1., 1.).with_resolution(4);
+        let samples: Vec<P2> = curve.samples().collect();
+
+        assert_eq!(
+            samples,
+            vec![
+                P2::new(-1., 1.),
+                P2::new(-0.5, 0.25),
+                P2::new(0., 0.),
+                P2::new(0.5, 0.25),
+                P2::new(1., 1.),
+            ]
+        );
+    }
+}
@@ -1,19 +1,47 @@
 //! Polygon.
 
 use crate::{
-    Angle, Canvas, Center, Ellipse, FlatIterPath, Paint, Rect, Rotate, Scale, Subdivide, Translate,
-    P2, V2,
+    path::{bounding_box, simplify},
+    Angle, Canvas, Center, Ellipse, FlatIterPath, Paint, Rect, Result, Rotate, Scale, Subdivide,
+    Translate, P2, V2,
 };
 use arrayvec::ArrayVec;
+use failure::bail;
+use geo::{
+    algorithm::buffer::{Buffer, BufferStyle, LineJoin},
+    BooleanOps,
+};
 use itertools::Itertools;
+use lyon_path::PathEvent;
 use std::iter::{DoubleEndedIterator, FromIterator};
 
+/// The default miter limit of `Polygon::offset()` and of stroke joins, as in SVG.
+pub const DEFAULT_MITER_LIMIT: f32 = 4.;
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Polygon {
     vertices: Vec<P2>,
 }
 
 impl Polygon {
+    /// Returns a superellipse, the curve `|x/a|^n + |y/b|^n = 1` around the center, sampled at
+    /// `resolution` points winding counter-clockwise from the positive x axis.
+    ///
+    /// `n` must be positive. At 2 this is an ellipse with radii `a` and `b`, at 1 a diamond, and
+    /// as `n` grows it approaches a `2a` by `2b` rectangle; below 1 the sides curve inwards.
+    pub fn superellipse(center: P2, a: f32, b: f32, n: f32, resolution: u32) -> Polygon {
+        let resolution = resolution.max(3);
+        let exponent = 2. / n;
+        let component = |v: f32| v.signum() * v.abs().powf(exponent);
+        (0..resolution)
+            .map(|i| {
+                let theta = 2. * std::f32::consts::PI * i as f32 / resolution as f32;
+                center + V2::new(a * component(theta.cos()), b * component(theta.sin()))
+            })
+            .collect()
+    }
+
     /// Returns an iterator over the polygon's vertices.
     pub fn vertices<'a>(&'a self) -> impl DoubleEndedIterator<Item = P2> + Clone + 'a {
         self.vertices.iter().copied()
@@ -33,11 +61,201 @@ impl Polygon {
             .chain(last_iter)
             .tuple_windows()
     }
+
+    /// Returns the smallest rectangle containing the polygon, or `None` if it has no vertices.
+    pub fn bounding_box(&self) -> Option<Rect> {
+        bounding_box(FlatIterPath::new(self.vertices(), /*closed=*/ true))
+    }
+
+    /// Returns the area of the polygon, which is positive if its vertices wind counter-clockwise
+    /// and negative if they wind clockwise.
+    pub fn signed_area(&self) -> f32 {
+        let next = self.vertices().skip(1).chain(self.vertices().take(1));
+        self.vertices()
+            .zip(next)
+            .map(|(a, b)| a.x * b.y - b.x * a.y)
+            .sum::<f32>()
+            / 2.
+    }
+
+    /// Returns the area of the polygon.
+    pub fn area(&self) -> f32 {
+        self.signed_area().abs()
+    }
+
+    /// Simplifies the polygon with `simplify()`, treating it as a polyline which returns to its
+    /// first vertex.
+    pub fn simplify(&self, epsilon: f32) -> Polygon {
+        let mut ring = self.vertices.clone();
+        ring.extend(self.vertices.first());
+        let mut vertices = simplify(&ring, epsilon);
+        vertices.pop();
+        Polygon { vertices }
+    }
+
+    /// Returns the regions covered by either polygon, each with its holes. Disjoint regions are
+    /// returned separately.
+    ///
+    /// Fails if either polygon has fewer than three vertices.
+    pub fn union(&self, other: &Polygon) -> Result<Vec<Region>> {
+        self.boolean(other, "union", |a, b| a.union(b))
+    }
+
+    /// Returns the regions covered by both polygons, which are none if they do not overlap.
+    /// Regions are returned as with `union()`.
+    ///
+    /// Fails if either polygon has fewer than three vertices.
+    pub fn intersection(&self, other: &Polygon) -> Result<Vec<Region>> {
+        self.boolean(other, "intersection", |a, b| a.intersection(b))
+    }
+
+    /// Returns the regions covered by this polygon but not `other`, so `other` cuts a hole out of
+    /// this polygon whatever its winding. `a.difference(&b)` is not `b.difference(&a)`. Regions
+    /// are returned as with `union()`.
+    ///
+    /// Fails if either polygon has fewer than three vertices.
+    pub fn difference(&self, other: &Polygon) -> Result<Vec<Region>> {
+        self.boolean(other, "difference", |a, b| a.difference(b))
+    }
+
+    /// Returns the polygon grown outward by `amount`, or shrunk inward if `amount` is negative,
+    /// with mitered corners limited by `DEFAULT_MITER_LIMIT`. Shrinking may split the polygon
+    /// into several regions. Regions are returned as with `union()`.
+    ///
+    /// Fails if the polygon has fewer than three vertices.
+    pub fn offset(&self, amount: f32) -> Result<Vec<Region>> {
+        self.offset_with_miter_limit(amount, DEFAULT_MITER_LIMIT)
+    }
+
+    /// Like `offset()`, but with a miter limit: the longest a mitered corner may extend from the
+    /// original corner, as a multiple of `amount`, before it is cut off.
+    ///
+    /// Fails if the polygon has fewer than three vertices or the miter limit is less than one.
+    pub fn offset_with_miter_limit(&self, amount: f32, miter_limit: f32) -> Result<Vec<Region>> {
+        self.check_area("offset")?;
+        if miter_limit < 1. {
+            bail!("Miter limit must be at least 1; got {}", miter_limit);
+        }
+
+        // The buffer takes the sharpest angle which is still mitered, rather than a limit.
+        let min_angle = 2. * (1. / miter_limit).asin();
+        let style = BufferStyle::new(amount).line_join(LineJoin::Miter(min_angle));
+        Ok(regions(self.to_geo().buffer_with_style(style)))
+    }
+
+    fn boolean(
+        &self,
+        other: &Polygon,
+        name: &str,
+        op: impl Fn(&geo::Polygon<f32>, &geo::Polygon<f32>) -> geo::MultiPolygon<f32>,
+    ) -> Result<Vec<Region>> {
+        self.check_area(name)?;
+        other.check_area(name)?;
+        Ok(regions(op(&self.to_geo(), &other.to_geo())))
+    }
+
+    fn check_area(&self, operation: &str) -> Result<()> {
+        if self.vertices.len() < 3 {
+            bail!(
+                "A polygon needs at least 3 vertices for {}; got {}",
+                operation,
+                self.vertices.len()
+            );
+        }
+        Ok(())
+    }
+
+    fn to_geo(&self) -> geo::Polygon<f32> {
+        geo::Polygon::new(
+            self.vertices()
+                .map(|v| geo::coord! { x: v.x, y: v.y })
+                .collect(),
+            vec![],
+        )
+    }
+}
+
+/// Returns the convex hull of the points with its vertices in counter-clockwise order, or `None`
+/// if the points do not span an area. Points along the edges of the hull are not vertices of it.
+pub fn convex_hull(points: &[P2]) -> Option<Polygon> {
+    let mut points: Vec<P2> = points.to_vec();
+    points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    points.dedup();
+
+    // Andrew's monotone chain: build the lower and then the upper hull.
+    let mut hull: Vec<P2> = Vec::with_capacity(points.len() + 1);
+    extend_hull(&mut hull, points.iter());
+    extend_hull(&mut hull, points.iter().rev());
+
+    if hull.len() < 3 {
+        return None;
+    }
+    Some(Polygon { vertices: hull })
+}
+
+/// Appends a chain of the hull through the sorted points, keeping only left turns. The last point
+/// of the chain is left off, as it begins the next chain.
+fn extend_hull<'a>(hull: &mut Vec<P2>, points: impl Iterator<Item = &'a P2>) {
+    let start = hull.len();
+    for &p in points {
+        while hull.len() >= start + 2
+            && (hull[hull.len() - 1] - hull[hull.len() - 2]).cross(p - hull[hull.len() - 2]) <= 0.
+        {
+            hull.pop();
+        }
+        hull.push(p);
+    }
+    hull.pop();
+}
+
+/// A region bounded by a polygon, with holes cut out of it where it is bounded by others, such
+/// as the result of a boolean operation on polygons.
+///
+/// Painting the region paints the outline and each hole as contours of one path, which fills
+/// only the area between them.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Region {
+    pub exterior: Polygon,
+    pub holes: Vec<Polygon>,
+}
+
+impl Region {
+    /// Returns the area of the region, less the area of its holes.
+    pub fn area(&self) -> f32 {
+        self.exterior.area() - self.holes.iter().map(Polygon::area).sum::<f32>()
+    }
+
+    /// Returns the path of the region: the exterior followed by each hole as closed subpaths of
+    /// one path, so that the holes are cut out of its fill.
+    pub fn path<'a>(&'a self) -> impl Iterator<Item = PathEvent> + Clone + 'a {
+        std::iter::once(&self.exterior)
+            .chain(&self.holes)
+            .flat_map(|polygon| FlatIterPath::new(polygon.vertices(), /*closed=*/ true))
+    }
+}
+
+/// Returns each polygon with its interior rings as holes.
+fn regions(polygons: geo::MultiPolygon<f32>) -> Vec<Region> {
+    // Rings from geo repeat their first vertex at the end.
+    let from_geo = |ring: &geo::LineString<f32>| -> Polygon {
+        let mut vertices: Vec<P2> = ring.coords().map(|c| P2::new(c.x, c.y)).collect();
+        vertices.pop();
+        Polygon { vertices }
+    };
+
+    polygons
+        .iter()
+        .map(|polygon| Region {
+            exterior: from_geo(polygon.exterior()),
+            holes: polygon.interiors().iter().map(from_geo).collect(),
+        })
+        .collect()
 }
 
 impl Paint for Polygon {
     fn paint(&self, canvas: &mut Canvas) {
-        canvas.paint(FlatIterPath::new(self.vertices(), /*closed=*/ true))
+        canvas.paint(FlatIterPath::new(self.vertices(), /*closed=*/ true));
     }
 }
 
@@ -47,6 +265,12 @@ impl Paint for &Polygon {
     }
 }
 
+impl Paint for Region {
+    fn paint(&self, canvas: &mut Canvas) {
+        canvas.paint(self.path());
+    }
+}
+
 impl Subdivide for Polygon {
     fn subdivide(self) -> Self {
         let from_start = self.vertices();
@@ -122,3 +346,147 @@ impl FromIterator<P2> for Polygon {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::raster::{raster_path, Method};
+    use lyon_path::{builder::PathBuilder, Path};
+    use palette::LinSrgba;
+
+    fn square(x: f32, y: f32) -> Polygon {
+        vec![
+            P2::new(x, y),
+            P2::new(x + 2., y),
+            P2::new(x + 2., y + 2.),
+            P2::new(x, y + 2.),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn superellipses_span_diamond_to_rectangle() {
+        let area = |n| Polygon::superellipse(P2::new(1., 1.), 2., 1., n, 400).signed_area();
+
+        assert!((area(1.) - 4.).abs() < 1e-3);
+        assert!((area(2.) - 2. * std::f32::consts::PI).abs() < 1e-2);
+        assert!(area(50.) > 7.8 && area(50.) < 8.);
+    }
+
+    #[test]
+    fn signed_area_is_positive_counter_clockwise() {
+        assert_eq!(square(0., 0.).signed_area(), 4.);
+        let clockwise: Polygon = square(0., 0.).vertices().rev().collect();
+        assert_eq!(clockwise.signed_area(), -4.);
+        assert_eq!(clockwise.area(), 4.);
+    }
+
+    #[test]
+    fn convex_hull_skips_interior_and_collinear_points() {
+        let points = [
+            P2::new(1., 1.),
+            P2::new(0., 0.),
+            P2::new(2., 2.),
+            P2::new(1., 0.),
+            P2::new(0., 2.),
+            P2::new(2., 0.),
+        ];
+        let hull: Vec<P2> = convex_hull(&points).unwrap().vertices().collect();
+        assert_eq!(
+            hull,
+            vec![
+                P2::new(0., 0.),
+                P2::new(2., 0.),
+                P2::new(2., 2.),
+                P2::new(0., 2.)
+            ]
+        );
+        assert!(convex_hull(&[P2::new(0., 0.), P2::new(1., 1.), P2::new(2., 2.)]).is_none());
+    }
+
+    #[test]
+    fn boolean_operations() {
+        assert_eq!(square(0., 0.).union(&square(5., 5.)).unwrap().len(), 2);
+        assert_eq!(square(0., 0.).union(&square(0., 0.)).unwrap().len(), 1);
+
+        let merged = square(0., 0.).union(&square(1., 1.)).unwrap();
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].exterior.vertices().count(), 8);
+
+        assert!(square(0., 0.)
+            .intersection(&square(5., 5.))
+            .unwrap()
+            .is_empty());
+        let shared = square(0., 0.).intersection(&square(1., 1.)).unwrap();
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared[0].exterior.vertices().count(), 4);
+
+        let bar: Polygon = vec![
+            P2::new(0.5, -1.),
+            P2::new(1.5, -1.),
+            P2::new(1.5, 3.),
+            P2::new(0.5, 3.),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(square(0., 0.).difference(&bar).unwrap().len(), 2);
+        assert!(bar.difference(&bar).unwrap().is_empty());
+
+        let grown = square(0., 0.).offset(1.).unwrap();
+        assert_eq!(grown.len(), 1);
+        assert_eq!(Rect::extent(grown[0].exterior.vertices()).width, 4.);
+        assert!(square(0., 0.).offset(-1.5).unwrap().is_empty());
+
+        let line: Polygon = vec![P2::new(0., 0.), P2::new(1., 1.)].into_iter().collect();
+        assert!(square(0., 0.).union(&line).is_err());
+    }
+
+    #[test]
+    fn boolean_operations_keep_holes() {
+        let frame = vec![
+            P2::new(-1., -1.),
+            P2::new(3., -1.),
+            P2::new(3., 3.),
+            P2::new(-1., 3.),
+        ]
+        .into_iter()
+        .collect::<Polygon>()
+        .difference(&square(0., 0.))
+        .unwrap();
+
+        assert_eq!(frame.len(), 1);
+        assert_eq!(frame[0].holes.len(), 1);
+        assert_eq!(frame[0].holes[0].area(), 4.);
+        assert_eq!(frame[0].area(), 12.);
+    }
+
+    #[test]
+    fn regions_fill_around_their_holes() {
+        let region = Region {
+            exterior: vec![
+                P2::new(0., 0.),
+                P2::new(4., 0.),
+                P2::new(4., 4.),
+                P2::new(0., 4.),
+            ]
+            .into_iter()
+            .collect(),
+            holes: vec![square(1., 1.)],
+        };
+        let mut builder = Path::builder();
+        region.path().for_each(|event| builder.path_event(event));
+        let (vertices, indices) =
+            raster_path(builder.build(), Method::Fill, LinSrgba::new(1., 1., 1., 1.)).unwrap();
+
+        let mut area = 0.;
+        for triangle in indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| P2::from(vertices[triangle[i] as usize].vpos));
+            let centroid = P2::new((a.x + b.x + c.x) / 3., (a.y + b.y + c.y) / 3.);
+            assert!(!(1. < centroid.x && centroid.x < 3. && 1. < centroid.y && centroid.y < 3.));
+            area += (b - a).cross(c - a).abs() / 2.;
+        }
+        assert!((area - region.area()).abs() < 1e-3);
+        assert_eq!(region.area(), 12.);
+    }
+}
@@ -18,8 +18,8 @@ impl Paint for Rect {
 impl Rect {
     /// Returns a `Rect` containing all given vertices.
     pub fn extent(vertices: impl Iterator<Item = P2>) -> Self {
-        let mut min_x = FloatOrd(std::f32::MAX);
-        let mut min_y = FloatOrd(std::f32::MAX);
+        let mut min_x = FloatOrd(f32::MAX);
+        let mut min_y = FloatOrd(f32::MAX);
         let mut max_y = FloatOrd(0.);
         let mut max_x = FloatOrd(0.);
 
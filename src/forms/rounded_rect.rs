@@ -0,0 +1,97 @@
+//! Rounded rectangles.
+
+use crate::{Canvas, Center, Paint, Rect, Translate, P2, V2};
+
+/// The distance of bezier control points from the ends of a quarter circle of radius one that
+/// best approximates it.
+const QUARTER_CIRCLE_HANDLE: f32 = 0.552_284_8;
+
+/// A rectangle with quarter circle corners.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RoundedRect {
+    pub rect: Rect,
+    pub corner_radius: f32,
+}
+
+impl RoundedRect {
+    /// Rounds the corners of `rect`. Radii too large for the rectangle are clamped to half its
+    /// shorter side, which makes a stadium.
+    pub fn new(rect: Rect, corner_radius: f32) -> Self {
+        Self {
+            rect,
+            corner_radius: corner_radius.min(rect.width.min(rect.height) / 2.).max(0.),
+        }
+    }
+}
+
+impl Paint for RoundedRect {
+    fn paint(&self, canvas: &mut Canvas) {
+        let r = self.corner_radius;
+        if r <= 0. {
+            canvas.paint(self.rect);
+            return;
+        }
+
+        let Rect {
+            bottom_left,
+            width,
+            height,
+        } = self.rect;
+        let (left, bottom) = (bottom_left.x, bottom_left.y);
+        let (right, top) = (left + width, bottom + height);
+        let handle = r * QUARTER_CIRCLE_HANDLE;
+        let corner = |canvas: &mut Canvas, from: P2, around: P2, to: P2| {
+            canvas.cubic_to(
+                from + (around - from).normalize() * handle,
+                to + (around - to).normalize() * handle,
+                to,
+            );
+        };
+
+        canvas.move_to(P2::new(left + r, bottom));
+        canvas.line_to(P2::new(right - r, bottom));
+        corner(
+            canvas,
+            P2::new(right - r, bottom),
+            P2::new(right, bottom),
+            P2::new(right, bottom + r),
+        );
+        canvas.line_to(P2::new(right, top - r));
+        corner(
+            canvas,
+            P2::new(right, top - r),
+            P2::new(right, top),
+            P2::new(right - r, top),
+        );
+        canvas.line_to(P2::new(left + r, top));
+        corner(
+            canvas,
+            P2::new(left + r, top),
+            P2::new(left, top),
+            P2::new(left, top - r),
+        );
+        canvas.line_to(P2::new(left, bottom + r));
+        corner(
+            canvas,
+            P2::new(left, bottom + r),
+            P2::new(left, bottom),
+            P2::new(left + r, bottom),
+        );
+        canvas.close_path();
+    }
+}
+
+impl Center for RoundedRect {
+    fn center(&self) -> P2 {
+        self.rect.center()
+    }
+}
+
+impl Translate for RoundedRect {
+    fn translate(self, offset: V2) -> Self {
+        Self {
+            rect: self.rect.translate(offset),
+            ..self
+        }
+    }
+}
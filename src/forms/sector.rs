@@ -0,0 +1,51 @@
+//! Circular sectors.
+
+use crate::{path::arc_curves, Angle, Canvas, Paint, Translate, P2, V2};
+
+/// A wedge of a circle between two angles, measured counter-clockwise from the positive x axis.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sector {
+    pub center: P2,
+    pub radius: f32,
+    pub start_angle: Angle,
+    pub end_angle: Angle,
+}
+
+impl Sector {
+    pub fn new(center: P2, radius: f32, start_angle: Angle, end_angle: Angle) -> Self {
+        Self {
+            center,
+            radius,
+            start_angle,
+            end_angle,
+        }
+    }
+}
+
+impl Paint for Sector {
+    fn paint(&self, canvas: &mut Canvas) {
+        canvas.move_to(self.center);
+        canvas.line_to(
+            self.center
+                + V2::new(
+                    self.start_angle.radians.cos(),
+                    self.start_angle.radians.sin(),
+                ) * self.radius,
+        );
+        for (ctrl1, ctrl2, to) in
+            arc_curves(self.center, self.radius, self.start_angle, self.end_angle)
+        {
+            canvas.cubic_to(ctrl1, ctrl2, to);
+        }
+        canvas.close_path();
+    }
+}
+
+impl Translate for Sector {
+    fn translate(self, offset: V2) -> Self {
+        Self {
+            center: self.center + offset,
+            ..self
+        }
+    }
+}
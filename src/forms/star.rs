@@ -0,0 +1,97 @@
+//! Star polygons.
+
+use crate::{Angle, Canvas, FlatIterPath, Paint, Result, Rotate, Scale, Translate, P2, V2};
+use failure::bail;
+use std::f32::consts::PI;
+
+/// A star whose vertices alternate between points on an outer circle and notches on an inner
+/// circle.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Star {
+    pub center: P2,
+    pub outer_radius: f32,
+    pub inner_radius: f32,
+    pub points: usize,
+    /// The angle of the first point counter-clockwise from the positive x axis.
+    pub rotation: Angle,
+}
+
+impl Star {
+    /// Fails if there are fewer than two points or either radius is not positive.
+    pub fn new(
+        center: P2,
+        outer_radius: f32,
+        inner_radius: f32,
+        points: usize,
+        rotation: Angle,
+    ) -> Result<Self> {
+        if points < 2 {
+            bail!("A star needs at least 2 points; got {}", points);
+        }
+        if outer_radius <= 0. || inner_radius <= 0. {
+            bail!(
+                "Star radii must be positive; got {} and {}",
+                outer_radius,
+                inner_radius
+            );
+        }
+
+        Ok(Self {
+            center,
+            outer_radius,
+            inner_radius,
+            points,
+            rotation,
+        })
+    }
+
+    /// Returns the vertices of the star in counter-clockwise order, beginning with the first
+    /// point.
+    pub fn vertices(&self) -> impl Iterator<Item = P2> + Clone {
+        let star = *self;
+        (0..star.points * 2).map(move |i| {
+            let theta = star.rotation.radians + i as f32 * PI / star.points as f32;
+            let radius = if i % 2 == 0 {
+                star.outer_radius
+            } else {
+                star.inner_radius
+            };
+            star.center + V2::new(theta.cos(), theta.sin()) * radius
+        })
+    }
+}
+
+impl Paint for Star {
+    fn paint(&self, canvas: &mut Canvas) {
+        canvas.paint(FlatIterPath::new(self.vertices(), /*closed=*/ true));
+    }
+}
+
+impl Scale for Star {
+    fn scale(self, factor: f32) -> Self {
+        Self {
+            outer_radius: self.outer_radius * factor,
+            inner_radius: self.inner_radius * factor,
+            ..self
+        }
+    }
+}
+
+impl Translate for Star {
+    fn translate(self, translation: V2) -> Self {
+        Self {
+            center: self.center + translation,
+            ..self
+        }
+    }
+}
+
+impl Rotate for Star {
+    fn rotate(self, pivot: P2, theta: Angle) -> Self {
+        Self {
+            center: self.center.rotate(pivot, theta),
+            rotation: self.rotation + theta,
+            ..self
+        }
+    }
+}
@@ -0,0 +1,281 @@
+//! Animated GIF export of rendered frames.
+
+use crate::Result;
+use failure::bail;
+use gif::{Encoder, Frame, Repeat, SetParameter};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+/// Encodes frames into a looping animated GIF.
+///
+/// GIF frames hold at most 256 colors, so each frame is quantized to its own palette with median
+/// cut. Transparency is not exported; frames are treated as opaque.
+pub struct GifExporter {
+    path: PathBuf,
+    fps: u32,
+    palette_size: u16,
+    encoder: Option<Encoder<BufWriter<File>>>,
+    dimensions: Option<(u32, u32)>,
+    /// The playback time of the frames written so far, in hundredths of a second.
+    centiseconds: u64,
+    frames: u64,
+    skipped_frames: u64,
+}
+
+impl GifExporter {
+    /// Creates an exporter which writes to the given path, playing frames back at `fps` with a
+    /// palette of up to `palette_size` colors per frame. The palette size is clamped to the
+    /// range GIF supports, [2, 256].
+    ///
+    /// The file is created when the first frame is added.
+    pub fn new(path: &Path, fps: u32, palette_size: u16) -> GifExporter {
+        GifExporter {
+            path: path.to_path_buf(),
+            fps: fps.max(1),
+            palette_size: palette_size.clamp(2, 256),
+            encoder: None,
+            dimensions: None,
+            centiseconds: 0,
+            frames: 0,
+            skipped_frames: 0,
+        }
+    }
+
+    /// Whether a frame of the given size can be added: the first frame sets the size of the
+    /// rest.
+    pub fn fits(&self, width: u32, height: u32) -> bool {
+        self.dimensions
+            .is_none_or(|dimensions| dimensions == (width, height))
+    }
+
+    /// Notes that a frame which does not fit was left out, warning about the first.
+    pub fn skip_frame(&mut self, width: u32, height: u32) {
+        if self.skipped_frames == 0 {
+            if let Some((gif_width, gif_height)) = self.dimensions {
+                eprintln!(
+                    "Leaving {}x{} frames out of {}x{} GIF {}",
+                    width,
+                    height,
+                    gif_width,
+                    gif_height,
+                    self.path.display()
+                );
+            }
+        }
+        self.skipped_frames += 1;
+    }
+
+    /// Appends a frame of sRGB encoded RGBA bytes, with rows from top to bottom.
+    ///
+    /// Every frame must have the size of the first.
+    pub fn add_frame(&mut self, rgba: &[u8], width: u32, height: u32) -> Result<()> {
+        if rgba.len() != width as usize * height as usize * 4 {
+            bail!(
+                "GIF frame of {}x{} needs {} bytes of RGBA; got {}",
+                width,
+                height,
+                width as usize * height as usize * 4,
+                rgba.len()
+            );
+        }
+        if width == 0 || height == 0 || width > u32::from(u16::MAX) || height > u32::from(u16::MAX)
+        {
+            bail!(
+                "GIF frames must be between 1x1 and 65535x65535; got {}x{}",
+                width,
+                height
+            );
+        }
+        match self.dimensions {
+            Some(dimensions) if dimensions != (width, height) => bail!(
+                "GIF frame is {}x{}, but earlier frames are {}x{}",
+                width,
+                height,
+                dimensions.0,
+                dimensions.1
+            ),
+            _ => {}
+        }
+
+        if self.encoder.is_none() {
+            let file = BufWriter::new(File::create(&self.path)?);
+            let mut encoder = Encoder::new(file, width as u16, height as u16, &[])?;
+            encoder.set(Repeat::Infinite)?;
+            self.encoder = Some(encoder);
+            self.dimensions = Some((width, height));
+        }
+
+        let colors: Vec<[u8; 3]> = rgba
+            .chunks_exact(4)
+            .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+            .collect();
+        let (palette, indices) = quantize(&colors, self.palette_size as usize);
+
+        let delay = frame_delay(self.frames, self.centiseconds, self.fps);
+        self.frames += 1;
+        self.centiseconds += u64::from(delay);
+        let frame = Frame {
+            width: width as u16,
+            height: height as u16,
+            delay,
+            palette: Some(palette.iter().flatten().copied().collect()),
+            buffer: indices.into(),
+            ..Frame::default()
+        };
+        self.encoder
+            .as_mut()
+            .expect("encoder to be created")
+            .write_frame(&frame)?;
+        Ok(())
+    }
+
+    /// Completes the file. Nothing is written if no frames were added.
+    pub fn finish(mut self) -> Result<()> {
+        // The encoder writes the GIF trailer when dropped.
+        self.encoder.take();
+        Ok(())
+    }
+}
+
+/// Returns the delay in hundredths of a second of the frame after `frames` frames, which have
+/// taken `centiseconds` to play, so that frames end as close as possible to when they would at
+/// `fps`. GIF delays are whole hundredths, so at most frame rates they alternate in length.
+fn frame_delay(frames: u64, centiseconds: u64, fps: u32) -> u16 {
+    let end = ((frames + 1) * 100 + u64::from(fps) / 2) / u64::from(fps);
+    end.saturating_sub(centiseconds)
+        .clamp(1, u64::from(u16::MAX)) as u16
+}
+
+/// Reduces the colors to a palette of at most `size` colors by median cut, returning the palette
+/// and the index of each color's nearest palette entry.
+fn quantize(colors: &[[u8; 3]], size: usize) -> (Vec<[u8; 3]>, Vec<u8>) {
+    let mut counts: HashMap<[u8; 3], u32> = HashMap::new();
+    for color in colors {
+        *counts.entry(*color).or_insert(0) += 1;
+    }
+
+    let mut boxes: Vec<Vec<([u8; 3], u32)>> = vec![counts.into_iter().collect()];
+    while boxes.len() < size {
+        // Split the box whose colors span the widest range of any channel.
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(i, colors)| {
+                let (channel, range) = (0..3)
+                    .map(|c| {
+                        let min = colors.iter().map(|(color, _)| color[c]).min().unwrap();
+                        let max = colors.iter().map(|(color, _)| color[c]).max().unwrap();
+                        (c, max - min)
+                    })
+                    .max_by_key(|(_, range)| *range)
+                    .unwrap();
+                (i, channel, range)
+            })
+            .max_by_key(|(_, _, range)| *range);
+        let (i, channel) = match widest {
+            Some((i, channel, _)) => (i, channel),
+            None => break,
+        };
+
+        let mut colors = boxes.swap_remove(i);
+        colors.sort_unstable_by_key(|(color, _)| color[channel]);
+        // Split at the median pixel, keeping at least one color on each side.
+        let total: u32 = colors.iter().map(|(_, count)| count).sum();
+        let mut seen = 0;
+        let median = colors
+            .iter()
+            .position(|(_, count)| {
+                seen += count;
+                seen * 2 >= total
+            })
+            .unwrap_or(0)
+            .min(colors.len() - 2);
+        let upper = colors.split_off(median + 1);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    let palette: Vec<[u8; 3]> = boxes
+        .iter()
+        .filter(|colors| !colors.is_empty())
+        .map(|colors| {
+            let total: u64 = colors.iter().map(|(_, count)| u64::from(*count)).sum();
+            let mut average = [0; 3];
+            for (c, channel) in average.iter_mut().enumerate() {
+                let sum: u64 = colors
+                    .iter()
+                    .map(|(color, count)| u64::from(color[c]) * u64::from(*count))
+                    .sum();
+                *channel = ((sum + total / 2) / total) as u8;
+            }
+            average
+        })
+        .collect();
+
+    let mut nearest: HashMap<[u8; 3], u8> = HashMap::new();
+    let indices = colors
+        .iter()
+        .map(|color| {
+            *nearest.entry(*color).or_insert_with(|| {
+                let distance = |entry: &[u8; 3]| -> i32 {
+                    (0..3)
+                        .map(|c| (i32::from(entry[c]) - i32::from(color[c])).pow(2))
+                        .sum()
+                };
+                (0..palette.len())
+                    .min_by_key(|i| distance(&palette[*i]))
+                    .unwrap_or(0) as u8
+            })
+        })
+        .collect();
+
+    (palette, indices)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quantize_keeps_few_colors_exact() {
+        let colors = [[255, 0, 0], [0, 0, 255], [255, 0, 0], [0, 255, 0]];
+        let (palette, indices) = quantize(&colors, 256);
+        assert_eq!(palette.len(), 3);
+        let mapped: Vec<[u8; 3]> = indices.iter().map(|i| palette[*i as usize]).collect();
+        assert_eq!(mapped, colors);
+    }
+
+    #[test]
+    fn frame_delays_keep_the_frame_rate() {
+        let delays = |fps| {
+            let mut centiseconds = 0;
+            (0..fps as u64)
+                .map(|frames| {
+                    let delay = frame_delay(frames, centiseconds, fps);
+                    centiseconds += u64::from(delay);
+                    delay
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(delays(30).iter().map(|d| u32::from(*d)).sum::<u32>(), 100);
+        assert!(delays(30).iter().all(|d| *d == 3 || *d == 4));
+        assert_eq!(delays(25), vec![4; 25]);
+        // Faster than GIFs can play, every frame takes the shortest delay.
+        assert_eq!(delays(200), vec![1; 200]);
+    }
+
+    #[test]
+    fn quantize_limits_palette() {
+        let colors: Vec<[u8; 3]> = (0..=255).map(|v| [v, v, v]).collect();
+        let (palette, indices) = quantize(&colors, 16);
+        assert_eq!(palette.len(), 16);
+        assert_eq!(indices[0], indices[1]);
+        assert_ne!(indices[0], indices[255]);
+    }
+}
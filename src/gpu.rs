@@ -1,32 +1,52 @@
 //! GPU handle and types.
 
 use crate::{
+    buffer_pool::{BufferPool, GeometryBuffers, DEFAULT_BUFFER_POOL_CAPACITY},
+    canvas::BlendMode,
     raster::{raster_path, Method},
+    shaders::{ShaderCompileError, WatchedProgram},
+    text::Coverage,
     uniforms::*,
-    Result,
+    Rect, Result, Transform, P2, V2,
 };
+use failure::bail;
+use float_ord::FloatOrd;
 use glium::{
     backend::{
         glutin::{headless::Headless, Display},
         Context, Facade,
     },
+    draw_parameters::{Stencil, StencilOperation, StencilTest},
+    framebuffer::SimpleFrameBuffer,
     glutin::event_loop::EventLoop,
     implement_vertex,
     index::PrimitiveType,
     texture::{
-        texture2d::Texture2d, texture2d_multisample::Texture2dMultisample, MipmapsOption,
-        RawImage2d, UncompressedFloatFormat,
+        texture2d::Texture2d, texture2d_multisample::Texture2dMultisample, ClientFormat,
+        DepthStencilTexture2dMultisample, MipmapsOption, RawImage2d, UncompressedFloatFormat,
     },
+    uniform,
     uniforms::{MagnifySamplerFilter, UniformValue, Uniforms},
     Blend, BlendingFunction, DrawParameters, Frame, IndexBuffer, LinearBlendingFactor, Program,
     Surface, VertexBuffer,
 };
 use glutin::dpi::PhysicalSize;
+use image::{ImageBuffer, Rgba};
 use itertools::Itertools;
-use lyon_path::Builder;
-use palette::LinSrgba;
+use palette::{
+    encoding::{srgb::Srgb, TransferFn},
+    Component, LinSrgba,
+};
 use rand::random;
-use std::rc::Rc;
+use rayon::prelude::*;
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Instant,
+};
 
 #[derive(Debug, Copy, Clone)]
 pub struct GpuVertex {
@@ -36,21 +56,43 @@ pub struct GpuVertex {
 
 implement_vertex!(GpuVertex, vpos, vcol);
 
+/// The transform of one instance of an instanced element, in output space.
+#[derive(Debug, Copy, Clone)]
+struct GpuInstance {
+    a_instance_transform: [[f32; 3]; 3],
+}
+
+implement_vertex!(GpuInstance, a_instance_transform);
+
+/// The attribute instanced shaders read the transform of each instance from.
+pub(crate) const INSTANCE_TRANSFORM_ATTRIBUTE: &str = "a_instance_transform";
+
 pub(crate) const TEXTURE_FORMAT: UncompressedFloatFormat = UncompressedFloatFormat::F32F32F32F32;
 
 pub const VERTEX_SHADER: &str = include_str!("shaders/default.vert");
 const FRAGMENT_SHADER: &str = include_str!("shaders/default.frag");
+const COMPOSITE_SHADER: &str = include_str!("shaders/composite.frag");
+const GRADIENT_SHADER: &str = include_str!("shaders/gradient.frag");
+const TEXT_SHADER: &str = include_str!("shaders/text.frag");
+const SOLID_SHADER: &str = include_str!("shaders/solid.frag");
+const IMAGE_SHADER: &str = include_str!("shaders/image.frag");
+const TEXTURE_PAINT_SHADER: &str = include_str!("shaders/texture_paint.frag");
+const INSTANCED_VERTEX_SHADER: &str = include_str!("shaders/instanced.vert");
 
-struct NoUniforms;
+/// The multisample counts which may be used for anti-aliasing, in ascending order.
+pub const SUPPORTED_SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
 
-impl Uniforms for NoUniforms {
-    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, _f: F) {}
-}
+/// The multisample count used for anti-aliasing unless another is requested.
+pub const DEFAULT_SAMPLE_COUNT: u32 = 4;
+
+/// The maximum number of color stops in a gradient.
+pub const MAX_GRADIENT_STOPS: usize = 16;
 
 #[derive(Default, Clone)]
 pub struct UniformBuffer {
     user_uniforms: Option<Rc<dyn OwnedUniforms>>,
     uniforms: Vec<(String, UniformValue<'static>)>,
+    textures: Vec<(String, Rc<Texture2d>)>,
 }
 
 impl UniformBuffer {
@@ -71,6 +113,12 @@ impl Uniforms for UniformBuffer {
         for (name, value) in &self.uniforms {
             f(name.as_str(), *value);
         }
+        for (name, texture) in &self.textures {
+            f(
+                name.as_str(),
+                UniformValue::Texture2d(texture.as_ref(), None),
+            );
+        }
     }
 }
 
@@ -82,24 +130,104 @@ where
         UniformBuffer {
             user_uniforms: Some(Rc::new(src)),
             uniforms: vec![],
+            textures: vec![],
         }
     }
 }
 
+/// A program shared by every shader built with it, so that a recompiled program can be swapped
+/// in for all of them at once.
+pub(crate) type SharedProgram = Rc<RefCell<Rc<Program>>>;
+
 /// A shader which can be used to shade paths. See `ShaderProgram` for construction.
 #[derive(Clone)]
 pub struct Shader {
     id: u64,
-    program: Rc<Program>,
+    program: SharedProgram,
     uniforms: UniformBuffer,
 }
 
+impl Shader {
+    /// Returns a copy of this shader with the texture bound to the named `sampler2D` uniform, in
+    /// addition to its other uniforms.
+    pub fn with_texture(&self, name: &str, texture: Rc<Texture2d>) -> Shader {
+        let mut uniforms = self.uniforms.clone();
+        uniforms.textures.push((String::from(name), texture));
+        Shader {
+            id: random(),
+            program: self.program.clone(),
+            uniforms,
+        }
+    }
+
+    /// Whether the shader's vertex stage reads instance transforms, as required by
+    /// `Canvas::draw_instanced()`.
+    pub(crate) fn is_instanced(&self) -> bool {
+        self.program
+            .borrow()
+            .get_attribute(INSTANCE_TRANSFORM_ATTRIBUTE)
+            .is_some()
+    }
+
+    /// Returns a copy of this shader with the value bound to the named uniform.
+    pub(crate) fn with_value(&self, name: &str, value: UniformValue<'static>) -> Shader {
+        let mut uniforms = self.uniforms.clone();
+        uniforms.push(String::from(name), value);
+        Shader {
+            id: random(),
+            program: self.program.clone(),
+            uniforms,
+        }
+    }
+}
+
 /// A rasterable element in a composition.
 pub struct Element {
-    pub path: Builder,
+    /// The path in output space.
+    pub path: lyon_path::Path,
     pub color: LinSrgba,
     pub raster_method: Method,
     pub shader: Shader,
+    /// Either `BlendMode::Normal` or `BlendMode::Add`, which can be done by the blending
+    /// hardware. Other modes are painted as layers.
+    pub blend: BlendMode,
+    /// The region of output space the element is clipped to, if any.
+    pub clip: Option<Rect>,
+    /// Whether the path is tessellated on rayon's worker threads, concurrently with the others
+    /// of its draw call. Only has an effect with the `parallel` feature; see
+    /// `Canvas::draw_batch_par()`.
+    pub parallel: bool,
+}
+
+/// The geometry of a gradient, in output space.
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum GradientGeometry {
+    Linear { start: P2, end: P2 },
+    Radial { center: P2, radius: f32 },
+}
+
+/// A group of drawables which are rendered together and composited onto the drawables beneath
+/// them.
+pub struct Layer {
+    pub opacity: f32,
+    pub blend: BlendMode,
+    /// A path in output space outside which the layer is transparent.
+    pub mask: Option<lyon_path::Path>,
+    pub drawables: Vec<Drawable>,
+}
+
+/// An element which is rasterized once and drawn at many transforms in a single draw call.
+pub struct Instanced {
+    pub element: Element,
+    /// The output space transforms of the instances.
+    pub transforms: Vec<Transform>,
+}
+
+/// An item in a composition.
+pub enum Drawable {
+    Element(Element),
+    Layer(Layer),
+    Instanced(Instanced),
 }
 
 pub struct DisplayFacade(Display);
@@ -114,6 +242,8 @@ pub trait FacadeExt: Facade {
     fn get_frame(&self) -> Option<Frame> {
         None
     }
+
+    fn set_title(&self, _title: &str) {}
 }
 
 impl FacadeExt for Headless {}
@@ -122,6 +252,10 @@ impl FacadeExt for DisplayFacade {
     fn get_frame(&self) -> Option<Frame> {
         Some(self.0.draw())
     }
+
+    fn set_title(&self, title: &str) {
+        self.0.gl_window().window().set_title(title);
+    }
 }
 
 /// A handle to the GPU for managing buffers and shaders.
@@ -129,20 +263,154 @@ impl FacadeExt for DisplayFacade {
 pub struct Gpu {
     pub(crate) ctx: Rc<dyn FacadeExt>,
     program: Rc<Program>,
+    composite_program: Rc<Program>,
+    gradient_program: Rc<Program>,
+    text_program: Rc<Program>,
+    solid_program: Rc<Program>,
+    image_program: Rc<Program>,
+    texture_paint_program: Rc<Program>,
+    instanced_program: Rc<Program>,
     height_sign: f32,
+    samples: u32,
+    buffer_pool: Rc<RefCell<BufferPool>>,
+    image_cache: Rc<RefCell<ImageCache>>,
+    metrics: Rc<Cell<FrameMetrics>>,
+    watched_programs: Rc<RefCell<Vec<WatchedProgram>>>,
+}
+
+/// Textures loaded by `Gpu::load_image()`, by canonical path and whether they have mipmaps.
+type ImageCache = HashMap<(PathBuf, bool), Rc<Texture2d>>;
+
+/// Measurements of the rendering of a frame, for finding where time goes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct FrameMetrics {
+    /// The wall-clock time spent tessellating paths into triangles.
+    pub tessellation_ns: u64,
+    /// The wall-clock time spent uploading vertices and indices to the GPU, which is near zero
+    /// for geometry reused from the buffer pool.
+    pub gpu_upload_ns: u64,
+    /// The number of draw calls issued, including those compositing layers.
+    pub draw_call_count: u32,
+    /// The number of vertices tessellated.
+    pub vertex_count: u64,
+}
+
+impl FrameMetrics {
+    /// Prints the metrics on one line to stderr.
+    pub fn print_summary(&self) {
+        eprintln!(
+            "tessellation {:.2}ms, upload {:.2}ms, {} draw calls, {} vertices",
+            self.tessellation_ns as f64 / 1e6,
+            self.gpu_upload_ns as f64 / 1e6,
+            self.draw_call_count,
+            self.vertex_count
+        );
+    }
+}
+
+/// How images are uploaded into textures.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct TextureOptions {
+    /// Whether to generate mipmaps, which smooth the texture where it is shrunk a lot on screen
+    /// at the cost of a third more memory.
+    pub mipmaps: bool,
+}
+
+/// Creates an event loop for a headless context, which unlike a window needs no main thread.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn headless_event_loop() -> EventLoop<()> {
+    use glium::glutin::platform::unix::EventLoopExtUnix;
+    EventLoop::new_any_thread()
+}
+
+/// Creates an event loop for a headless context, which unlike a window needs no main thread.
+#[cfg(target_os = "windows")]
+fn headless_event_loop() -> EventLoop<()> {
+    use glium::glutin::platform::windows::EventLoopExtWindows;
+    EventLoop::new_any_thread()
+}
+
+/// Creates an event loop for a headless context.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "windows"
+)))]
+fn headless_event_loop() -> EventLoop<()> {
+    EventLoop::new()
+}
+
+/// Rounds a multisample count down to a supported one, warning if it changed.
+fn supported_samples(samples: u32) -> u32 {
+    let supported = SUPPORTED_SAMPLE_COUNTS
+        .iter()
+        .rev()
+        .find(|&&supported| supported <= samples)
+        .copied()
+        .unwrap_or(SUPPORTED_SAMPLE_COUNTS[0]);
+    if supported != samples {
+        eprintln!(
+            "{} samples are not supported for anti-aliasing; using {}",
+            samples, supported
+        );
+    }
+    supported
 }
 
 struct GpuCommand<'a, S> {
-    pub vertices: VertexBuffer<GpuVertex>,
-    pub indices: IndexBuffer<u32>,
+    pub vertices: &'a VertexBuffer<GpuVertex>,
+    pub indices: &'a IndexBuffer<u32>,
     pub target: &'a mut S,
     pub program: &'a Program,
     pub uniforms: &'a UniformBuffer,
+    pub blend: BlendMode,
+    pub scissor: Option<glium::Rect>,
+    pub stencil: StencilMode,
+}
+
+/// How a draw uses the stencil buffer of its target, which holds the mask of the masked layer
+/// being rendered into it. See `Layer::mask`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum StencilMode {
+    /// The stencil buffer is neither read nor written.
+    Ignore,
+    /// The draw marks the pixels it covers in the stencil buffer, without coloring them.
+    Write,
+    /// The draw colors only the pixels marked in the stencil buffer.
+    Test,
 }
 
 impl Gpu {
+    /// Creates a handle to a headless GPU context, which needs no window or display.
+    ///
+    /// Paint into a texture from `build_texture()` and save it with `save_texture()`. Unlike a
+    /// windowed context, a headless one may be created on any thread, though it may only be used
+    /// on the thread which created it.
+    pub fn new_headless() -> Result<Self> {
+        Self::new().map(|(gpu, _)| gpu)
+    }
+
+    /// Returns a handle which builds textures with the given multisample count for
+    /// anti-aliasing. Unsupported counts fall back to the next lower supported count, with a
+    /// warning.
+    pub fn with_samples(self, samples: u32) -> Self {
+        Gpu {
+            samples: supported_samples(samples),
+            ..self
+        }
+    }
+
     pub(crate) fn new() -> Result<(Self, EventLoop<()>)> {
-        let events_loop = EventLoop::new();
+        let events_loop = headless_event_loop();
         let ctx = glium::glutin::ContextBuilder::new()
             .with_multisampling(0)
             .build_headless(
@@ -154,85 +422,114 @@ impl Gpu {
             )?;
         let ctx = Rc::new(Headless::new(ctx)?);
 
-        let program = Rc::new(Program::from_source(
-            ctx.as_ref(),
-            VERTEX_SHADER,
-            FRAGMENT_SHADER,
-            None,
-        )?);
-
-        Ok((
-            Gpu {
-                program,
-                ctx,
-                height_sign: 1.,
-            },
-            events_loop,
-        ))
+        Ok((Gpu::from_context(ctx, 1.)?, events_loop))
     }
 
     pub(crate) fn with_window(
         width: u32,
         height: u32,
+        samples: u32,
+        resizable: bool,
     ) -> Result<(Self, EventLoop<()>, (u32, u32))> {
+        // The window is multisampled as saved frames are, so they look the same.
+        let samples = supported_samples(samples);
         let events_loop = EventLoop::new();
         let wb = winit::window::WindowBuilder::new()
             .with_inner_size(glutin::dpi::LogicalSize {
                 width: width as f64,
                 height: height as f64,
             })
-            .with_resizable(false)
+            .with_resizable(resizable)
             .with_title("valora");
         let cb = glium::glutin::ContextBuilder::new()
             .with_srgb(false)
-            .with_multisampling(8);
+            .with_multisampling(samples as u16);
         let display = glium::Display::new(wb, cb, &events_loop).unwrap();
         let dimensions = display.get_framebuffer_dimensions();
         let ctx = Rc::new(DisplayFacade(display));
 
-        let program = Rc::new(Program::from_source(
-            ctx.as_ref(),
-            VERTEX_SHADER,
-            FRAGMENT_SHADER,
-            None,
-        )?);
-
         Ok((
-            Gpu {
-                program,
-                ctx,
-                height_sign: -1.,
-            },
+            Gpu::from_context(ctx, -1.)?.with_samples(samples),
             events_loop,
             dimensions,
         ))
     }
 
+    fn from_context(ctx: Rc<dyn FacadeExt>, height_sign: f32) -> Result<Self> {
+        let compile = |fragment| -> Result<Rc<Program>> {
+            Program::from_source(ctx.as_ref(), VERTEX_SHADER, fragment, None)
+                .map(Rc::new)
+                .map_err(|e| ShaderCompileError::from_program_error(e, VERTEX_SHADER, fragment))
+        };
+
+        Ok(Gpu {
+            program: compile(FRAGMENT_SHADER)?,
+            composite_program: compile(COMPOSITE_SHADER)?,
+            gradient_program: compile(GRADIENT_SHADER)?,
+            text_program: compile(TEXT_SHADER)?,
+            solid_program: compile(SOLID_SHADER)?,
+            image_program: compile(IMAGE_SHADER)?,
+            texture_paint_program: compile(TEXTURE_PAINT_SHADER)?,
+            instanced_program: Program::from_source(
+                ctx.as_ref(),
+                INSTANCED_VERTEX_SHADER,
+                FRAGMENT_SHADER,
+                None,
+            )
+            .map(Rc::new)
+            .map_err(|e| {
+                ShaderCompileError::from_program_error(e, INSTANCED_VERTEX_SHADER, FRAGMENT_SHADER)
+            })?,
+            ctx,
+            height_sign,
+            samples: DEFAULT_SAMPLE_COUNT,
+            buffer_pool: Rc::new(RefCell::new(BufferPool::new(DEFAULT_BUFFER_POOL_CAPACITY))),
+            image_cache: Rc::new(RefCell::new(HashMap::new())),
+            metrics: Rc::new(Cell::new(FrameMetrics::default())),
+            watched_programs: Rc::new(RefCell::new(vec![])),
+        })
+    }
+
     pub(crate) fn get_frame(&self) -> Option<Frame> {
         self.ctx.get_frame()
     }
 
+    pub(crate) fn set_title(&self, title: &str) {
+        self.ctx.set_title(title);
+    }
+
     pub(crate) fn default_shader(&self) -> Shader {
-        Shader {
-            id: random(),
-            program: self.program.clone(),
-            uniforms: UniformBuffer::default(),
-        }
+        self.build_shader(self.program.clone(), UniformBuffer::default())
     }
 
     pub(crate) fn compile_glsl(&self, source: &str) -> Result<Rc<Program>> {
-        Ok(Rc::new(Program::from_source(
-            self.ctx.as_ref(),
-            VERTEX_SHADER,
-            source,
-            None,
-        )?))
+        self.compile_glsl_with_vertex_shader(VERTEX_SHADER, source)
+    }
+
+    /// Compiles a program from custom vertex and fragment shaders. The vertex shader receives the
+    /// same inputs and uniforms as the default vertex shader.
+    pub(crate) fn compile_glsl_with_vertex_shader(
+        &self,
+        vertex: &str,
+        fragment: &str,
+    ) -> Result<Rc<Program>> {
+        Program::from_source(self.ctx.as_ref(), vertex, fragment, None)
+            .map(Rc::new)
+            .map_err(|e| ShaderCompileError::from_program_error(e, vertex, fragment))
     }
 
     pub(crate) fn build_shader(
         &self,
         program: Rc<Program>,
         uniforms: impl Into<UniformBuffer>,
+    ) -> Shader {
+        self.build_shader_shared(Rc::new(RefCell::new(program)), uniforms)
+    }
+
+    pub(crate) fn build_shader_shared(
+        &self,
+        program: SharedProgram,
+        uniforms: impl Into<UniformBuffer>,
     ) -> Shader {
         Shader {
             id: random(),
@@ -241,6 +538,154 @@ impl Gpu {
         }
     }
 
+    /// Watches the sources of a program, so that `reload_changed_programs()` recompiles it when
+    /// they change. The program is watched until every shader built with it is dropped.
+    pub(crate) fn watch_program(&self, watched: WatchedProgram) {
+        self.watched_programs.borrow_mut().push(watched);
+    }
+
+    /// Recompiles the watched programs whose sources have changed, and swaps each into every
+    /// shader built with it. If recompilation fails the error is printed and the last working
+    /// program stays in use.
+    pub(crate) fn reload_changed_programs(&self) {
+        let mut watched_programs = self.watched_programs.borrow_mut();
+        watched_programs.retain(WatchedProgram::in_use);
+        for watched in watched_programs.iter_mut() {
+            if let Err(e) = watched.reload_if_changed(self) {
+                eprintln!("Failed to reload custom shader; error: {:?}", e);
+            }
+        }
+    }
+
+    /// Builds a shader which shades paths with a gradient. Stops are `(offset, color)` pairs with
+    /// offsets in [0, 1].
+    pub(crate) fn gradient_shader(
+        &self,
+        gradient: GradientGeometry,
+        mut stops: Vec<(f32, LinSrgba)>,
+    ) -> Shader {
+        stops.sort_by_key(|(offset, _)| FloatOrd(*offset));
+        stops.truncate(MAX_GRADIENT_STOPS);
+
+        let mut uniforms = UniformBuffer::default();
+        let (kind, start, end, radius) = match gradient {
+            GradientGeometry::Linear { start, end } => (0, start, end, 0.),
+            GradientGeometry::Radial { center, radius } => (1, center, center, radius),
+        };
+        uniforms.push(String::from("gradient_kind"), UniformValue::SignedInt(kind));
+        uniforms.push(
+            String::from("gradient_start"),
+            UniformValue::Vec2([start.x, start.y]),
+        );
+        uniforms.push(
+            String::from("gradient_end"),
+            UniformValue::Vec2([end.x, end.y]),
+        );
+        uniforms.push(String::from("gradient_radius"), UniformValue::Float(radius));
+        uniforms.push(
+            String::from("stop_count"),
+            UniformValue::SignedInt(stops.len() as i32),
+        );
+        for (i, (offset, color)) in stops.into_iter().enumerate() {
+            uniforms.push(format!("stop_offsets[{}]", i), UniformValue::Float(offset));
+            uniforms.push(
+                format!("stop_colors[{}]", i),
+                UniformValue::Vec4([color.red, color.green, color.blue, color.alpha]),
+            );
+        }
+
+        self.build_shader(self.gradient_program.clone(), uniforms)
+    }
+
+    /// Builds a shader which shades paths with the coverage of rasterized text, mapped onto the
+    /// parallelogram in output space with a corner at `origin` and sides `x_axis` and `y_axis`.
+    pub(crate) fn text_shader(
+        &self,
+        coverage: Coverage,
+        origin: P2,
+        x_axis: V2,
+        y_axis: V2,
+    ) -> Result<Shader> {
+        #[derive(UniformSet)]
+        struct TextUniforms {
+            coverage: Texture2d,
+        }
+
+        let texture = Texture2d::with_format(
+            self.ctx.as_ref(),
+            RawImage2d {
+                data: Cow::Owned(coverage.data),
+                width: coverage.width,
+                height: coverage.height,
+                format: ClientFormat::U8,
+            },
+            UncompressedFloatFormat::U8,
+            MipmapsOption::AutoGeneratedMipmaps,
+        )?;
+
+        let mut uniforms = UniformBuffer::from(TextUniforms { coverage: texture });
+        uniforms.push(
+            String::from("text_origin"),
+            UniformValue::Vec2([origin.x, origin.y]),
+        );
+        uniforms.push(
+            String::from("text_x_axis"),
+            UniformValue::Vec2([x_axis.x, x_axis.y]),
+        );
+        uniforms.push(
+            String::from("text_y_axis"),
+            UniformValue::Vec2([y_axis.x, y_axis.y]),
+        );
+
+        Ok(self.build_shader(self.text_program.clone(), uniforms))
+    }
+
+    /// Builds a shader which draws instances with the colors they were painted with.
+    pub(crate) fn instanced_shader(&self) -> Shader {
+        self.build_shader(self.instanced_program.clone(), UniformBuffer::default())
+    }
+
+    /// Builds a shader which shades paths with a single color, regardless of the canvas color.
+    pub(crate) fn solid_shader(&self, color: LinSrgba) -> Shader {
+        let mut uniforms = UniformBuffer::default();
+        uniforms.push(
+            String::from("solid_color"),
+            UniformValue::Vec4([color.red, color.green, color.blue, color.alpha]),
+        );
+        self.build_shader(self.solid_program.clone(), uniforms)
+    }
+
+    /// Builds a shader which shades paths with a texture stretched over the whole output.
+    pub(crate) fn image_shader(&self, texture: Rc<Texture2d>) -> Shader {
+        struct ImageUniforms {
+            image: Rc<Texture2d>,
+        }
+
+        impl OwnedUniforms for ImageUniforms {
+            fn visit_owned_values(&self, f: &mut dyn FnMut(&str, &dyn IntoUniformValue)) {
+                f("image", self.image.as_ref());
+            }
+        }
+
+        self.build_shader(self.image_program.clone(), ImageUniforms { image: texture })
+    }
+
+    /// Builds a shader which shades paths with a texture, sampled at the coordinates the
+    /// transform maps output space positions to.
+    pub(crate) fn texture_paint_shader(&self, texture: Rc<Texture2d>, uv: Transform) -> Shader {
+        let mut uniforms = UniformBuffer::default();
+        uniforms.push(
+            String::from("image_transform"),
+            UniformValue::Mat3([
+                [uv.m11, uv.m12, 0.],
+                [uv.m21, uv.m22, 0.],
+                [uv.m31, uv.m32, 1.],
+            ]),
+        );
+        self.build_shader(self.texture_paint_program.clone(), uniforms)
+            .with_texture("image", texture)
+    }
+
     pub fn build_texture(&self, width: u32, height: u32) -> Result<Texture2dMultisample> {
         Ok(Texture2dMultisample::empty_with_format(
             self.ctx.as_ref(),
@@ -248,11 +693,31 @@ impl Gpu {
             MipmapsOption::NoMipmap,
             width,
             height,
-            /*samples=*/ 16,
+            self.samples,
         )?)
     }
 
-    fn build_ram_texture(&self, width: u32, height: u32) -> Result<Texture2d> {
+    /// Returns a handle which renders with y increasing up the target, as a windowed GPU does.
+    ///
+    /// Textures rendered by it share the orientation of uploaded images regardless of whether the
+    /// GPU is headless.
+    pub(crate) fn upright(&self) -> Gpu {
+        Gpu {
+            height_sign: -1.,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a handle which renders textures in the orientation `save_texture()` expects, as a
+    /// headless GPU does.
+    pub(crate) fn for_saving(&self) -> Gpu {
+        Gpu {
+            height_sign: 1.,
+            ..self.clone()
+        }
+    }
+
+    pub(crate) fn build_ram_texture(&self, width: u32, height: u32) -> Result<Texture2d> {
         Ok(Texture2d::empty_with_format(
             self.ctx.as_ref(),
             TEXTURE_FORMAT,
@@ -262,9 +727,16 @@ impl Gpu {
         )?)
     }
 
-    pub fn read_to_ram(&self, texture: &Texture2dMultisample) -> Result<RawImage2d<u8>> {
+    pub fn read_to_ram(&self, texture: &Texture2dMultisample) -> Result<RawImage2d<'_, u8>> {
         let (width, height) = texture.dimensions();
         let target = self.build_ram_texture(width, height)?;
+        self.resolve(texture, &target);
+        Ok(target.read())
+    }
+
+    /// Resolves the samples of a multisampled texture into a texture of the same size.
+    pub(crate) fn resolve(&self, texture: &Texture2dMultisample, target: &Texture2d) {
+        let (width, height) = texture.dimensions();
         texture.as_surface().blit_color(
             &glium::Rect {
                 bottom: 0,
@@ -281,95 +753,546 @@ impl Gpu {
             },
             MagnifySamplerFilter::Linear,
         );
-        Ok(target.read())
+    }
+
+    /// Saves the texture to an image file at the given path. The format is inferred from the
+    /// path's extension.
+    pub fn save_texture(
+        &self,
+        texture: &Texture2dMultisample,
+        path: impl AsRef<Path>,
+    ) -> Result<()> {
+        let (width, height) = texture.dimensions();
+        let image: ImageBuffer<Rgba<u8>, Vec<u8>> =
+            ImageBuffer::from_raw(width, height, self.read_srgba8(texture)?).unwrap();
+
+        image.save(path)?;
+        Ok(())
+    }
+
+    /// Loads an image file into a texture for painting with `TexturePaint`. The format is
+    /// inferred from the file, and colors are linearized from sRGB.
+    ///
+    /// Textures are cached by path, so loading a file again returns the texture already uploaded
+    /// for it.
+    pub fn load_image(
+        &self,
+        path: impl AsRef<Path>,
+        options: TextureOptions,
+    ) -> Result<Rc<Texture2d>> {
+        let key = (path.as_ref().canonicalize()?, options.mipmaps);
+        if let Some(texture) = self.image_cache.borrow().get(&key) {
+            return Ok(texture.clone());
+        }
+
+        let image = image::open(&key.0)?.to_rgba();
+        let (width, height) = image.dimensions();
+        let texture = self.texture_from_rgba(width, height, &image.into_raw(), options)?;
+        self.image_cache.borrow_mut().insert(key, texture.clone());
+        Ok(texture)
+    }
+
+    /// Uploads sRGB encoded RGBA bytes into a texture for painting with `TexturePaint`. Rows run
+    /// from the top of the image down, as in image files.
+    pub fn texture_from_rgba(
+        &self,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        options: TextureOptions,
+    ) -> Result<Rc<Texture2d>> {
+        let expected = width as usize * height as usize * 4;
+        if pixels.len() != expected {
+            bail!(
+                "Expected {} bytes of RGBA pixels for a {}x{} texture; got {}",
+                expected,
+                width,
+                height,
+                pixels.len()
+            );
+        }
+
+        let linear = |v: u8| <Srgb as TransferFn>::into_linear(v.convert::<f32>());
+        let data: Vec<f32> = pixels
+            .chunks_exact(4)
+            .flat_map(|pixel| {
+                vec![
+                    linear(pixel[0]),
+                    linear(pixel[1]),
+                    linear(pixel[2]),
+                    pixel[3].convert::<f32>(),
+                ]
+            })
+            .collect();
+
+        // Rows are reversed so that the bottom of the image lies at the bottom of texture space,
+        // as the y axis of the canvas points up.
+        Ok(Rc::new(Texture2d::with_format(
+            self.ctx.as_ref(),
+            RawImage2d::from_raw_rgba_reversed(&data, (width, height)),
+            TEXTURE_FORMAT,
+            if options.mipmaps {
+                MipmapsOption::AutoGeneratedMipmaps
+            } else {
+                MipmapsOption::NoMipmap
+            },
+        )?))
+    }
+
+    /// Reads the texture into sRGB encoded RGBA bytes, in the row order images are saved in.
+    pub(crate) fn read_srgba8(&self, texture: &Texture2dMultisample) -> Result<Vec<u8>> {
+        let raw = self.read_to_ram(texture)?;
+        Ok(raw
+            .data
+            .into_par_iter()
+            .map(|v| v.convert::<f32>())
+            .map(<Srgb as TransferFn>::from_linear)
+            .map(|v| v.convert::<u8>())
+            .collect())
     }
 
     pub fn render(
         &self,
         width: u32,
         height: u32,
-        elements: impl IntoIterator<Item = Element>,
+        drawables: impl IntoIterator<Item = Drawable>,
         target: &mut impl Surface,
     ) -> Result<()> {
-        let elements = elements.into_iter();
-        for (_id, batch) in &elements.group_by(|e| e.shader.id) {
-            let mut batch = batch.peekable();
-            let mut first = if let Some(first) = batch.peek() {
-                first.shader.clone()
-            } else {
-                println!("This is possible??");
-                continue;
-            };
-
-            // TODO: reconcile conflicts between user uniforms and the defaults
-            first.uniforms.push(
-                String::from("_valora_width"),
-                UniformValue::Float(width as f32),
-            );
-            first.uniforms.push(
-                String::from("_valora_height"),
-                UniformValue::Float(height as f32),
-            );
-            first.uniforms.push(
-                String::from("_valora_height_sign"),
-                UniformValue::Float(self.height_sign),
-            );
-
-            let (_, cpu_vertices, cpu_indices) = batch
-                .try_fold::<_, _, Result<(u32, Vec<GpuVertex>, Vec<u32>)>>(
-                    (0, vec![], vec![]),
-                    |(idx, mut vertices, mut indices), element| {
-                        let (mut new_vertices, new_indices) =
-                            raster_path(element.path, element.raster_method, element.color)?;
-                        vertices.append(&mut new_vertices);
-                        indices.extend(new_indices.into_iter().map(|i| i + idx));
-                        Ok((vertices.len() as u32, vertices, indices))
-                    },
-                )?;
+        self.render_with_stencil(width, height, drawables, target, StencilMode::Ignore)
+    }
 
-            let vertices = VertexBuffer::new(self.ctx.as_ref(), cpu_vertices.as_slice())?;
-            let indices = IndexBuffer::new(
-                self.ctx.as_ref(),
-                PrimitiveType::TrianglesList,
-                cpu_indices.as_slice(),
-            )?;
+    fn render_with_stencil(
+        &self,
+        width: u32,
+        height: u32,
+        drawables: impl IntoIterator<Item = Drawable>,
+        target: &mut impl Surface,
+        stencil: StencilMode,
+    ) -> Result<()> {
+        let drawables = drawables.into_iter();
+        for (_id, batch) in &drawables.group_by(|d| match d {
+            Drawable::Element(element) => Some((
+                element.shader.id,
+                element.blend,
+                element.clip,
+                element.parallel,
+            )),
+            Drawable::Layer(_) | Drawable::Instanced(_) => None,
+        }) {
+            let mut elements = vec![];
+            for drawable in batch {
+                match drawable {
+                    Drawable::Element(element) => elements.push(element),
+                    Drawable::Layer(layer) => {
+                        self.render_layer(width, height, layer, target, stencil)?
+                    }
+                    Drawable::Instanced(instanced) => {
+                        self.render_instanced(width, height, instanced, target, stencil)?
+                    }
+                }
+            }
 
-            self.draw_to_texture(GpuCommand {
-                indices,
-                vertices,
-                target,
-                program: first.program.as_ref(),
-                uniforms: &first.uniforms,
-            })?;
+            if !elements.is_empty() {
+                self.render_elements(width, height, elements, target, stencil)?;
+            }
         }
 
         Ok(())
     }
 
-    fn draw_to_texture<S: Surface>(&self, cmd: GpuCommand<S>) -> Result<()> {
-        Ok(cmd.target.draw(
-            &cmd.vertices,
-            &cmd.indices,
-            cmd.program,
-            cmd.uniforms,
+    fn render_elements(
+        &self,
+        width: u32,
+        height: u32,
+        elements: Vec<Element>,
+        target: &mut impl Surface,
+        stencil: StencilMode,
+    ) -> Result<()> {
+        let mut first = elements[0].shader.clone();
+        let blend = elements[0].blend;
+        let scissor = self.scissor(elements[0].clip, width, height, target.get_dimensions());
+        #[cfg(feature = "parallel")]
+        let parallel = elements[0].parallel;
+        self.push_output_uniforms(&mut first.uniforms, width, height);
+
+        let paths: Vec<_> = elements
+            .into_iter()
+            .map(|element| (element.path, element.raster_method, element.color))
+            .collect();
+
+        // Results are collected in order either way, so the output is the same.
+        let tessellation_start = Instant::now();
+        let raster = |(path, method, color): (lyon_path::Path, Method, LinSrgba)| {
+            raster_path(path, method, color)
+        };
+        #[cfg(feature = "parallel")]
+        let rastered: Result<Vec<_>> = if parallel {
+            paths.into_par_iter().map(raster).collect()
+        } else {
+            paths.into_iter().map(raster).collect()
+        };
+        #[cfg(not(feature = "parallel"))]
+        let rastered: Result<Vec<_>> = paths.into_iter().map(raster).collect();
+        let rastered = rastered?;
+        let tessellation_ns = elapsed_ns(tessellation_start);
+
+        let mut cpu_vertices = vec![];
+        let mut cpu_indices = vec![];
+        for (mut new_vertices, new_indices) in rastered {
+            let idx = cpu_vertices.len() as u32;
+            cpu_vertices.append(&mut new_vertices);
+            cpu_indices.extend(new_indices.into_iter().map(|i| i + idx));
+        }
+
+        let buffers = self.upload(&cpu_vertices, &cpu_indices)?;
+        self.record_draw(tessellation_ns, cpu_vertices.len());
+        let program = first.program.borrow().clone();
+        self.draw_to_texture(GpuCommand {
+            vertices: &buffers.vertices,
+            indices: &buffers.indices,
+            target,
+            program: program.as_ref(),
+            uniforms: &first.uniforms,
+            blend,
+            scissor,
+            stencil,
+        })
+    }
+
+    fn render_instanced(
+        &self,
+        width: u32,
+        height: u32,
+        instanced: Instanced,
+        target: &mut impl Surface,
+        stencil: StencilMode,
+    ) -> Result<()> {
+        let Instanced {
+            element,
+            transforms,
+        } = instanced;
+        if transforms.is_empty() {
+            return Ok(());
+        }
+
+        let mut uniforms = element.shader.uniforms.clone();
+        self.push_output_uniforms(&mut uniforms, width, height);
+
+        let tessellation_start = Instant::now();
+        let (cpu_vertices, cpu_indices) =
+            raster_path(element.path, element.raster_method, element.color)?;
+        let tessellation_ns = elapsed_ns(tessellation_start);
+        let buffers = self.upload(&cpu_vertices, &cpu_indices)?;
+        self.record_draw(tessellation_ns, cpu_vertices.len());
+        let program = element.shader.program.borrow().clone();
+        let instances = VertexBuffer::new(
+            self.ctx.as_ref(),
+            &transforms
+                .iter()
+                .map(|t| GpuInstance {
+                    a_instance_transform: [
+                        [t.m11, t.m12, 0.],
+                        [t.m21, t.m22, 0.],
+                        [t.m31, t.m32, 1.],
+                    ],
+                })
+                .collect::<Vec<_>>(),
+        )?;
+
+        Ok(target.draw(
+            (
+                &buffers.vertices,
+                instances
+                    .per_instance()
+                    .map_err(|_| failure::err_msg("Instanced drawing is not supported"))?,
+            ),
+            &buffers.indices,
+            program.as_ref(),
+            &uniforms,
+            &draw_parameters(
+                element.blend,
+                self.scissor(element.clip, width, height, target.get_dimensions()),
+                stencil,
+            ),
+        )?)
+    }
+
+    fn upload(&self, vertices: &[GpuVertex], indices: &[u32]) -> Result<Rc<GeometryBuffers>> {
+        let upload_start = Instant::now();
+        let buffers = self
+            .buffer_pool
+            .borrow_mut()
+            .upload(self.ctx.as_ref(), vertices, indices)?;
+        let mut metrics = self.metrics.get();
+        metrics.gpu_upload_ns += elapsed_ns(upload_start);
+        self.metrics.set(metrics);
+        Ok(buffers)
+    }
+
+    /// Counts a draw call of freshly tessellated vertices in the metrics.
+    fn record_draw(&self, tessellation_ns: u64, vertex_count: usize) {
+        let mut metrics = self.metrics.get();
+        metrics.tessellation_ns += tessellation_ns;
+        metrics.draw_call_count += 1;
+        metrics.vertex_count += vertex_count as u64;
+        self.metrics.set(metrics);
+    }
+
+    /// Returns the metrics of rendering since they were last taken, and starts counting again
+    /// from zero.
+    ///
+    /// The metrics are shared by every clone of this handle.
+    pub(crate) fn take_metrics(&self) -> FrameMetrics {
+        self.metrics.take()
+    }
+
+    /// Sets the maximum number of geometries kept uploaded between frames, so geometry which
+    /// has not changed is not uploaded again. The least recently drawn geometry is evicted
+    /// first. The default is `DEFAULT_BUFFER_POOL_CAPACITY`, and zero disables reuse.
+    ///
+    /// The pool is shared by every clone of this handle.
+    pub fn set_buffer_pool_capacity(&self, capacity: usize) {
+        self.buffer_pool.borrow_mut().set_capacity(capacity);
+    }
+
+    /// Converts an output space clip to a scissor rectangle in the pixels of a target of the
+    /// given dimensions, which the output is stretched over.
+    fn scissor(
+        &self,
+        clip: Option<Rect>,
+        width: u32,
+        height: u32,
+        (target_width, target_height): (u32, u32),
+    ) -> Option<glium::Rect> {
+        let clip = clip?;
+        let (x_scale, y_scale) = (
+            target_width as f32 / width as f32,
+            target_height as f32 / height as f32,
+        );
+        let left = clip.bottom_left.x * x_scale;
+        let right = (clip.bottom_left.x + clip.width) * x_scale;
+        // Output space has y pointing up, and rows of the target count up from its bottom, so
+        // they only line up when rendering upright.
+        let (bottom, top) = if self.height_sign < 0. {
+            (clip.bottom_left.y, clip.bottom_left.y + clip.height)
+        } else {
+            (
+                height as f32 - (clip.bottom_left.y + clip.height),
+                height as f32 - clip.bottom_left.y,
+            )
+        };
+        let (bottom, top) = (bottom * y_scale, top * y_scale);
+
+        let pixel = |v: f32, max: u32| (v.round().max(0.) as u32).min(max);
+        let (left, right) = (pixel(left, target_width), pixel(right, target_width));
+        let (bottom, top) = (pixel(bottom, target_height), pixel(top, target_height));
+        Some(glium::Rect {
+            left,
+            bottom,
+            width: right.saturating_sub(left),
+            height: top.saturating_sub(bottom),
+        })
+    }
+
+    fn push_output_uniforms(&self, uniforms: &mut UniformBuffer, width: u32, height: u32) {
+        // TODO: reconcile conflicts between user uniforms and the defaults
+        uniforms.push(
+            String::from("_valora_width"),
+            UniformValue::Float(width as f32),
+        );
+        uniforms.push(
+            String::from("_valora_height"),
+            UniformValue::Float(height as f32),
+        );
+        uniforms.push(
+            String::from("_valora_height_sign"),
+            UniformValue::Float(self.height_sign),
+        );
+    }
+
+    /// Renders the layer to its own texture, then composites it onto the target by drawing a
+    /// quad which reads both the layer and a copy of the target.
+    fn render_layer(
+        &self,
+        width: u32,
+        height: u32,
+        layer: Layer,
+        target: &mut impl Surface,
+        stencil: StencilMode,
+    ) -> Result<()> {
+        let (target_width, target_height) = target.get_dimensions();
+
+        let layer_texture = self.build_texture(target_width, target_height)?;
+        match layer.mask {
+            Some(path) => {
+                // The mask is marked in the stencil buffer of the layer, and the layer's contents
+                // are drawn only where it is marked.
+                let depth_stencil = DepthStencilTexture2dMultisample::empty(
+                    self.ctx.as_ref(),
+                    target_width,
+                    target_height,
+                    self.samples,
+                )?;
+                let mut surface = SimpleFrameBuffer::with_depth_stencil_buffer(
+                    self.ctx.as_ref(),
+                    &layer_texture,
+                    &depth_stencil,
+                )?;
+                surface.clear_color_and_stencil((0., 0., 0., 0.), 0);
+                let mask = Element {
+                    path,
+                    color: LinSrgba::new(1., 1., 1., 1.),
+                    raster_method: Method::Fill,
+                    shader: self.solid_shader(LinSrgba::new(1., 1., 1., 1.)),
+                    blend: BlendMode::Normal,
+                    clip: None,
+                    parallel: false,
+                };
+                self.render_with_stencil(
+                    width,
+                    height,
+                    vec![Drawable::Element(mask)],
+                    &mut surface,
+                    StencilMode::Write,
+                )?;
+                self.render_with_stencil(
+                    width,
+                    height,
+                    layer.drawables,
+                    &mut surface,
+                    StencilMode::Test,
+                )?;
+            }
+            None => {
+                layer_texture.as_surface().clear_color(0., 0., 0., 0.);
+                self.render(
+                    width,
+                    height,
+                    layer.drawables,
+                    &mut layer_texture.as_surface(),
+                )?;
+            }
+        }
+
+        let backdrop = self.build_texture(target_width, target_height)?;
+        target.blit_whole_color_to(
+            &backdrop.as_surface(),
+            &glium::BlitTarget {
+                bottom: 0,
+                left: 0,
+                width: target_width as i32,
+                height: target_height as i32,
+            },
+            MagnifySamplerFilter::Nearest,
+        );
+
+        let (width, height) = (width as f32, height as f32);
+        let quad = [[0., 0.], [width, 0.], [width, height], [0., height]];
+        let vertices = VertexBuffer::new(
+            self.ctx.as_ref(),
+            &quad
+                .iter()
+                .map(|&vpos| GpuVertex {
+                    vpos,
+                    vcol: [0., 0., 0., 0.],
+                })
+                .collect::<Vec<_>>(),
+        )?;
+        let indices = IndexBuffer::new(
+            self.ctx.as_ref(),
+            PrimitiveType::TrianglesList,
+            &[0u32, 1, 2, 0, 2, 3],
+        )?;
+
+        self.record_draw(0, quad.len());
+        Ok(target.draw(
+            &vertices,
+            &indices,
+            &self.composite_program,
+            &uniform! {
+                layer: &layer_texture,
+                backdrop: &backdrop,
+                opacity: layer.opacity,
+                blend_mode: layer.blend as i32,
+                _valora_width: width,
+                _valora_height: height,
+                _valora_height_sign: self.height_sign,
+            },
             &DrawParameters {
-                blend: Blend {
-                    color: BlendingFunction::Addition {
-                        source: LinearBlendingFactor::SourceAlpha,
-                        destination: LinearBlendingFactor::OneMinusSourceAlpha,
-                    },
-                    alpha: BlendingFunction::Addition {
-                        source: LinearBlendingFactor::One,
-                        destination: LinearBlendingFactor::OneMinusSourceAlpha,
-                    },
-                    constant_value: (0.0, 0.0, 0.0, 0.0),
-                },
-                line_width: Some(1.0),
                 multisampling: true,
                 dithering: false,
-                smooth: Some(glium::draw_parameters::Smooth::Nicest),
+                stencil: stencil_parameters(stencil),
                 ..Default::default()
             },
         )?)
     }
+
+    fn draw_to_texture<S: Surface>(&self, cmd: GpuCommand<S>) -> Result<()> {
+        Ok(cmd.target.draw(
+            cmd.vertices,
+            cmd.indices,
+            cmd.program,
+            cmd.uniforms,
+            &draw_parameters(cmd.blend, cmd.scissor, cmd.stencil),
+        )?)
+    }
+}
+
+/// The stencil tests and operations of a draw, which mark or test for the value 1 on either
+/// winding of triangle.
+fn stencil_parameters(stencil: StencilMode) -> Stencil {
+    let (test, operation) = match stencil {
+        StencilMode::Ignore => return Stencil::default(),
+        StencilMode::Write => (StencilTest::AlwaysPass, StencilOperation::Replace),
+        StencilMode::Test => (StencilTest::IfEqual { mask: !0 }, StencilOperation::Keep),
+    };
+    Stencil {
+        test_clockwise: test,
+        reference_value_clockwise: 1,
+        depth_pass_operation_clockwise: operation,
+        test_counter_clockwise: test,
+        reference_value_counter_clockwise: 1,
+        depth_pass_operation_counter_clockwise: operation,
+        ..Stencil::default()
+    }
+}
+
+fn elapsed_ns(start: Instant) -> u64 {
+    start.elapsed().as_nanos() as u64
+}
+
+/// The parameters for drawing elements, blended by the hardware with the given mode and clipped
+/// to the scissor rectangle in target pixels if there is one.
+fn draw_parameters<'a>(
+    blend: BlendMode,
+    scissor: Option<glium::Rect>,
+    stencil: StencilMode,
+) -> DrawParameters<'a> {
+    DrawParameters {
+        scissor,
+        stencil: stencil_parameters(stencil),
+        color_mask: match stencil {
+            StencilMode::Write => (false, false, false, false),
+            StencilMode::Ignore | StencilMode::Test => (true, true, true, true),
+        },
+        blend: Blend {
+            color: BlendingFunction::Addition {
+                source: LinearBlendingFactor::SourceAlpha,
+                destination: match blend {
+                    BlendMode::Add => LinearBlendingFactor::One,
+                    _ => LinearBlendingFactor::OneMinusSourceAlpha,
+                },
+            },
+            alpha: BlendingFunction::Addition {
+                source: LinearBlendingFactor::One,
+                destination: LinearBlendingFactor::OneMinusSourceAlpha,
+            },
+            constant_value: (0.0, 0.0, 0.0, 0.0),
+        },
+        line_width: Some(1.0),
+        multisampling: true,
+        dithering: false,
+        smooth: Some(glium::draw_parameters::Smooth::Nicest),
+        ..Default::default()
+    }
 }
@@ -0,0 +1,99 @@
+//! Grids for laying out elements.
+
+use crate::{Rect, P2, V2};
+use lyon_path::geom::LineSegment;
+
+/// A rectangle divided into equally sized cells in columns and rows.
+///
+/// Columns are counted from the left and rows from the bottom.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Grid {
+    pub rect: Rect,
+    pub cols: usize,
+    pub rows: usize,
+}
+
+impl Grid {
+    pub fn new(rect: Rect, cols: usize, rows: usize) -> Self {
+        Self { rect, cols, rows }
+    }
+
+    /// Returns the size of each cell.
+    pub fn cell_size(&self) -> V2 {
+        V2::new(
+            self.rect.width / self.cols as f32,
+            self.rect.height / self.rows as f32,
+        )
+    }
+
+    /// Returns the cell at the given column and row, if it is in the grid.
+    pub fn cell(&self, col: usize, row: usize) -> Option<Rect> {
+        if col >= self.cols || row >= self.rows {
+            return None;
+        }
+
+        let size = self.cell_size();
+        Some(Rect {
+            bottom_left: self.rect.bottom_left + V2::new(size.x * col as f32, size.y * row as f32),
+            width: size.x,
+            height: size.y,
+        })
+    }
+
+    /// Returns the cells of the grid in row major order.
+    pub fn cells(&self) -> impl Iterator<Item = Rect> + Clone {
+        let grid = *self;
+        (0..grid.rows)
+            .flat_map(move |row| (0..grid.cols).filter_map(move |col| grid.cell(col, row)))
+    }
+
+    /// Returns the lines between and around the cells of the grid: first the vertical lines from
+    /// left to right, then the horizontal lines from bottom to top.
+    pub fn lines(&self) -> impl Iterator<Item = LineSegment<f32>> + Clone {
+        let grid = *self;
+        let size = self.cell_size();
+        let bottom_left = self.rect.bottom_left;
+        let vertical = (0..=grid.cols).map(move |col| {
+            let x = bottom_left.x + size.x * col as f32;
+            LineSegment {
+                from: P2::new(x, bottom_left.y),
+                to: P2::new(x, bottom_left.y + grid.rect.height),
+            }
+        });
+        let horizontal = (0..=grid.rows).map(move |row| {
+            let y = bottom_left.y + size.y * row as f32;
+            LineSegment {
+                from: P2::new(bottom_left.x, y),
+                to: P2::new(bottom_left.x + grid.rect.width, y),
+            }
+        });
+        vertical.chain(horizontal)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cells_are_row_major_from_the_bottom_left() {
+        let grid = Grid::new(
+            Rect {
+                bottom_left: P2::new(10., 20.),
+                width: 30.,
+                height: 20.,
+            },
+            3,
+            2,
+        );
+
+        let cells: Vec<Rect> = grid.cells().collect();
+        assert_eq!(cells.len(), 6);
+        assert_eq!(cells[0].bottom_left, P2::new(10., 20.));
+        assert_eq!(cells[1].bottom_left, P2::new(20., 20.));
+        assert_eq!(cells[3].bottom_left, P2::new(10., 30.));
+        assert_eq!(grid.cell(2, 1), Some(cells[5]));
+        assert_eq!(grid.cell(3, 0), None);
+        assert_eq!(grid.lines().count(), 4 + 3);
+    }
+}
@@ -0,0 +1,144 @@
+//! Mouse input in coordinate space.
+
+use crate::{World, P2};
+use glutin::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
+
+/// The pixels of a scroll by touchpad or other precise device equal to one line of a wheel.
+pub const PIXELS_PER_SCROLL_LINE: f32 = 20.;
+
+/// A mouse event, with positions in coordinate space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MouseEvent {
+    ButtonPressed(MouseButton, P2),
+    ButtonReleased(MouseButton, P2),
+    Moved(P2),
+    /// A vertical scroll in lines, positive for scrolling up.
+    ScrollDelta(f32),
+}
+
+/// Converts a position in pixels from the top left of a window of the given size in pixels to
+/// coordinate space.
+pub fn window_to_world(x: f64, y: f64, window_size: (u32, u32), world: &World) -> P2 {
+    Viewport::window(window_size).to_world(x, y, world)
+}
+
+/// The region of a window the painting is shown in, in pixels from the top left.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct Viewport {
+    pub left: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Viewport {
+    /// A viewport covering a whole window of the given size.
+    pub fn window(window_size: (u32, u32)) -> Self {
+        Self {
+            left: 0.,
+            top: 0.,
+            width: f64::from(window_size.0),
+            height: f64::from(window_size.1),
+        }
+    }
+
+    fn to_world(self, x: f64, y: f64, world: &World) -> P2 {
+        P2::new(
+            ((x - self.left) / self.width) as f32 * world.width,
+            (1. - ((y - self.top) / self.height) as f32) * world.height,
+        )
+    }
+}
+
+/// Extracts the mouse events from window events of a window of the given size in pixels.
+///
+/// Button events are placed at the position of the cursor as of the latest move before them, or
+/// at `cursor` if there is no earlier move; without either they are left out.
+pub fn extract_mouse_events(
+    events: &[WindowEvent<'_>],
+    window_size: (u32, u32),
+    world: &World,
+    mut cursor: Option<P2>,
+) -> Vec<MouseEvent> {
+    events
+        .iter()
+        .filter_map(|event| {
+            let event = mouse_event(event, Viewport::window(window_size), world, cursor)?;
+            if let MouseEvent::Moved(position) = event {
+                cursor = Some(position);
+            }
+            Some(event)
+        })
+        .collect()
+}
+
+/// Converts a window event to a mouse event, if it is one. See `extract_mouse_events()`.
+pub(crate) fn mouse_event(
+    event: &WindowEvent<'_>,
+    viewport: Viewport,
+    world: &World,
+    cursor: Option<P2>,
+) -> Option<MouseEvent> {
+    match event {
+        WindowEvent::CursorMoved { position, .. } => Some(MouseEvent::Moved(
+            viewport.to_world(position.x, position.y, world),
+        )),
+        WindowEvent::MouseInput { state, button, .. } => Some(match state {
+            ElementState::Pressed => MouseEvent::ButtonPressed(*button, cursor?),
+            ElementState::Released => MouseEvent::ButtonReleased(*button, cursor?),
+        }),
+        WindowEvent::MouseWheel { delta, .. } => Some(MouseEvent::ScrollDelta(match delta {
+            MouseScrollDelta::LineDelta(_, y) => *y,
+            MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_SCROLL_LINE,
+        })),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use glutin::{dpi::PhysicalPosition, event::DeviceId};
+
+    #[test]
+    fn buttons_take_the_latest_cursor_position() {
+        // Device ids are only compared, so a dummy is safe here.
+        let device_id = unsafe { DeviceId::dummy() };
+        let world = World {
+            seed: 0,
+            width: 100.,
+            height: 50.,
+            scale: 1.,
+            frames: None,
+            framerate: 24,
+        };
+        #[allow(deprecated)]
+        let events = [
+            WindowEvent::MouseInput {
+                device_id,
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                modifiers: Default::default(),
+            },
+            WindowEvent::CursorMoved {
+                device_id,
+                position: PhysicalPosition::new(50., 0.),
+                modifiers: Default::default(),
+            },
+            WindowEvent::MouseInput {
+                device_id,
+                state: ElementState::Released,
+                button: MouseButton::Left,
+                modifiers: Default::default(),
+            },
+        ];
+
+        assert_eq!(
+            extract_mouse_events(&events, (200, 100), &world, None),
+            vec![
+                MouseEvent::Moved(P2::new(25., 50.)),
+                MouseEvent::ButtonReleased(MouseButton::Left, P2::new(25., 50.)),
+            ]
+        );
+    }
+}
@@ -2,54 +2,96 @@
 
 //! A brush for generative fine art.
 
+mod buffer_pool;
 mod gpu;
 mod noise_traits;
 mod raster;
 mod render;
+mod sampling;
 
 pub mod attributes;
 pub mod canvas;
+pub mod color;
+pub mod easing;
+pub mod flow_field;
 pub mod forms;
+pub mod gif_export;
+pub mod grid;
+pub mod input;
 pub mod paint;
 pub mod path;
+pub mod pipeline;
+pub mod recording;
 pub mod shaders;
+pub mod stipple;
+pub mod svg_export;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod text;
+pub mod timeline;
 pub mod transforms;
+pub mod turtle;
 pub mod uniforms;
+pub mod video_export;
+pub mod voronoi;
 
 /// Exhuastive set of imports for painting.
 pub mod prelude {
     pub use self::{
-        attributes::*, canvas::*, forms::*, paint::*, path::*, shaders::*, transforms::*,
-        uniforms::*,
+        attributes::*, canvas::*, easing::*, flow_field::*, forms::*, gif_export::*, grid::*,
+        input::*, paint::*, path::*, pipeline::*, recording::*, shaders::*, stipple::*,
+        svg_export::*, text::*, timeline::*, transforms::*, turtle::*, uniforms::*,
+        video_export::*, voronoi::*,
     };
     pub use super::*;
-    pub use euclid;
-    pub use noise::{self, *};
+    pub use ::euclid;
+    pub use ::noise::{self, *};
+    pub use ::rayon::{self, prelude::*};
     pub use noise_traits::*;
-    pub use rayon::{self, prelude::*};
 
-    pub use palette::{
+    pub use ::palette::{
         self, encoding::Srgb, Alpha, Blend, ComponentWise, Hue, IntoColor, LinSrgb, LinSrgba,
         Saturate, *,
     };
-    pub use rand::{self, rngs::StdRng, Rng, SeedableRng};
+    pub use ::rand::{self, rngs::StdRng, Rng, SeedableRng};
     pub use structopt::StructOpt;
 
     pub use std::f32::consts::PI;
 }
 
 pub use self::{
-    gpu::{Gpu, Shader},
+    buffer_pool::DEFAULT_BUFFER_POOL_CAPACITY,
+    gpu::{
+        FrameMetrics, Gpu, Shader, TextureOptions, DEFAULT_SAMPLE_COUNT, MAX_GRADIENT_STOPS,
+        SUPPORTED_SAMPLE_COUNTS,
+    },
     render::Context,
     shaders::ShaderProgram,
 };
 
+/// Generates `fn main()` running the artist it is attached to, which may be a struct, enum or
+/// `impl Artist` block.
+///
+/// Options such as `#[valora::main(size = 512, fps = 60)]` set defaults for the command line,
+/// which is parsed with `Options::from_args_with_extra()`. The supported options are `size`,
+/// `width`, `height`, `scale`, `seed`, `frames` and `fps`.
+pub use valora_derive::main;
+
+/// Keys of the keyboard and buttons of the mouse, as reported on `Context`.
+pub use glutin::event::{MouseButton, VirtualKeyCode};
+
 use self::{gpu::*, prelude::*, raster::Method};
 use euclid::{Point3D, Size2D, UnknownUnit, Vector2D, Vector3D};
-use failure::Error;
+use failure::{bail, format_err, Error};
 use lyon_path::math::Point;
 use render::*;
-use std::{path::PathBuf, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::OnceLock,
+    time::Duration,
+};
+use structopt::clap::{AppSettings, Arg, ErrorKind};
 
 /// A two dimensional point.
 pub type P2 = Point;
@@ -69,6 +111,9 @@ pub type S2 = Size2D<f32, UnknownUnit>;
 /// An angle.
 pub type Angle = euclid::Angle<f32>;
 
+/// A two dimensional affine transform.
+pub type Transform = euclid::Transform2D<f32, UnknownUnit, UnknownUnit>;
+
 /// A compiled GLSL program.
 pub type Program = glium::program::Program;
 
@@ -98,9 +143,83 @@ pub struct Options {
     #[structopt(short = "d", long = "delay", default_value = "0")]
     pub delay: usize,
 
+    /// The number of samples per pixel used to anti-alias painting. Supported counts are 1, 2,
+    /// 4 and 8; others fall back to the next lower supported count.
+    #[structopt(short = "m", long = "msaa", default_value = default_samples_arg())]
+    pub samples: u32,
+
     /// Prefix of output path. Output is <prefix>/<seed>/<frame_number>.png
     #[structopt(short = "o", long = "output", parse(from_os_str))]
     pub output: Option<PathBuf>,
+
+    /// The frame rate at which recorded frames play back, if it differs from the rate frames are
+    /// displayed at.
+    ///
+    /// Each frame advances the time of the composition by one frame at this rate however long it
+    /// takes to render, so saved frames and GIFs play back at exactly this rate.
+    #[structopt(long = "record_fps")]
+    pub record_fps: Option<usize>,
+
+    /// Whether the preview window can be resized.
+    #[structopt(long = "resizable")]
+    pub resizable: bool,
+
+    /// How the painting fills a resized preview window: "stretch" to fill it, or "letterbox" to
+    /// fit it at its own aspect ratio.
+    #[structopt(long = "resize_behavior", default_value = "stretch")]
+    pub resize_behavior: ResizeBehavior,
+
+    /// Path of an animated GIF to capture frames into, at the recording framerate. Frames delayed
+    /// from saving are not captured, nor are frames of a resized window which differ in size
+    /// from the first captured.
+    #[structopt(long = "gif", parse(from_os_str))]
+    pub gif: Option<PathBuf>,
+
+    /// The number of colors in the palette of each GIF frame, up to 256.
+    #[structopt(long = "gif_colors", default_value = "256")]
+    pub gif_colors: u16,
+
+    /// The length in frames of a seamless loop. The frame number and time of the composition wrap
+    /// to zero after each loop. Rendering to file saves one loop, from the first frame after the
+    /// delay at which the loop begins.
+    #[structopt(long = "loop_frames")]
+    pub loop_frames: Option<usize>,
+
+    /// Whether a loop plays forward and then backward, so the frame number counts up to the end
+    /// of the loop and back down to zero. Rendering to file saves only the frames forward, zero
+    /// through the end of the loop.
+    #[structopt(long = "ping_pong", requires = "loop_frames")]
+    pub ping_pong: bool,
+
+    /// Arguments the options do not recognize, left for the painting's own use. Only filled by
+    /// `Options::from_args_with_extra()`.
+    #[structopt(skip)]
+    pub extra_args: Vec<String>,
+}
+
+/// How the painting fills a resized preview window.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ResizeBehavior {
+    /// The painting is stretched over the whole window.
+    Stretch,
+    /// The painting is scaled to fit the window at its own aspect ratio, centered between black
+    /// bars.
+    Letterbox,
+}
+
+impl std::str::FromStr for ResizeBehavior {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "stretch" => Ok(ResizeBehavior::Stretch),
+            "letterbox" => Ok(ResizeBehavior::Letterbox),
+            _ => bail!(
+                "Unknown resize behavior {:?}; expected \"stretch\" or \"letterbox\"",
+                s
+            ),
+        }
+    }
 }
 
 /// The world in which the painting takes place.
@@ -136,14 +255,294 @@ pub struct World {
     pub scale: f32,
 
     /// The total number of frames in this painting.
+    ///
+    /// Once they are rendered the run ends cleanly, closing the preview window unless in
+    /// brainstorm mode. Without a frame count, painting continues until the window is closed.
     #[structopt(short = "f", long = "frames")]
     pub frames: Option<usize>,
 
     /// The number of frames (to try) to render per second.
-    #[structopt(short = "r", long = "frames_per_second", default_value = "24")]
+    #[structopt(
+        short = "r",
+        long = "frames_per_second",
+        visible_alias = "fps",
+        default_value = "24"
+    )]
     pub framerate: usize,
 }
 
+/// `DEFAULT_SAMPLE_COUNT` as the default of the `--msaa` argument.
+fn default_samples_arg() -> &'static str {
+    static ARG: OnceLock<String> = OnceLock::new();
+    ARG.get_or_init(|| DEFAULT_SAMPLE_COUNT.to_string())
+}
+
+impl Options {
+    /// The frame rate of the composition's time: the recording frame rate if one is set, or else
+    /// the world's framerate.
+    pub fn logical_framerate(&self) -> usize {
+        self.record_fps.unwrap_or(self.world.framerate)
+    }
+
+    /// The number of frames before a loop repeats: the length of the loop, or in ping-pong mode
+    /// the frames forward and back without repeating either end. `None` if there is no loop.
+    pub fn loop_period(&self) -> Option<usize> {
+        match self.loop_frames {
+            Some(loop_frames) if self.ping_pong && loop_frames > 1 => Some(2 * (loop_frames - 1)),
+            Some(loop_frames) if loop_frames > 0 => Some(loop_frames),
+            _ => None,
+        }
+    }
+
+    /// The frame of the composition at the given number of frames since the run began: wrapped
+    /// to the length of the loop if there is one, or reflected back and forth over it in
+    /// ping-pong mode.
+    pub fn loop_frame(&self, frame: usize) -> usize {
+        let (loop_frames, period) = match (self.loop_frames, self.loop_period()) {
+            (Some(loop_frames), Some(period)) => (loop_frames, period),
+            _ => return frame,
+        };
+        let phase = frame % period;
+        if phase < loop_frames {
+            phase
+        } else {
+            period - phase
+        }
+    }
+
+    /// The number of frames rendered without saving when rendering to file: the delay, or if
+    /// there is a loop, the delay extended to just before the loop next begins, so that saved
+    /// files begin at frame zero.
+    pub fn save_delay(&self) -> usize {
+        match self.loop_period() {
+            Some(period) => (self.delay / period + 1) * period - 1,
+            None => self.delay,
+        }
+    }
+
+    /// The number of frames saved when rendering to file: the frames of the world, but no more
+    /// than one pass forward through the loop if there is one.
+    pub fn saved_frames(&self) -> Option<usize> {
+        match self.loop_frames {
+            Some(loop_frames) => Some(
+                self.world
+                    .frames
+                    .map_or(loop_frames, |f| f.min(loop_frames)),
+            ),
+            None => self.world.frames,
+        }
+    }
+
+    /// Parses options from the command line like `from_args()`, but passes arguments it does not
+    /// recognize through to `extra_args` rather than failing on them, and accepts `--size N` for
+    /// a square painting N wide and high. Prints usage and exits for `--help`.
+    ///
+    /// An unrecognized flag takes the argument after it as its value unless that begins with `-`.
+    /// A repeated option takes its last value.
+    pub fn from_args_with_extra() -> Result<Self> {
+        Self::from_iter_with_extra(std::env::args())
+    }
+
+    /// Parses options from the given arguments, the first being the program name. See
+    /// `from_args_with_extra()`.
+    pub fn from_iter_with_extra(args: impl IntoIterator<Item = impl Into<String>>) -> Result<Self> {
+        let mut args: Vec<String> = args.into_iter().map(Into::into).collect();
+        let mut extra_args = vec![];
+        loop {
+            let app = Self::clap().setting(AppSettings::AllArgsOverrideSelf).arg(
+                Arg::with_name("size")
+                    .long("size")
+                    .takes_value(true)
+                    .help("The width and height in coordinate space of a square painting"),
+            );
+            let e = match app.get_matches_from_safe(&args) {
+                Ok(matches) => {
+                    let mut options = Self::from_clap(&matches);
+                    if let Some(size) = matches.value_of("size") {
+                        let size = size
+                            .parse()
+                            .map_err(|_| format_err!("Invalid painting size {:?}", size))?;
+                        options.world.width = size;
+                        options.world.height = size;
+                    }
+                    options.extra_args = extra_args;
+                    return Ok(options);
+                }
+                Err(e) => e,
+            };
+
+            match e.kind {
+                ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => e.exit(),
+                ErrorKind::UnknownArgument => {}
+                _ => return Err(e.into()),
+            }
+            let unknown = e.info.as_ref().and_then(|info| info.first()).cloned();
+            let position = unknown.as_ref().and_then(|unknown| {
+                args.iter()
+                    .skip(1)
+                    .position(|arg| arg == unknown || arg.starts_with(&format!("{}=", unknown)))
+            });
+            let i = match position {
+                Some(position) => position + 1,
+                None => return Err(e.into()),
+            };
+
+            let flag = args.remove(i);
+            let takes_value = flag.starts_with('-')
+                && !flag.contains('=')
+                && args.get(i).is_some_and(|next| !next.starts_with('-'));
+            extra_args.push(flag);
+            if takes_value {
+                extra_args.push(args.remove(i));
+            }
+        }
+    }
+
+    /// Returns a builder for constructing options in code rather than from the command line.
+    pub fn builder() -> OptionsBuilder {
+        OptionsBuilder::default()
+    }
+}
+
+/// A builder for `Options`.
+///
+/// Unset fields take the same defaults as the command line interface.
+#[derive(Debug, Clone)]
+pub struct OptionsBuilder {
+    options: Options,
+}
+
+impl Default for OptionsBuilder {
+    fn default() -> Self {
+        Self {
+            options: Options::from_iter(std::iter::once("valora")),
+        }
+    }
+}
+
+impl OptionsBuilder {
+    /// Sets the width and height in coordinate space of the painting.
+    pub fn size(mut self, width: f32, height: f32) -> Self {
+        self.options.world.width = width;
+        self.options.world.height = height;
+        self
+    }
+
+    /// Sets the scale of the output.
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.options.world.scale = scale;
+        self
+    }
+
+    /// Sets the RNG seed for the painting.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.options.world.seed = seed;
+        self
+    }
+
+    /// Sets the total number of frames in the painting.
+    pub fn frames(mut self, frames: usize) -> Self {
+        self.options.world.frames = Some(frames);
+        self
+    }
+
+    /// Sets the number of frames (to try) to render per second.
+    pub fn framerate(mut self, framerate: usize) -> Self {
+        self.options.world.framerate = framerate;
+        self
+    }
+
+    /// Enables or disables brainstorm mode.
+    pub fn brainstorm(mut self, brainstorm: bool) -> Self {
+        self.options.brainstorm = brainstorm;
+        self
+    }
+
+    /// Sets the number of frames to delay saving to file.
+    pub fn delay(mut self, delay: usize) -> Self {
+        self.options.delay = delay;
+        self
+    }
+
+    /// Sets the number of samples per pixel used to anti-alias painting.
+    pub fn samples(mut self, samples: u32) -> Self {
+        self.options.samples = samples;
+        self
+    }
+
+    /// Sets the output path prefix, which causes frames to be rendered to file.
+    pub fn output(mut self, output: impl Into<PathBuf>) -> Self {
+        self.options.output = Some(output.into());
+        self
+    }
+
+    /// Sets the frame rate at which recorded frames play back.
+    pub fn record_fps(mut self, record_fps: usize) -> Self {
+        self.options.record_fps = Some(record_fps);
+        self
+    }
+
+    /// Allows the preview window to be resized, with the painting filling it as given.
+    pub fn resizable(mut self, resize_behavior: ResizeBehavior) -> Self {
+        self.options.resizable = true;
+        self.options.resize_behavior = resize_behavior;
+        self
+    }
+
+    /// Sets the path of an animated GIF to capture frames into.
+    pub fn gif(mut self, gif: impl Into<PathBuf>) -> Self {
+        self.options.gif = Some(gif.into());
+        self
+    }
+
+    /// Sets the number of colors in the palette of each GIF frame.
+    pub fn gif_colors(mut self, gif_colors: u16) -> Self {
+        self.options.gif_colors = gif_colors;
+        self
+    }
+
+    /// Sets the length in frames of a seamless loop.
+    pub fn loop_frames(mut self, loop_frames: usize) -> Self {
+        self.options.loop_frames = Some(loop_frames);
+        self
+    }
+
+    /// Enables or disables playing the loop forward and then backward.
+    pub fn ping_pong(mut self, ping_pong: bool) -> Self {
+        self.options.ping_pong = ping_pong;
+        self
+    }
+
+    /// Validates and returns the options.
+    pub fn build(self) -> Result<Options> {
+        let world = &self.options.world;
+        if world.width <= 0. || world.height <= 0. {
+            bail!(
+                "Painting size must be positive; got {}x{}",
+                world.width,
+                world.height
+            );
+        }
+        if world.scale <= 0. {
+            bail!("Painting scale must be positive; got {}", world.scale);
+        }
+        if world.framerate == 0 {
+            bail!("Framerate must be positive");
+        }
+        if self.options.record_fps == Some(0) {
+            bail!("Recording framerate must be positive");
+        }
+        if self.options.loop_frames == Some(0) {
+            bail!("Loop length must be positive");
+        }
+        if self.options.ping_pong && self.options.loop_frames.is_none() {
+            bail!("Ping-pong mode requires a loop length");
+        }
+
+        Ok(self.options)
+    }
+}
+
 impl World {
     /// Normalizes coordinates into the range [0, 1] by dividing them by the coordinate space dimensions.
     pub fn normalize(&self, p: P2) -> P2 {
@@ -188,16 +587,70 @@ pub trait Artist: Sized {
     fn paint(&mut self, ctx: Context, canvas: &mut Canvas);
 }
 
+/// An artist composed of two others, painting `b` over `a` with a blend mode.
+///
+/// Both artists are set up with the same gpu, world and rng, `a` first, and take turns with the
+/// context of each frame. `b` is painted in a layer, starting from the drawing state `a` leaves
+/// behind.
+///
+/// Set up as an `Artist`, the blend mode is `BlendMode::Normal`; use `Composed::new()` with
+/// `run_fn()` for others.
+#[derive(Debug, Clone)]
+pub struct Composed<A, B> {
+    pub a: A,
+    pub b: B,
+    pub blend: BlendMode,
+}
+
+impl<A, B> Composed<A, B> {
+    pub fn new(a: A, b: B, blend: BlendMode) -> Self {
+        Self { a, b, blend }
+    }
+}
+
+impl<A: Artist, B: Artist> Artist for Composed<A, B> {
+    fn setup(gpu: Gpu, world: World, rng: &mut StdRng) -> Result<Self> {
+        let a = A::setup(gpu.clone(), world, rng)?;
+        let b = B::setup(gpu, world, rng)?;
+        Ok(Self::new(a, b, BlendMode::Normal))
+    }
+
+    fn paint(&mut self, mut ctx: Context, canvas: &mut Canvas) {
+        self.a.paint(ctx.reborrow(), canvas);
+        canvas.push_layer(1., self.blend);
+        self.b.paint(ctx, canvas);
+        // Only fails if `b` popped more layers than it pushed, ending this layer itself.
+        let popped = canvas.pop_layer();
+        debug_assert!(
+            popped.is_ok(),
+            "The second artist of a composition popped a layer it did not push"
+        );
+    }
+}
+
 /// Run an artist defined by raw functions.
 ///
 /// Takes a function that produces the function that should paint each frame.
 pub fn run_fn<F>(options: Options, f: impl Fn(Gpu, World, &mut StdRng) -> Result<F>) -> Result<()>
+where
+    F: FnMut(Context, &mut Canvas),
+{
+    run_fn_on(options, None, f)
+}
+
+/// Runs an artist defined by raw functions, painting with the given headless GPU handle when
+/// rendering to files instead of creating one.
+fn run_fn_on<F>(
+    options: Options,
+    headless: Option<Gpu>,
+    f: impl Fn(Gpu, World, &mut StdRng) -> Result<F>,
+) -> Result<()>
 where
     F: FnMut(Context, &mut Canvas),
 {
     let (output_width, output_height) = (
-        (options.world.width as f32 * options.world.scale) as u32,
-        (options.world.height as f32 * options.world.scale) as u32,
+        (options.world.width * options.world.scale) as u32,
+        (options.world.height * options.world.scale) as u32,
     );
 
     let number_width = options
@@ -209,11 +662,15 @@ where
         .count();
 
     let (gpu, mut strategy) = if let Some(base_path) = options.output.clone() {
-        let (gpu, _) = Gpu::new()?;
+        let gpu = match headless {
+            Some(gpu) => gpu,
+            None => Gpu::new_headless()?,
+        }
+        .with_samples(options.samples);
         let buffer = gpu.build_texture(output_width, output_height)?;
 
         std::fs::create_dir_all(&base_path)
-            .expect(&format!("To create save directory {}", base_path.display()));
+            .unwrap_or_else(|_| panic!("To create save directory {}", base_path.display()));
 
         (
             gpu,
@@ -232,8 +689,12 @@ where
             },
         )
     } else {
-        let (gpu, events_loop, (screen_width, screen_height)) =
-            Gpu::with_window(output_width, output_height)?;
+        let (gpu, events_loop, (screen_width, screen_height)) = Gpu::with_window(
+            output_width,
+            output_height,
+            options.samples,
+            options.resizable,
+        )?;
         let buffer = gpu.build_texture(screen_width, screen_height)?;
 
         let wait = Duration::from_secs_f64(1. / options.world.framerate as f64);
@@ -245,6 +706,7 @@ where
         (
             gpu,
             RenderStrategy::Screen {
+                window_size: (screen_width, screen_height),
                 events_loop,
                 wait,
                 buffer,
@@ -258,6 +720,11 @@ where
         )
     };
 
+    let mut gif = options
+        .gif
+        .as_ref()
+        .map(|path| GifExporter::new(path, options.logical_framerate() as u32, options.gif_colors));
+
     let mut current_seed = options.world.seed;
     let mut render_count = 0;
     loop {
@@ -279,8 +746,14 @@ where
                 ..options.clone()
             },
             rng: &mut rng,
-            output_width: output_width,
-            output_height: output_height,
+            named_rngs: HashMap::new(),
+            gif: gif.as_mut(),
+            mouse_position: None,
+            keys_held: HashSet::new(),
+            mouse_events: vec![],
+            resized: None,
+            output_width,
+            output_height,
         };
 
         let report = renderer.render_frames(|ctx, canvas| paint_fn(ctx, canvas))?;
@@ -293,7 +766,7 @@ where
             }
         } else if options.brainstorm
             && options.output.is_some()
-            && render_count < options.world.frames.unwrap_or(usize::max_value())
+            && render_count < options.world.frames.unwrap_or(usize::MAX)
         {
             current_seed += 1;
         } else if report.explicit_quit || !options.brainstorm || options.output.is_some() {
@@ -303,6 +776,10 @@ where
         render_count += 1;
     }
 
+    if let Some(gif) = gif {
+        gif.finish()?;
+    }
+
     Ok(())
 }
 
@@ -313,3 +790,208 @@ pub fn run<A: Artist>(options: Options) -> Result<()> {
         Ok(move |ctx: Context, canvas: &mut Canvas| artist.paint(ctx, canvas))
     })
 }
+
+/// The progress of a batch render, reported as each seed finishes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BatchProgress {
+    /// The seed which finished rendering.
+    pub seed: u64,
+    /// The number of seeds rendered so far, including this one.
+    pub done: usize,
+    /// The number of seeds in the batch.
+    pub total: usize,
+}
+
+/// Run an artist defined by raw functions once for each seed, saving the frames of each to
+/// `<output>/<seed>/` and reporting progress to stdout. A GIF path in the options is likewise
+/// placed in each seed's directory.
+///
+/// Rendering is headless, and requires an output path. With the `parallel` feature, seeds are
+/// rendered concurrently across rayon's worker threads. See `run_batch_fn_with_progress()`.
+pub fn run_batch_fn<F>(
+    options: Options,
+    seeds: impl IntoIterator<Item = u64>,
+    f: impl Fn(Gpu, World, &mut StdRng) -> Result<F> + Sync,
+) -> Result<()>
+where
+    F: FnMut(Context, &mut Canvas),
+{
+    run_batch_fn_with_progress(options, seeds, f, |progress| {
+        println!(
+            "Rendered seed {} ({}/{})",
+            progress.seed, progress.done, progress.total
+        )
+    })
+}
+
+/// Run an artist defined by raw functions once for each seed as `run_batch_fn()` does, calling
+/// `on_progress` on the calling thread as each seed finishes instead of printing.
+///
+/// Each thread rendering seeds keeps one headless GPU context for all the seeds it renders, as
+/// contexts can only be used on the thread which created them. Contexts on different threads
+/// share no resources, so each compiles its own shaders once.
+pub fn run_batch_fn_with_progress<F>(
+    options: Options,
+    seeds: impl IntoIterator<Item = u64>,
+    f: impl Fn(Gpu, World, &mut StdRng) -> Result<F> + Sync,
+    mut on_progress: impl FnMut(BatchProgress),
+) -> Result<()>
+where
+    F: FnMut(Context, &mut Canvas),
+{
+    thread_local! {
+        static BATCH_GPU: std::cell::RefCell<Option<Gpu>> = const { std::cell::RefCell::new(None) };
+    }
+
+    let base_path = match options.output.clone() {
+        Some(base_path) => base_path,
+        None => bail!("Batch rendering requires an output path"),
+    };
+
+    let seeds: Vec<u64> = seeds.into_iter().collect();
+    let render_seed = |seed: u64| -> Result<()> {
+        let gpu = BATCH_GPU.with(|cached| -> Result<Gpu> {
+            let mut cached = cached.borrow_mut();
+            match &*cached {
+                Some(gpu) => Ok(gpu.clone()),
+                None => {
+                    let gpu = Gpu::new_headless()?;
+                    *cached = Some(gpu.clone());
+                    Ok(gpu)
+                }
+            }
+        })?;
+
+        let seed_path = base_path.join(seed.to_string());
+        run_fn_on(
+            Options {
+                world: World {
+                    seed,
+                    ..options.world
+                },
+                brainstorm: false,
+                output: Some(seed_path.clone()),
+                gif: options
+                    .gif
+                    .as_ref()
+                    .and_then(|gif| gif.file_name())
+                    .map(|name| seed_path.join(name)),
+                ..options.clone()
+            },
+            Some(gpu),
+            &f,
+        )
+    };
+
+    let total = seeds.len();
+    #[cfg(feature = "parallel")]
+    {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::scope(|scope| {
+            let rendering = scope.spawn(|| {
+                seeds.par_iter().try_for_each_with(sender, |sender, seed| {
+                    render_seed(*seed)?;
+                    // The receiver only hangs up once rendering has finished.
+                    let _ = sender.send(*seed);
+                    Ok(())
+                })
+            });
+            for (done, seed) in receiver.iter().enumerate() {
+                on_progress(BatchProgress {
+                    seed,
+                    done: done + 1,
+                    total,
+                });
+            }
+            rendering
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        })
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        seeds.iter().enumerate().try_for_each(|(done, seed)| {
+            render_seed(*seed)?;
+            on_progress(BatchProgress {
+                seed: *seed,
+                done: done + 1,
+                total,
+            });
+            Ok(())
+        })
+    }
+}
+
+/// Run an artist once for each seed. See `run_batch_fn()`.
+pub fn run_batch<A: Artist>(options: Options, seeds: impl IntoIterator<Item = u64>) -> Result<()> {
+    run_batch_fn(options, seeds, |gpu, world, rng| {
+        let mut artist = A::setup(gpu, world, rng)?;
+        Ok(move |ctx: Context, canvas: &mut Canvas| artist.paint(ctx, canvas))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn passes_unknown_arguments_through() {
+        let options = Options::from_iter_with_extra(vec![
+            "sketch",
+            "--size",
+            "300",
+            "--density",
+            "0.5",
+            "--fps",
+            "30",
+            "--wobbly",
+            "--mode=fast",
+            "-e",
+            "7",
+        ])
+        .unwrap();
+
+        assert_eq!((options.world.width, options.world.height), (300., 300.));
+        assert_eq!(options.world.framerate, 30);
+        assert_eq!(options.world.seed, 7);
+        assert_eq!(options.samples, DEFAULT_SAMPLE_COUNT);
+        assert_eq!(
+            options.extra_args,
+            vec!["--density", "0.5", "--wobbly", "--mode=fast"]
+        );
+    }
+
+    /// The loop frames of the frames saved when rendering to file, which are those after the
+    /// save delay.
+    fn saved_loop_frames(options: &Options) -> Vec<usize> {
+        let start = options.save_delay() + 1;
+        (start..start + options.saved_frames().unwrap())
+            .map(|frame| options.loop_frame(frame))
+            .collect()
+    }
+
+    #[test]
+    fn loops_start_at_frame_zero() {
+        let options = Options::builder().loop_frames(4).build().unwrap();
+        let shown: Vec<usize> = (0..6).map(|frame| options.loop_frame(frame)).collect();
+
+        assert_eq!(shown, vec![0, 1, 2, 3, 0, 1]);
+        assert_eq!(saved_loop_frames(&options), vec![0, 1, 2, 3]);
+        let delayed = Options::builder().delay(5).loop_frames(4).build().unwrap();
+        assert_eq!(delayed.save_delay(), 7);
+        assert_eq!(saved_loop_frames(&delayed), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn saved_ping_pong_loops_only_go_forward() {
+        let options = Options::builder()
+            .loop_frames(4)
+            .ping_pong(true)
+            .build()
+            .unwrap();
+        let shown: Vec<usize> = (0..7).map(|frame| options.loop_frame(frame)).collect();
+
+        assert_eq!(shown, vec![0, 1, 2, 3, 2, 1, 0]);
+        assert_eq!(saved_loop_frames(&options), vec![0, 1, 2, 3]);
+    }
+}
@@ -1,7 +1,9 @@
 //! Noise sampling.
 
 use crate::{P2, P3};
-use noise::NoiseFn;
+use noise::{NoiseFn, Perlin, Seedable};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use std::cell::RefCell;
 
 /// A trait for types which are sources of noise, samplable by type `P`.
 pub trait NoiseSrc<P> {
@@ -26,3 +28,371 @@ where
         self.get([p.x as f64, p.y as f64, p.z as f64]) as f32
     }
 }
+
+thread_local! {
+    /// The most recently used Perlin source, since building a permutation table for every sample
+    /// would dominate the cost of sampling.
+    static PERLIN: RefCell<Option<(u32, Perlin)>> = const { RefCell::new(None) };
+
+    /// The most recently used simplex permutation table, cached like `PERLIN`.
+    static SIMPLEX: RefCell<Option<(u32, Box<[u8; 512]>)>> = const { RefCell::new(None) };
+}
+
+fn with_perlin<T>(seed: u32, f: impl FnOnce(&Perlin) -> T) -> T {
+    PERLIN.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        match cache.as_ref() {
+            Some((cached, _)) if *cached == seed => {}
+            _ => *cache = Some((seed, Perlin::new().set_seed(seed))),
+        }
+        f(&cache.as_ref().unwrap().1)
+    })
+}
+
+/// Samples two dimensional Perlin noise in [-1, 1]. The seed permutes the noise's gradients.
+pub fn perlin2(x: f32, y: f32, seed: u32) -> f32 {
+    with_perlin(seed, |perlin| perlin.get([x as f64, y as f64]) as f32).clamp(-1., 1.)
+}
+
+/// Samples three dimensional Perlin noise in [-1, 1]. The seed permutes the noise's gradients.
+pub fn perlin3(x: f32, y: f32, z: f32, seed: u32) -> f32 {
+    with_perlin(seed, |perlin| {
+        perlin.get([x as f64, y as f64, z as f64]) as f32
+    })
+    .clamp(-1., 1.)
+}
+
+/// Samples fractal Brownian motion in [-1, 1]: the sum of `octaves` layers of Perlin noise, each
+/// `lacunarity` times the frequency and `gain` times the amplitude of the last.
+pub fn fbm2(x: f32, y: f32, seed: u32, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+    let mut total = 0.;
+    let mut amplitudes = 0.;
+    let (mut frequency, mut amplitude) = (1., 1.);
+    for octave in 0..octaves {
+        // Each octave has its own gradients, so that their features do not line up.
+        total += perlin2(x * frequency, y * frequency, seed.wrapping_add(octave)) * amplitude;
+        amplitudes += amplitude;
+        frequency *= lacunarity;
+        amplitude *= gain;
+    }
+
+    if amplitudes == 0. {
+        0.
+    } else {
+        total / amplitudes
+    }
+}
+
+fn with_permutation<T>(seed: u32, f: impl FnOnce(&[u8; 512]) -> T) -> T {
+    SIMPLEX.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        match cache.as_ref() {
+            Some((cached, _)) if *cached == seed => {}
+            _ => {
+                let mut table: Vec<u8> = (0..=255).collect();
+                table.shuffle(&mut StdRng::seed_from_u64(u64::from(seed)));
+                let mut permutation = Box::new([0; 512]);
+                for (i, entry) in permutation.iter_mut().enumerate() {
+                    *entry = table[i % 256];
+                }
+                *cache = Some((seed, permutation));
+            }
+        }
+        f(&cache.as_ref().unwrap().1)
+    })
+}
+
+/// The gradients of simplex noise: the midpoints of the edges of a cube.
+const GRADIENTS: [[f32; 3]; 12] = [
+    [1., 1., 0.],
+    [-1., 1., 0.],
+    [1., -1., 0.],
+    [-1., -1., 0.],
+    [1., 0., 1.],
+    [-1., 0., 1.],
+    [1., 0., -1.],
+    [-1., 0., -1.],
+    [0., 1., 1.],
+    [0., -1., 1.],
+    [0., 1., -1.],
+    [0., -1., -1.],
+];
+
+/// Samples two dimensional simplex noise in [-1, 1], following Stefan Gustavson's
+/// implementation. The seed permutes the noise's gradients.
+pub fn simplex2(x: f32, y: f32, seed: u32) -> f32 {
+    let skew = 0.5 * (3f32.sqrt() - 1.);
+    let unskew = (3. - 3f32.sqrt()) / 6.;
+
+    // Find the simplex cell containing the point, and the point's offset from its corners.
+    let s = (x + y) * skew;
+    let (i, j) = ((x + s).floor(), (y + s).floor());
+    let t = (i + j) * unskew;
+    let (x0, y0) = (x - (i - t), y - (j - t));
+    let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+    let corners = [
+        (x0, y0, 0, 0),
+        (x0 - i1 as f32 + unskew, y0 - j1 as f32 + unskew, i1, j1),
+        (x0 - 1. + 2. * unskew, y0 - 1. + 2. * unskew, 1, 1),
+    ];
+
+    let (i, j) = ((i as i32 & 255) as usize, (j as i32 & 255) as usize);
+    let total: f32 = with_permutation(seed, |perm| {
+        corners
+            .iter()
+            .map(|&(dx, dy, di, dj)| {
+                let falloff = 0.5 - dx * dx - dy * dy;
+                if falloff < 0. {
+                    return 0.;
+                }
+                let gradient = GRADIENTS[perm[i + di + perm[j + dj] as usize] as usize % 12];
+                falloff.powi(4) * (gradient[0] * dx + gradient[1] * dy)
+            })
+            .sum()
+    });
+    (70. * total).clamp(-1., 1.)
+}
+
+/// Samples three dimensional simplex noise in [-1, 1], following Stefan Gustavson's
+/// implementation. The seed permutes the noise's gradients.
+pub fn simplex3(x: f32, y: f32, z: f32, seed: u32) -> f32 {
+    let skew = 1. / 3.;
+    let unskew = 1. / 6.;
+
+    let s = (x + y + z) * skew;
+    let (i, j, k) = ((x + s).floor(), (y + s).floor(), (z + s).floor());
+    let t = (i + j + k) * unskew;
+    let (x0, y0, z0) = (x - (i - t), y - (j - t), z - (k - t));
+
+    // Find which of the six tetrahedra in the cube contains the point, by the order of the
+    // offsets.
+    let ((i1, j1, k1), (i2, j2, k2)) = if x0 >= y0 {
+        if y0 >= z0 {
+            ((1, 0, 0), (1, 1, 0))
+        } else if x0 >= z0 {
+            ((1, 0, 0), (1, 0, 1))
+        } else {
+            ((0, 0, 1), (1, 0, 1))
+        }
+    } else if y0 < z0 {
+        ((0, 0, 1), (0, 1, 1))
+    } else if x0 < z0 {
+        ((0, 1, 0), (0, 1, 1))
+    } else {
+        ((0, 1, 0), (1, 1, 0))
+    };
+    let corner = |di: usize, dj: usize, dk: usize, n: f32| {
+        (
+            x0 - di as f32 + n * unskew,
+            y0 - dj as f32 + n * unskew,
+            z0 - dk as f32 + n * unskew,
+            di,
+            dj,
+            dk,
+        )
+    };
+    let corners = [
+        corner(0, 0, 0, 0.),
+        corner(i1, j1, k1, 1.),
+        corner(i2, j2, k2, 2.),
+        corner(1, 1, 1, 3.),
+    ];
+
+    let (i, j, k) = (
+        (i as i32 & 255) as usize,
+        (j as i32 & 255) as usize,
+        (k as i32 & 255) as usize,
+    );
+    let total: f32 = with_permutation(seed, |perm| {
+        corners
+            .iter()
+            .map(|&(dx, dy, dz, di, dj, dk)| {
+                let falloff = 0.6 - dx * dx - dy * dy - dz * dz;
+                if falloff < 0. {
+                    return 0.;
+                }
+                let hash = perm[i + di + perm[j + dj + perm[k + dk] as usize] as usize];
+                let gradient = GRADIENTS[hash as usize % 12];
+                falloff.powi(4) * (gradient[0] * dx + gradient[1] * dy + gradient[2] * dz)
+            })
+            .sum()
+    });
+    (32. * total).clamp(-1., 1.)
+}
+
+/// A two dimensional noise function.
+///
+/// This is implemented for closures, so that any function of a point can be used where noise is
+/// expected, such as in `FbmNoise`.
+pub trait NoiseFn2 {
+    fn eval(&self, x: f32, y: f32) -> f32;
+}
+
+/// A three dimensional noise function.
+///
+/// Its method is named apart from `NoiseFn2::eval()` so that types implementing both can be
+/// sampled without qualification.
+pub trait NoiseFn3 {
+    fn eval3(&self, x: f32, y: f32, z: f32) -> f32;
+}
+
+impl<F: Fn(f32, f32) -> f32> NoiseFn2 for F {
+    fn eval(&self, x: f32, y: f32) -> f32 {
+        self(x, y)
+    }
+}
+
+impl<F: Fn(f32, f32, f32) -> f32> NoiseFn3 for F {
+    fn eval3(&self, x: f32, y: f32, z: f32) -> f32 {
+        self(x, y, z)
+    }
+}
+
+/// Seeded Perlin noise, as sampled by `perlin2()` and `perlin3()`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PerlinNoise {
+    pub seed: u32,
+}
+
+impl PerlinNoise {
+    pub fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+}
+
+impl NoiseFn2 for PerlinNoise {
+    fn eval(&self, x: f32, y: f32) -> f32 {
+        perlin2(x, y, self.seed)
+    }
+}
+
+impl NoiseFn3 for PerlinNoise {
+    fn eval3(&self, x: f32, y: f32, z: f32) -> f32 {
+        perlin3(x, y, z, self.seed)
+    }
+}
+
+/// Seeded simplex noise, as sampled by `simplex2()` and `simplex3()`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SimplexNoise {
+    pub seed: u32,
+}
+
+impl SimplexNoise {
+    pub fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+}
+
+impl NoiseFn2 for SimplexNoise {
+    fn eval(&self, x: f32, y: f32) -> f32 {
+        simplex2(x, y, self.seed)
+    }
+}
+
+impl NoiseFn3 for SimplexNoise {
+    fn eval3(&self, x: f32, y: f32, z: f32) -> f32 {
+        simplex3(x, y, z, self.seed)
+    }
+}
+
+/// Fractal Brownian motion: the sum of `octaves` layers of the inner noise, each `lacunarity`
+/// times the frequency and `gain` times the amplitude of the last, normalized to the range of
+/// the inner noise.
+///
+/// `FbmNoise::new(SimplexNoise::new(seed)).octaves(6).lacunarity(2.).gain(0.5)`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FbmNoise<N> {
+    pub inner: N,
+    pub octaves: u32,
+    pub lacunarity: f32,
+    pub gain: f32,
+}
+
+impl<N> FbmNoise<N> {
+    /// Stacks six octaves of the inner noise with a lacunarity of 2 and a gain of 0.5.
+    pub fn new(inner: N) -> Self {
+        Self {
+            inner,
+            octaves: 6,
+            lacunarity: 2.,
+            gain: 0.5,
+        }
+    }
+
+    pub fn octaves(self, octaves: u32) -> Self {
+        Self { octaves, ..self }
+    }
+
+    pub fn lacunarity(self, lacunarity: f32) -> Self {
+        Self { lacunarity, ..self }
+    }
+
+    pub fn gain(self, gain: f32) -> Self {
+        Self { gain, ..self }
+    }
+
+    /// Sums the octaves of `sample`, which samples the inner noise at a frequency.
+    fn stack(&self, sample: impl Fn(f32) -> f32) -> f32 {
+        let mut total = 0.;
+        let mut amplitudes = 0.;
+        let (mut frequency, mut amplitude) = (1., 1.);
+        for _ in 0..self.octaves {
+            total += sample(frequency) * amplitude;
+            amplitudes += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+
+        if amplitudes == 0. {
+            0.
+        } else {
+            total / amplitudes
+        }
+    }
+}
+
+impl<N: NoiseFn2> NoiseFn2 for FbmNoise<N> {
+    fn eval(&self, x: f32, y: f32) -> f32 {
+        self.stack(|frequency| self.inner.eval(x * frequency, y * frequency))
+    }
+}
+
+impl<N: NoiseFn3> NoiseFn3 for FbmNoise<N> {
+    fn eval3(&self, x: f32, y: f32, z: f32) -> f32 {
+        self.stack(|frequency| {
+            self.inner
+                .eval3(x * frequency, y * frequency, z * frequency)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn simplex_is_seeded_and_bounded() {
+        let samples = |seed| -> Vec<f32> {
+            (0..100)
+                .map(|i| simplex2(i as f32 * 0.37, i as f32 * 0.11, seed))
+                .collect()
+        };
+
+        assert_eq!(samples(1), samples(1));
+        assert_ne!(samples(1), samples(2));
+        assert!(samples(3).iter().all(|v| (-1. ..=1.).contains(v)));
+        assert!(samples(3).iter().any(|v| *v != 0.));
+        assert_eq!(simplex3(0., 0., 0., 4), 0.);
+    }
+
+    #[test]
+    fn fbm_normalizes_octaves() {
+        let constant = |_: f32, _: f32| 0.5;
+        assert_eq!(FbmNoise::new(constant).eval(3., 4.), 0.5);
+        assert_eq!(FbmNoise::new(constant).octaves(0).eval(3., 4.), 0.);
+
+        let fbm = FbmNoise::new(SimplexNoise::new(7)).octaves(4);
+        assert_eq!(fbm.eval(0.3, 0.4), fbm.eval(0.3, 0.4));
+    }
+}
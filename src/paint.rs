@@ -1,7 +1,15 @@
 //! Paint trait and implementations.
 
-use crate::canvas::Canvas;
+use crate::{
+    canvas::Canvas,
+    forms::{Ellipse, Rect, DEFAULT_MITER_LIMIT},
+    path::{bounding_box, flatten},
+    Transform, P2, V2,
+};
+use glium::texture::Texture2d;
 use lyon_path::PathEvent;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::rc::Rc;
 
 /// A trait for types which can be represented on a `Canvas`.
 pub trait Paint {
@@ -33,20 +41,543 @@ impl<P: Paint> Paint for Stroked<P> {
     }
 }
 
+/// Paints a path with a dashed stroke.
+pub struct DashedStroked<D> {
+    pub element: D,
+    pub width: f32,
+    /// Alternating lengths of dashes and the gaps between them, as in SVG's `stroke-dasharray`.
+    /// A pattern with an odd number of lengths is repeated to make it even.
+    pub dash_pattern: Vec<f32>,
+    /// The distance into the pattern at which the start of the path lies. Changing this over
+    /// time makes the dashes crawl along the path.
+    pub dash_offset: f32,
+}
+
+impl<P: Paint> Paint for DashedStroked<P> {
+    fn paint(&self, comp: &mut Canvas) {
+        let path = comp.capture_path(&self.element);
+        comp.set_stroke_width(self.width);
+        for dash in dashes(path.iter(), &self.dash_pattern, self.dash_offset) {
+            let mut points = dash.into_iter();
+            if let Some(start) = points.next() {
+                comp.move_to(start);
+                points.for_each(|p| {
+                    comp.line_to(p);
+                });
+                comp.stroke();
+            }
+        }
+    }
+}
+
+/// Paints a stroke whose width varies along the path, for calligraphic or brush-like lines.
+///
+/// `width_fn` maps the fraction of the length of each subpath travelled, from 0 at its start to
+/// 1 at its end, to the width there. The stroke is filled as an outline, so turns much tighter
+/// than the width may leave gaps.
+pub struct TaperedStroked<D> {
+    pub element: D,
+    pub width_fn: Box<dyn Fn(f32) -> f32>,
+}
+
+impl<D> TaperedStroked<D> {
+    pub fn new(element: D, width_fn: impl Fn(f32) -> f32 + 'static) -> Self {
+        Self {
+            element,
+            width_fn: Box::new(width_fn),
+        }
+    }
+}
+
+impl<P: Paint> Paint for TaperedStroked<P> {
+    fn paint(&self, comp: &mut Canvas) {
+        let path = comp.capture_path(&self.element);
+        for (mut points, closed) in flatten(path.iter(), 0.05) {
+            if closed {
+                points.push(points[0]);
+            }
+            let mut outline = taper_outline(&points, &*self.width_fn).into_iter();
+            if let Some(start) = outline.next() {
+                comp.move_to(start);
+                outline.for_each(|p| {
+                    comp.line_to(p);
+                });
+                comp.close_path().fill();
+            }
+        }
+    }
+}
+
+/// Paints the inside of a path with evenly spaced parallel strokes. Paint two at different angles
+/// to cross-hatch.
+///
+/// Open subpaths are treated as closed, and where subpaths overlap the inside alternates, as
+/// with the even-odd fill rule.
+pub struct Hatched<D> {
+    pub element: D,
+    /// The direction of the strokes, in degrees counter-clockwise from the positive x axis.
+    pub angle_deg: f32,
+    /// The distance between strokes.
+    pub spacing: f32,
+    pub width: f32,
+}
+
+impl<P: Paint> Paint for Hatched<P> {
+    fn paint(&self, comp: &mut Canvas) {
+        let path = comp.capture_path(&self.element);
+        comp.set_stroke_width(self.width);
+        for (from, to) in hatches(path.iter(), self.angle_deg, self.spacing) {
+            comp.move_to(from).line_to(to).stroke();
+        }
+    }
+}
+
+/// Paints the inside of a path with randomly scattered dots in the current color.
+///
+/// Dots are placed by the seed, so a stipple drawn with the same seed is the same every time.
+/// Where subpaths overlap the inside alternates, as with the even-odd fill rule.
+pub struct Stippled<D> {
+    pub element: D,
+    /// The expected number of dots per unit of area.
+    pub density: f32,
+    pub dot_radius: f32,
+    pub seed: u64,
+}
+
+impl<D> Stippled<D> {
+    pub fn new(element: D, density: f32, dot_radius: f32) -> Self {
+        Self {
+            element,
+            density,
+            dot_radius,
+            seed: 0,
+        }
+    }
+
+    /// Seeds the placement of dots from the rng, such as the rng of the `Context`.
+    pub fn with_rng(mut self, rng: &mut StdRng) -> Self {
+        self.seed = rng.gen();
+        self
+    }
+}
+
+impl<P: Paint> Paint for Stippled<P> {
+    fn paint(&self, comp: &mut Canvas) {
+        let path = comp.capture_path(&self.element);
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        for center in stipples(path.iter(), self.density, &mut rng) {
+            comp.paint(Filled(Ellipse::circle(center, self.dot_radius)));
+        }
+    }
+}
+
+/// Fills a path with samples of a texture, such as a `RenderTexture` painted on a previous frame
+/// or an image loaded with `Gpu::load_image()`.
+///
+/// Painting it fills the path, so it is not wrapped in `Filled`. Without a UV transform, paths
+/// whose bounding box has no area are not painted.
+pub struct TexturePaint<D> {
+    pub element: D,
+    pub texture: Rc<Texture2d>,
+    /// Maps coordinate space to texture coordinates. By default the bounding box of the element
+    /// is mapped to [0, 1]².
+    pub uv_transform: Option<Transform>,
+    /// The region of the texture, in texture coordinates from its bottom left, that [0, 1]²
+    /// covers, such as one sprite of a sheet. By default it is the whole texture.
+    pub uv_rect: Option<Rect>,
+}
+
+impl<D> TexturePaint<D> {
+    pub fn new(element: D, texture: Rc<Texture2d>) -> Self {
+        Self {
+            element,
+            texture,
+            uv_transform: None,
+            uv_rect: None,
+        }
+    }
+
+    pub fn with_uv_transform(mut self, uv_transform: Transform) -> Self {
+        self.uv_transform = Some(uv_transform);
+        self
+    }
+
+    pub fn with_uv_rect(mut self, uv_rect: Rect) -> Self {
+        self.uv_rect = Some(uv_rect);
+        self
+    }
+}
+
+impl<P: Paint> Paint for TexturePaint<P> {
+    fn paint(&self, comp: &mut Canvas) {
+        let path = comp.capture_path(&self.element);
+        let uv_transform = self.uv_transform.or_else(|| {
+            let bounds = bounding_box(path.iter())
+                .filter(|bounds| bounds.width > 0. && bounds.height > 0.)?;
+            Some(
+                Transform::create_translation(-bounds.bottom_left.x, -bounds.bottom_left.y)
+                    .post_scale(1. / bounds.width, 1. / bounds.height),
+            )
+        });
+
+        let uv_transform = uv_transform.map(|uv| match self.uv_rect {
+            Some(rect) => uv
+                .post_scale(rect.width, rect.height)
+                .post_translate(rect.bottom_left.to_vector()),
+            None => uv,
+        });
+
+        let output_to_uv =
+            uv_transform.and_then(|uv| Some(comp.projection().inverse()?.post_transform(&uv)));
+        let uv = match output_to_uv {
+            Some(uv) => uv,
+            None => return,
+        };
+
+        // Filling here consumes the fill shader, so it does not shade whatever is filled next.
+        let shader = comp.gpu().texture_paint_shader(self.texture.clone(), uv);
+        comp.set_fill_shader(shader);
+        path.iter().paint(comp);
+        comp.fill();
+    }
+}
+
+/// Splits a path into the polylines of its dashes. Each subpath starts at the beginning of the
+/// pattern, shifted by the offset.
+fn dashes(path: impl Iterator<Item = PathEvent>, pattern: &[f32], offset: f32) -> Vec<Vec<P2>> {
+    let mut pattern = pattern.to_vec();
+    if pattern.len() % 2 == 1 {
+        pattern.extend_from_slice(&pattern.clone());
+    }
+    let period: f32 = pattern.iter().map(|l| l.max(0.)).sum();
+
+    let subpaths: Vec<Vec<P2>> = flatten(path, 0.05)
+        .into_iter()
+        .map(|(mut points, closed)| {
+            if closed {
+                points.push(points[0]);
+            }
+            points
+        })
+        .collect();
+
+    if period <= 0. {
+        return subpaths;
+    }
+
+    let mut dashes = vec![];
+    for subpath in subpaths {
+        // Find where in the pattern the subpath begins.
+        let mut index = 0;
+        let mut left = offset.rem_euclid(period);
+        while left >= pattern[index].max(0.) {
+            left -= pattern[index].max(0.);
+            index = (index + 1) % pattern.len();
+        }
+        left = pattern[index].max(0.) - left;
+
+        let mut dash = vec![];
+        if index % 2 == 0 {
+            dash.push(subpath[0]);
+        }
+
+        for (&from, &to) in subpath.iter().zip(subpath.iter().skip(1)) {
+            let length = (to - from).length();
+            let mut travelled = 0.;
+            while length - travelled > left {
+                travelled += left;
+                dash.push(from.lerp(to, travelled / length));
+                if index % 2 == 0 {
+                    dashes.push(std::mem::take(&mut dash));
+                }
+                index = (index + 1) % pattern.len();
+                left = pattern[index].max(0.);
+            }
+
+            left -= length - travelled;
+            if index % 2 == 0 {
+                dash.push(to);
+            }
+        }
+
+        if index % 2 == 0 && dash.len() > 1 {
+            dashes.push(dash);
+        }
+    }
+
+    dashes
+}
+
+/// Returns the outline of a polyline offset on each side by half the width at each point, going
+/// out along its left side and back along its right.
+fn taper_outline(points: &[P2], width_fn: &dyn Fn(f32) -> f32) -> Vec<P2> {
+    // Repeated points have no direction, so they are skipped.
+    let mut points = points.to_vec();
+    points.dedup();
+    if points.len() < 2 {
+        return vec![];
+    }
+
+    let lengths: Vec<f32> = std::iter::once(0.)
+        .chain(points.windows(2).scan(0., |travelled, segment| {
+            *travelled += (segment[1] - segment[0]).length();
+            Some(*travelled)
+        }))
+        .collect();
+    let total = lengths[lengths.len() - 1];
+
+    let direction = |i: usize| (points[i + 1] - points[i]).normalize();
+    let (left, right): (Vec<P2>, Vec<P2>) = points
+        .iter()
+        .enumerate()
+        .map(|(i, &p)| {
+            // The tangent at a corner splits the directions of the segments on either side.
+            let (tangent, miter) = match i {
+                0 => (direction(0), 1.),
+                i if i == points.len() - 1 => (direction(i - 1), 1.),
+                i => match (direction(i - 1) + direction(i)).try_normalize() {
+                    // Offsetting a corner along the bisector keeps the sides at half the width
+                    // from each segment when lengthened as a miter, up to the miter limit.
+                    Some(tangent) => (
+                        tangent,
+                        (1. / tangent.dot(direction(i))).min(DEFAULT_MITER_LIMIT),
+                    ),
+                    None => (direction(i), 1.),
+                },
+            };
+            let normal = V2::new(-tangent.y, tangent.x);
+            let half_width = width_fn(lengths[i] / total).max(0.) / 2. * miter;
+            (p + normal * half_width, p - normal * half_width)
+        })
+        .unzip();
+
+    left.into_iter().chain(right.into_iter().rev()).collect()
+}
+
+/// Returns the segments of evenly spaced lines at the angle which lie inside the path, by the
+/// even-odd rule. Lines lie at multiples of the spacing from the origin, so the hatching of
+/// neighbouring shapes lines up.
+fn hatches(path: impl Iterator<Item = PathEvent>, angle_deg: f32, spacing: f32) -> Vec<(P2, P2)> {
+    if spacing <= 0. || !spacing.is_finite() {
+        return vec![];
+    }
+
+    // Rotate the path so the lines run along the x axis and scan it row by row.
+    let (sin, cos) = angle_deg.to_radians().sin_cos();
+    let unrotate = |p: P2| P2::new(p.x * cos + p.y * sin, p.y * cos - p.x * sin);
+    let rotate = |x: f32, y: f32| P2::new(x * cos - y * sin, x * sin + y * cos);
+    let polygons: Vec<Vec<P2>> = flatten(path, 0.05)
+        .into_iter()
+        .map(|(points, _)| points.into_iter().map(unrotate).collect())
+        .collect();
+
+    let (min_y, max_y) = polygons
+        .iter()
+        .flatten()
+        .fold((f32::MAX, f32::MIN), |(min, max), p| {
+            (min.min(p.y), max.max(p.y))
+        });
+    if min_y > max_y {
+        return vec![];
+    }
+
+    let mut segments = vec![];
+    let mut row = (min_y / spacing).ceil();
+    while row * spacing <= max_y {
+        let y = row * spacing;
+        let mut crossings = vec![];
+        for polygon in &polygons {
+            for (i, &a) in polygon.iter().enumerate() {
+                let b = polygon[(i + 1) % polygon.len()];
+                // Half-open so a vertex on the line is crossed once.
+                if (a.y <= y) != (b.y <= y) {
+                    crossings.push(a.x + (y - a.y) / (b.y - a.y) * (b.x - a.x));
+                }
+            }
+        }
+        crossings.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        for pair in crossings.chunks_exact(2) {
+            if pair[1] > pair[0] {
+                segments.push((rotate(pair[0], y), rotate(pair[1], y)));
+            }
+        }
+        row += 1.;
+    }
+
+    segments
+}
+
+/// Samples points inside the path by the even-odd rule, at the expected density per unit of area,
+/// by rejecting samples of its bounding box which fall outside it.
+fn stipples(
+    path: impl Iterator<Item = PathEvent> + Clone,
+    density: f32,
+    rng: &mut StdRng,
+) -> Vec<P2> {
+    let bounds = match bounding_box(path.clone()) {
+        Some(bounds) if density > 0. => bounds,
+        _ => return vec![],
+    };
+    let polygons: Vec<Vec<P2>> = flatten(path, 0.05)
+        .into_iter()
+        .map(|(points, _)| points)
+        .collect();
+
+    let samples = (bounds.width * bounds.height * density).round() as usize;
+    (0..samples)
+        .map(|_| {
+            bounds.bottom_left
+                + V2::new(
+                    rng.gen::<f32>() * bounds.width,
+                    rng.gen::<f32>() * bounds.height,
+                )
+        })
+        .filter(|&p| {
+            let crossings = polygons
+                .iter()
+                .flat_map(|polygon| {
+                    polygon
+                        .iter()
+                        .zip(polygon.iter().cycle().skip(1))
+                        .filter(move |(a, b)| {
+                            (a.y <= p.y) != (b.y <= p.y)
+                                && p.x < a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x)
+                        })
+                })
+                .count();
+            crossings % 2 == 1
+        })
+        .collect()
+}
+
 impl<P> Paint for P
 where
     P: Iterator<Item = PathEvent> + Clone,
 {
     fn paint(&self, canvas: &mut Canvas) {
+        let mut begun = false;
         self.clone().for_each(|p| match p {
-            PathEvent::Line { to, .. } => canvas.line_to(to),
-            PathEvent::Quadratic { ctrl, to, .. } => canvas.quadratic_to(ctrl, to),
+            PathEvent::Line { to, .. } => {
+                canvas.line_to(to);
+            }
+            PathEvent::Quadratic { ctrl, to, .. } => {
+                canvas.quadratic_to(ctrl, to);
+            }
             PathEvent::Cubic {
                 ctrl1, ctrl2, to, ..
-            } => canvas.cubic_to(ctrl1, ctrl2, to),
-            PathEvent::Begin { at } => canvas.move_to(at),
-            PathEvent::End { close, .. } if close => canvas.close_path(),
+            } => {
+                canvas.cubic_to(ctrl1, ctrl2, to);
+            }
+            // Later subpaths are added to the path the first began.
+            PathEvent::Begin { at } if begun => {
+                canvas.begin_subpath(at);
+            }
+            PathEvent::Begin { at } => {
+                canvas.move_to(at);
+                begun = true;
+            }
+            PathEvent::End { close, .. } if close => {
+                canvas.close_path();
+            }
             _ => {}
         });
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::FlatIterPath;
+
+    #[test]
+    fn dashes_follow_pattern_and_offset() {
+        let line = || FlatIterPath::new(vec![P2::new(0., 0.), P2::new(10., 0.)].into_iter(), false);
+
+        let dashed = dashes(line(), &[3., 1.], 0.);
+        assert_eq!(
+            dashed,
+            vec![
+                vec![P2::new(0., 0.), P2::new(3., 0.)],
+                vec![P2::new(4., 0.), P2::new(7., 0.)],
+                vec![P2::new(8., 0.), P2::new(10., 0.)],
+            ]
+        );
+
+        let offset = dashes(line(), &[3., 1.], 2.);
+        assert_eq!(offset[0], vec![P2::new(0., 0.), P2::new(1., 0.)]);
+        assert_eq!(offset[1], vec![P2::new(2., 0.), P2::new(5., 0.)]);
+
+        // Odd patterns repeat, alternating which lengths are dashes and gaps.
+        let odd = dashes(line(), &[2.], 0.);
+        assert_eq!(odd.len(), 3);
+    }
+
+    #[test]
+    fn stipples_fall_inside_at_density() {
+        // A right triangle of area 50.
+        let triangle = vec![P2::new(0., 0.), P2::new(10., 0.), P2::new(0., 10.)];
+        let path = FlatIterPath::new(triangle.into_iter(), true);
+
+        let dots = stipples(path, 20., &mut StdRng::seed_from_u64(0));
+        assert!((dots.len() as f32 - 1000.).abs() < 100.);
+        assert!(dots
+            .iter()
+            .all(|p| p.x >= 0. && p.y >= 0. && p.x + p.y <= 10.));
+    }
+
+    #[test]
+    fn tapers_along_length() {
+        let line = [P2::new(0., 0.), P2::new(2., 0.), P2::new(4., 0.)];
+        let outline = taper_outline(&line, &|t| 2. * (1. - t));
+        assert_eq!(
+            outline,
+            vec![
+                P2::new(0., 1.),
+                P2::new(2., 0.5),
+                P2::new(4., 0.),
+                P2::new(4., 0.),
+                P2::new(2., -0.5),
+                P2::new(0., -1.),
+            ]
+        );
+    }
+
+    #[test]
+    fn tapers_keep_width_around_corners() {
+        let corner = [P2::new(0., 0.), P2::new(1., 0.), P2::new(1., 1.)];
+        let outline = taper_outline(&corner, &|_| 0.2);
+        let (inner, outer) = (outline[1], outline[4]);
+
+        assert!((inner - P2::new(0.9, 0.1)).length() < 1e-6);
+        assert!((outer - P2::new(1.1, -0.1)).length() < 1e-6);
+    }
+
+    #[test]
+    fn hatches_skip_holes() {
+        // A square with a square hole, as two subpaths.
+        let outer = vec![
+            P2::new(0., 0.5),
+            P2::new(4., 0.5),
+            P2::new(4., 4.5),
+            P2::new(0., 4.5),
+        ];
+        let inner = vec![
+            P2::new(1., 1.),
+            P2::new(3., 1.),
+            P2::new(3., 2.5),
+            P2::new(1., 2.5),
+        ];
+        let path = FlatIterPath::new(outer.into_iter(), true)
+            .chain(FlatIterPath::new(inner.into_iter(), true));
+
+        let segments = hatches(path, 0., 1.5);
+        assert_eq!(
+            segments,
+            vec![
+                (P2::new(0., 1.5), P2::new(1., 1.5)),
+                (P2::new(3., 1.5), P2::new(4., 1.5)),
+                (P2::new(0., 3.), P2::new(4., 3.)),
+            ]
+        );
+    }
+}
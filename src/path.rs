@@ -1,7 +1,14 @@
 //! Path types and tools.
 
-use crate::P2;
-use lyon_path::PathEvent;
+use crate::{Angle, Rect, Result, P2, V2};
+use failure::bail;
+use float_ord::FloatOrd;
+use itertools::Itertools;
+use lyon_path::{
+    geom::{CubicBezierSegment, LineSegment, QuadraticBezierSegment},
+    iterator::PathIterator,
+    PathEvent,
+};
 
 /// An adapter for iterators over points that implements `Path`.
 #[derive(Debug, Copy, Clone)]
@@ -58,3 +65,964 @@ where
         result
     }
 }
+
+/// Returns an open path of straight lines through the points.
+pub fn polyline(points: &[P2]) -> impl Iterator<Item = PathEvent> + Clone + '_ {
+    FlatIterPath::new(points.iter().copied(), /*closed=*/ false)
+}
+
+/// Returns an open path of curves through the points, fit with a Catmull-Rom spline.
+///
+/// `tension` scales the tangents of the curves at each point: one gives a Catmull-Rom spline, and
+/// zero gives straight lines as with `polyline()`.
+pub fn smooth_polyline(points: &[P2], tension: f32) -> impl Iterator<Item = PathEvent> + Clone {
+    let mut events = vec![];
+    if let (Some(first), Some(last)) = (points.first(), points.last()) {
+        events.push(PathEvent::Begin { at: *first });
+        for i in 0..points.len() - 1 {
+            let (from, to) = (points[i], points[i + 1]);
+            // The ends have no outer neighbors, so they stand in for them.
+            let before = points[i.saturating_sub(1)];
+            let after = points[(i + 2).min(points.len() - 1)];
+            events.push(PathEvent::Cubic {
+                from,
+                ctrl1: from + (to - before) * tension / 6.,
+                ctrl2: to - (after - from) * tension / 6.,
+                to,
+            });
+        }
+        events.push(PathEvent::End {
+            last: *last,
+            first: *first,
+            close: false,
+        });
+    }
+    events.into_iter()
+}
+
+/// Returns the smallest rectangle containing the path, including the extremes of its curves, or
+/// `None` if the path is empty.
+pub fn bounding_box(events: impl Iterator<Item = PathEvent>) -> Option<Rect> {
+    let ranges = events.map(|event| match event {
+        PathEvent::Begin { at } => ((at.x, at.x), (at.y, at.y)),
+        PathEvent::Line { from, to }
+        | PathEvent::End {
+            last: from,
+            first: to,
+            ..
+        } => (
+            (from.x.min(to.x), from.x.max(to.x)),
+            (from.y.min(to.y), from.y.max(to.y)),
+        ),
+        PathEvent::Quadratic { from, ctrl, to } => {
+            let curve = QuadraticBezierSegment { from, ctrl, to };
+            (curve.bounding_range_x(), curve.bounding_range_y())
+        }
+        PathEvent::Cubic {
+            from,
+            ctrl1,
+            ctrl2,
+            to,
+        } => {
+            let curve = CubicBezierSegment {
+                from,
+                ctrl1,
+                ctrl2,
+                to,
+            };
+            (curve.bounding_range_x(), curve.bounding_range_y())
+        }
+    });
+
+    let ((min_x, max_x), (min_y, max_y)) = ranges.fold1(|(ax, ay), (bx, by)| {
+        (
+            (ax.0.min(bx.0), ax.1.max(bx.1)),
+            (ay.0.min(by.0), ay.1.max(by.1)),
+        )
+    })?;
+    Some(Rect {
+        bottom_left: P2::new(min_x, min_y),
+        width: max_x - min_x,
+        height: max_y - min_y,
+    })
+}
+
+/// Returns whether any two non-adjacent segments of the path cross or touch.
+pub fn has_self_intersections(events: impl Iterator<Item = PathEvent>) -> bool {
+    let mut found = false;
+    visit_self_intersections(events, |_| {
+        found = true;
+        false
+    });
+    found
+}
+
+/// Returns the points where non-adjacent segments of the path cross or touch. Curves are
+/// flattened first, so points on them are approximate.
+pub fn self_intersections(events: impl Iterator<Item = PathEvent>) -> Vec<P2> {
+    let mut points = vec![];
+    visit_self_intersections(events, |p| {
+        points.push(p);
+        true
+    });
+    points
+}
+
+/// Calls `f` with each self intersection until it returns false.
+fn visit_self_intersections(
+    events: impl Iterator<Item = PathEvent>,
+    mut f: impl FnMut(P2) -> bool,
+) {
+    struct Segment {
+        line: LineSegment<f32>,
+        subpath: usize,
+        index: usize,
+    }
+
+    let subpaths = flatten(events, 0.05);
+    let mut segments = vec![];
+    for (subpath, (points, closed)) in subpaths.iter().enumerate() {
+        let closing = points.first().filter(|_| *closed);
+        let ends = points.iter().chain(closing);
+        for (index, (from, to)) in ends.clone().zip(ends.skip(1)).enumerate() {
+            segments.push(Segment {
+                line: LineSegment {
+                    from: *from,
+                    to: *to,
+                },
+                subpath,
+                index,
+            });
+        }
+    }
+
+    let adjacent = |a: &Segment, b: &Segment| {
+        if a.subpath != b.subpath {
+            return false;
+        }
+        let (points, closed) = &subpaths[a.subpath];
+        let count = if *closed {
+            points.len()
+        } else {
+            points.len() - 1
+        };
+        let (low, high) = (a.index.min(b.index), a.index.max(b.index));
+        high - low == 1 || (*closed && low == 0 && high == count - 1)
+    };
+    let mut check = |a: &Segment, b: &Segment| match a.line.intersection(&b.line) {
+        Some(p) if !adjacent(a, b) => f(p),
+        _ => true,
+    };
+
+    if segments.len() < 64 {
+        for (i, a) in segments.iter().enumerate() {
+            for b in &segments[i + 1..] {
+                if !check(a, b) {
+                    return;
+                }
+            }
+        }
+        return;
+    }
+
+    // Sweep across x, only checking segments whose x ranges overlap.
+    let min_x = |s: &Segment| s.line.from.x.min(s.line.to.x);
+    let max_x = |s: &Segment| s.line.from.x.max(s.line.to.x);
+    segments.sort_by_key(|s| FloatOrd(min_x(s)));
+    let mut active: Vec<&Segment> = vec![];
+    for segment in &segments {
+        active.retain(|other| max_x(other) >= min_x(segment));
+        for other in &active {
+            if !check(other, segment) {
+                return;
+            }
+        }
+        active.push(segment);
+    }
+}
+
+/// Returns the point at `t` along a path, where each segment of the path spans an equal range of
+/// `t`. This is cheaper than `ArcLengthParam` but does not space points evenly.
+///
+/// For closed paths `t` wraps around the path. For open paths, `t` outside [0, 1] has no point.
+pub fn point_at(events: impl Iterator<Item = PathEvent> + Clone, t: f32) -> Option<P2> {
+    let is_segment = |event: &PathEvent| match event {
+        PathEvent::End { last, first, close } => *close && last != first,
+        PathEvent::Begin { .. } => false,
+        _ => true,
+    };
+    let count = events.clone().filter(is_segment).count();
+    let closed = events
+        .clone()
+        .filter_map(|event| match event {
+            PathEvent::End { close, .. } => Some(close),
+            _ => None,
+        })
+        .last()
+        .unwrap_or(false);
+
+    let t = match closed {
+        true => t.rem_euclid(1.),
+        false if (0. ..=1.).contains(&t) => t,
+        false => return None,
+    };
+    if count == 0 {
+        return None;
+    }
+
+    let position = t * count as f32;
+    let index = (position.floor() as usize).min(count - 1);
+    let local = position - index as f32;
+    events
+        .filter(is_segment)
+        .nth(index)
+        .map(|segment| match segment {
+            PathEvent::Line { from, to } => from.lerp(to, local),
+            PathEvent::Quadratic { from, ctrl, to } => {
+                QuadraticBezierSegment { from, ctrl, to }.sample(local)
+            }
+            PathEvent::Cubic {
+                from,
+                ctrl1,
+                ctrl2,
+                to,
+            } => CubicBezierSegment {
+                from,
+                ctrl1,
+                ctrl2,
+                to,
+            }
+            .sample(local),
+            PathEvent::End { last, first, .. } => last.lerp(first, local),
+            PathEvent::Begin { at } => at,
+        })
+}
+
+/// A lookup table from distance along a path to points on it, for spacing things evenly along
+/// a path regardless of how its curves are parameterized.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArcLengthParam {
+    points: Vec<P2>,
+    /// The length of the path up to each point.
+    lengths: Vec<f32>,
+}
+
+impl ArcLengthParam {
+    /// Measures the path, dividing each curve in it into `resolution` line segments.
+    pub fn new(events: impl Iterator<Item = PathEvent>, resolution: usize) -> Self {
+        let resolution = resolution.max(1);
+        let mut points = vec![];
+        let samples = |sample: &dyn Fn(f32) -> P2| {
+            (1..=resolution)
+                .map(|i| sample(i as f32 / resolution as f32))
+                .collect::<Vec<P2>>()
+        };
+        for event in events {
+            match event {
+                PathEvent::Begin { at } => points.push(at),
+                PathEvent::Line { to, .. } => points.push(to),
+                PathEvent::Quadratic { from, ctrl, to } => {
+                    let curve = QuadraticBezierSegment { from, ctrl, to };
+                    points.extend(samples(&|t| curve.sample(t)));
+                }
+                PathEvent::Cubic {
+                    from,
+                    ctrl1,
+                    ctrl2,
+                    to,
+                } => {
+                    let curve = CubicBezierSegment {
+                        from,
+                        ctrl1,
+                        ctrl2,
+                        to,
+                    };
+                    points.extend(samples(&|t| curve.sample(t)));
+                }
+                PathEvent::End { first, close, .. } if close => points.push(first),
+                _ => {}
+            }
+        }
+
+        // Jumps between subpaths are not counted.
+        let mut lengths = Vec::with_capacity(points.len());
+        let mut length = 0.;
+        let mut previous: Option<P2> = None;
+        for p in &points {
+            length += previous
+                .map(|previous| (*p - previous).length())
+                .unwrap_or(0.);
+            lengths.push(length);
+            previous = Some(*p);
+        }
+
+        Self { points, lengths }
+    }
+
+    /// Returns the length of the path.
+    pub fn total_length(&self) -> f32 {
+        self.lengths.last().copied().unwrap_or(0.)
+    }
+
+    /// Returns the point at the fraction `t` in [0, 1] of the path's length. Values of `t`
+    /// outside the range are clamped. An empty path has no points, so samples of it are the
+    /// origin.
+    pub fn sample(&self, t: f32) -> P2 {
+        let distance = t.clamp(0., 1.) * self.total_length();
+        let i = match self
+            .lengths
+            .binary_search_by_key(&FloatOrd(distance), |l| FloatOrd(*l))
+        {
+            Ok(i) => return self.points[i],
+            Err(i) => i,
+        };
+
+        if i == 0 {
+            return self.points.first().copied().unwrap_or_else(P2::origin);
+        }
+        if i == self.points.len() {
+            return self.points[i - 1];
+        }
+
+        let span = self.lengths[i] - self.lengths[i - 1];
+        self.points[i - 1].lerp(self.points[i], (distance - self.lengths[i - 1]) / span)
+    }
+}
+
+/// Flattens a path into the polylines of its subpaths, each paired with whether it is closed.
+///
+/// The polyline of a closed subpath does not repeat its first point.
+pub(crate) fn flatten(
+    path: impl Iterator<Item = PathEvent>,
+    tolerance: f32,
+) -> Vec<(Vec<P2>, bool)> {
+    let mut subpaths: Vec<(Vec<P2>, bool)> = vec![];
+    for event in path.flattened(tolerance) {
+        match event {
+            PathEvent::Begin { at } => subpaths.push((vec![at], false)),
+            PathEvent::Line { to, .. } => {
+                if let Some((points, _)) = subpaths.last_mut() {
+                    points.push(to);
+                }
+            }
+            PathEvent::End { close, .. } => {
+                if let Some((_, closed)) = subpaths.last_mut() {
+                    *closed = close;
+                }
+            }
+            _ => {}
+        }
+    }
+    subpaths
+}
+
+/// Returns an open circular arc from `start_angle` to `end_angle`, measured counter-clockwise
+/// from the positive x axis.
+pub fn arc(
+    center: P2,
+    radius: f32,
+    start_angle: Angle,
+    end_angle: Angle,
+) -> impl Iterator<Item = PathEvent> + Clone {
+    let first = circumpoint(center, radius, start_angle);
+    let mut last = first;
+    let mut events = vec![PathEvent::Begin { at: first }];
+    for (ctrl1, ctrl2, to) in arc_curves(center, radius, start_angle, end_angle) {
+        events.push(PathEvent::Cubic {
+            from: last,
+            ctrl1,
+            ctrl2,
+            to,
+        });
+        last = to;
+    }
+    events.push(PathEvent::End {
+        last,
+        first,
+        close: false,
+    });
+    events.into_iter()
+}
+
+/// Returns an Archimedean spiral around `center`, starting from the positive x axis, whose radius
+/// changes linearly from `start_radius` to `end_radius` over `turns` counter-clockwise turns.
+///
+/// The spiral is made of `resolution` line segments. A spiral of no turns is a single point.
+pub fn spiral(
+    center: P2,
+    start_radius: f32,
+    end_radius: f32,
+    turns: f32,
+    resolution: usize,
+) -> impl Iterator<Item = PathEvent> + Clone {
+    let segments = if turns == 0. { 0 } else { resolution };
+    let points = (0..=segments).map(move |i| {
+        let t = if segments == 0 {
+            0.
+        } else {
+            i as f32 / segments as f32
+        };
+        let radius = start_radius + (end_radius - start_radius) * t;
+        circumpoint(
+            center,
+            radius,
+            Angle::radians(t * turns * std::f32::consts::PI * 2.),
+        )
+    });
+    FlatIterPath::new(points, /*closed=*/ false)
+}
+
+/// Returns a Lissajous figure `(sin(a_freq * t + delta_rad), sin(b_freq * t))` for `t` over
+/// [0, 2π], scaled from [-1, 1] to the unit square [0, 1] x [0, 1].
+///
+/// The figure is sampled at `resolution` points. When both frequencies are integers it repeats
+/// after 2π, so the path is closed; otherwise it is left open at `t = 2π`.
+pub fn lissajous(
+    a_freq: f32,
+    b_freq: f32,
+    delta_rad: f32,
+    resolution: u32,
+) -> impl Iterator<Item = PathEvent> + Clone {
+    let closed = a_freq.fract() == 0. && b_freq.fract() == 0.;
+    let resolution = resolution.max(2);
+    // A closed figure leaves out the sample at 2π, which would repeat the first.
+    let steps = if closed { resolution } else { resolution - 1 };
+    let points = (0..resolution).map(move |i| {
+        let t = i as f32 / steps as f32 * std::f32::consts::PI * 2.;
+        P2::new(
+            ((a_freq * t + delta_rad).sin() + 1.) / 2.,
+            ((b_freq * t).sin() + 1.) / 2.,
+        )
+    });
+    FlatIterPath::new(points, closed)
+}
+
+/// Returns a hypotrochoid around the origin: the path of a pen `d` from the center of a circle of
+/// radius `small_r` rolling around the inside of a fixed circle of radius `big_r`, as drawn by a
+/// spirograph.
+///
+/// The rolling circle rolls its whole circumference `turns` times, sampled in `resolution`
+/// segments. The path is closed if it ends where it began, as it does after
+/// `spirograph_turns()`.
+pub fn hypotrochoid(
+    big_r: f32,
+    small_r: f32,
+    d: f32,
+    turns: f32,
+    resolution: u32,
+) -> impl Iterator<Item = PathEvent> + Clone {
+    trochoid(big_r, -small_r, d, turns, resolution)
+}
+
+/// Returns an epitrochoid around the origin, which is a hypotrochoid whose circle rolls around
+/// the outside of the fixed circle instead. See `hypotrochoid()`.
+pub fn epitrochoid(
+    big_r: f32,
+    small_r: f32,
+    d: f32,
+    turns: f32,
+    resolution: u32,
+) -> impl Iterator<Item = PathEvent> + Clone {
+    trochoid(big_r, small_r, d, turns, resolution)
+}
+
+/// Returns the turns after which a hypotrochoid or epitrochoid with the radii, rounded to
+/// integers, first returns to where it began: `big_r / gcd(big_r, small_r)`.
+pub fn spirograph_turns(big_r: f32, small_r: f32) -> f32 {
+    let (big_r, small_r) = (big_r.round().abs() as u64, small_r.round().abs() as u64);
+    let (mut a, mut b) = (big_r, small_r);
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    // Both radii round to zero only for a degenerate figure, which is traced once.
+    big_r.checked_div(a).map_or(1., |turns| turns as f32)
+}
+
+/// A trochoid of a circle of radius `small_r` rolling around the outside of the fixed circle, or
+/// of `-small_r` rolling around the inside.
+fn trochoid(
+    big_r: f32,
+    small_r: f32,
+    d: f32,
+    turns: f32,
+    resolution: u32,
+) -> impl Iterator<Item = PathEvent> + Clone {
+    let sign = small_r.signum();
+    let small_r = small_r.abs();
+    let centers = big_r + sign * small_r;
+    let ratio = centers / small_r;
+    // Rolling a circumference of the small circle along the fixed one turns its center by
+    // `small_r / big_r` of a revolution.
+    let end = turns * std::f32::consts::PI * 2. * small_r / big_r;
+    let segments = resolution.max(1);
+    let mut points: Vec<P2> = (0..=segments)
+        .map(|i| {
+            let theta = end * i as f32 / segments as f32;
+            P2::new(
+                centers * theta.cos() - sign * d * (ratio * theta).cos(),
+                centers * theta.sin() - d * (ratio * theta).sin(),
+            )
+        })
+        .collect();
+
+    let extent = big_r.abs() + small_r + d.abs();
+    let closed =
+        points.len() > 2 && (points[0] - points[points.len() - 1]).length() <= extent * 1e-4;
+    if closed {
+        points.pop();
+    }
+    FlatIterPath::new(points.into_iter(), closed)
+}
+
+/// Returns the Hilbert curve of the given order over the bounds: an open path through the center
+/// of every cell of a `2^order` by `2^order` grid over the bounds, stepping only between
+/// neighboring cells, in `4^order - 1` lines.
+///
+/// The curve starts in the bottom left cell and ends in the bottom right one. `order` is clamped
+/// to [1, 8], so the curve has at most 65536 points.
+pub fn hilbert(order: u32, bounds: Rect) -> impl Iterator<Item = PathEvent> + Clone {
+    let side = 1u32 << order.clamp(1, 8);
+    let cell = V2::new(bounds.width / side as f32, bounds.height / side as f32);
+    let points = (0..side * side).map(move |d| {
+        let (col, row) = hilbert_cell(side, d);
+        bounds.bottom_left + V2::new((col as f32 + 0.5) * cell.x, (row as f32 + 0.5) * cell.y)
+    });
+    FlatIterPath::new(points, /*closed=*/ false)
+}
+
+/// Returns the column and row of the cell at distance `d` along the Hilbert curve over a grid
+/// `side` cells across, where `side` is a power of two.
+fn hilbert_cell(side: u32, d: u32) -> (u32, u32) {
+    let (mut col, mut row) = (0, 0);
+    let mut t = d;
+    let mut size = 1;
+    // Each pass places the point within a quadrant of a square twice the size, rotating and
+    // reflecting the smaller curve so that the quadrants join end to end.
+    while size < side {
+        let right = 1 & (t / 2);
+        let up = 1 & (t ^ right);
+        if up == 0 {
+            if right == 1 {
+                col = size - 1 - col;
+                row = size - 1 - row;
+            }
+            std::mem::swap(&mut col, &mut row);
+        }
+        col += size * right;
+        row += size * up;
+        t /= 4;
+        size *= 2;
+    }
+    (col, row)
+}
+
+/// Simplifies a polyline with the Ramer-Douglas-Peucker algorithm, removing points until none left
+/// out would be further than `epsilon` from the simplified polyline.
+///
+/// The first and last points are always kept. An epsilon of zero or less keeps every point.
+pub fn simplify(points: &[P2], epsilon: f32) -> Vec<P2> {
+    if epsilon <= 0. || points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+
+    let mut spans = vec![(0, points.len() - 1)];
+    while let Some((start, end)) = spans.pop() {
+        let segment = LineSegment {
+            from: points[start],
+            to: points[end],
+        };
+        let farthest = (start + 1..end)
+            .map(|i| (i, distance_to_segment(&segment, points[i])))
+            .max_by_key(|(_, distance)| FloatOrd(*distance));
+
+        if let Some((i, distance)) = farthest {
+            if distance > epsilon {
+                keep[i] = true;
+                spans.push((start, i));
+                spans.push((i, end));
+            }
+        }
+    }
+
+    points
+        .iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(p, _)| *p)
+        .collect()
+}
+
+fn distance_to_segment(segment: &LineSegment<f32>, p: P2) -> f32 {
+    let length = segment.to_vector().square_length();
+    if length == 0. {
+        return (p - segment.from).length();
+    }
+
+    let t = ((p - segment.from).dot(segment.to_vector()) / length).clamp(0., 1.);
+    (p - segment.sample(t)).length()
+}
+
+/// Approximates a circular arc with cubic bezier curves of at most a quarter turn each,
+/// returned as `(ctrl1, ctrl2, to)`.
+pub(crate) fn arc_curves(
+    center: P2,
+    radius: f32,
+    start_angle: Angle,
+    end_angle: Angle,
+) -> Vec<(P2, P2, P2)> {
+    let sweep = end_angle.radians - start_angle.radians;
+    let count = (sweep.abs() / std::f32::consts::FRAC_PI_2).ceil().max(1.) as usize;
+    let step = sweep / count as f32;
+    // The standard length of the tangent handles for an arc of this angle.
+    let handle = radius * 4. / 3. * (step / 4.).tan();
+
+    (0..count)
+        .map(|i| {
+            let from = start_angle.radians + step * i as f32;
+            let to = from + step;
+            let tangent = |theta: f32| V2::new(-theta.sin(), theta.cos());
+            (
+                circumpoint(center, radius, Angle::radians(from)) + tangent(from) * handle,
+                circumpoint(center, radius, Angle::radians(to)) - tangent(to) * handle,
+                circumpoint(center, radius, Angle::radians(to)),
+            )
+        })
+        .collect()
+}
+
+/// Encodes path events in a compact binary format, for caching expensive paths to disk.
+///
+/// The format is a little endian `u32` count of events, followed by each event as a `u8` tag
+/// and then its points as little endian `f32` pairs. Decode it with `deserialize_path()`.
+pub fn serialize_path(events: &[PathEvent]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(4 + events.len() * 17);
+    bytes.extend_from_slice(&(events.len() as u32).to_le_bytes());
+    for event in events {
+        let (tag, points): (u8, &[P2]) = match event {
+            PathEvent::Begin { at } => (0, &[*at]),
+            PathEvent::Line { from, to } => (1, &[*from, *to]),
+            PathEvent::Quadratic { from, ctrl, to } => (2, &[*from, *ctrl, *to]),
+            PathEvent::Cubic {
+                from,
+                ctrl1,
+                ctrl2,
+                to,
+            } => (3, &[*from, *ctrl1, *ctrl2, *to]),
+            PathEvent::End { last, first, close } => (4 + *close as u8, &[*last, *first]),
+        };
+        bytes.push(tag);
+        for p in points {
+            bytes.extend_from_slice(&p.x.to_le_bytes());
+            bytes.extend_from_slice(&p.y.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+/// Decodes path events encoded by `serialize_path()`, exactly as they were.
+///
+/// Fails if the bytes are truncated, have trailing data, or have an unknown tag.
+pub fn deserialize_path(bytes: &[u8]) -> Result<Vec<PathEvent>> {
+    let mut reader = PathReader { bytes };
+    let count = u32::from_le_bytes(reader.take()?);
+    // Each event takes at least a tag and a point, so a corrupt count cannot allocate much.
+    let mut events = Vec::with_capacity((count as usize).min(bytes.len() / 9));
+    for _ in 0..count {
+        let tag = reader.take::<1>()?[0];
+        events.push(match tag {
+            0 => PathEvent::Begin {
+                at: reader.point()?,
+            },
+            1 => PathEvent::Line {
+                from: reader.point()?,
+                to: reader.point()?,
+            },
+            2 => PathEvent::Quadratic {
+                from: reader.point()?,
+                ctrl: reader.point()?,
+                to: reader.point()?,
+            },
+            3 => PathEvent::Cubic {
+                from: reader.point()?,
+                ctrl1: reader.point()?,
+                ctrl2: reader.point()?,
+                to: reader.point()?,
+            },
+            4 | 5 => PathEvent::End {
+                last: reader.point()?,
+                first: reader.point()?,
+                close: tag == 5,
+            },
+            _ => bail!("Unknown path event tag {}", tag),
+        });
+    }
+    if !reader.bytes.is_empty() {
+        bail!(
+            "{} bytes of trailing data after path events",
+            reader.bytes.len()
+        );
+    }
+    Ok(events)
+}
+
+/// Reads the fields of a serialized path from the front of its bytes.
+struct PathReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl PathReader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        if self.bytes.len() < N {
+            bail!("Serialized path is truncated");
+        }
+        let (field, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        let mut array = [0; N];
+        array.copy_from_slice(field);
+        Ok(array)
+    }
+
+    fn point(&mut self) -> Result<P2> {
+        let x = f32::from_le_bytes(self.take()?);
+        let y = f32::from_le_bytes(self.take()?);
+        Ok(P2::new(x, y))
+    }
+}
+
+fn circumpoint(center: P2, radius: f32, angle: Angle) -> P2 {
+    center + V2::new(angle.radians.cos(), angle.radians.sin()) * radius
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn arc_length_param_samples_evenly() {
+        let path = FlatIterPath::new(
+            vec![P2::new(0., 0.), P2::new(1., 0.), P2::new(1., 3.)].into_iter(),
+            /*closed=*/ false,
+        );
+        let param = ArcLengthParam::new(path, 8);
+
+        assert_eq!(param.total_length(), 4.);
+        assert_eq!(param.sample(0.), P2::new(0., 0.));
+        assert_eq!(param.sample(0.5), P2::new(1., 1.));
+        assert_eq!(param.sample(2.), P2::new(1., 3.));
+
+        let empty = ArcLengthParam::new(std::iter::empty(), 8);
+        assert_eq!(empty.total_length(), 0.);
+        assert_eq!(empty.sample(0.5), P2::origin());
+    }
+
+    #[test]
+    fn point_at_wraps_only_closed_paths() {
+        let square = |closed| {
+            FlatIterPath::new(
+                vec![
+                    P2::new(0., 0.),
+                    P2::new(1., 0.),
+                    P2::new(1., 1.),
+                    P2::new(0., 1.),
+                ]
+                .into_iter(),
+                closed,
+            )
+        };
+
+        assert_eq!(point_at(square(false), 0.5), Some(P2::new(1., 0.5)));
+        assert_eq!(point_at(square(false), 1.5), None);
+        assert_eq!(point_at(square(true), 0.5), Some(P2::new(1., 1.)));
+        assert_eq!(point_at(square(true), 1.5), Some(P2::new(1., 1.)));
+    }
+
+    #[test]
+    fn bounding_box_includes_curve_extremes() {
+        let hump = vec![
+            PathEvent::Begin {
+                at: P2::new(0., 0.),
+            },
+            PathEvent::Quadratic {
+                from: P2::new(0., 0.),
+                ctrl: P2::new(1., 2.),
+                to: P2::new(2., 0.),
+            },
+        ];
+
+        assert_eq!(
+            bounding_box(hump.into_iter()),
+            Some(Rect {
+                bottom_left: P2::new(0., 0.),
+                width: 2.,
+                height: 1.,
+            })
+        );
+        assert_eq!(bounding_box(std::iter::empty()), None);
+    }
+
+    #[test]
+    fn self_intersections_skip_adjacent_segments() {
+        let square = vec![
+            P2::new(0., 0.),
+            P2::new(1., 0.),
+            P2::new(1., 1.),
+            P2::new(0., 1.),
+        ];
+        assert!(!has_self_intersections(FlatIterPath::new(
+            square.clone().into_iter(),
+            /*closed=*/ true
+        )));
+
+        let bowtie = vec![
+            P2::new(0., 0.),
+            P2::new(1., 1.),
+            P2::new(1., 0.),
+            P2::new(0., 1.),
+        ];
+        assert_eq!(
+            self_intersections(FlatIterPath::new(bowtie.into_iter(), /*closed=*/ true)),
+            vec![P2::new(0.5, 0.5)]
+        );
+
+        // Enough segments to sweep.
+        let zigzag: Vec<P2> = (0..100)
+            .map(|i| P2::new(i as f32, (i % 2) as f32))
+            .chain(vec![
+                P2::new(99., 5.),
+                P2::new(50.5, 5.),
+                P2::new(50.5, -1.),
+            ])
+            .collect();
+        assert_eq!(
+            self_intersections(FlatIterPath::new(
+                zigzag.into_iter(),
+                /*closed=*/ false
+            ))
+            .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn simplify_removes_points_within_epsilon() {
+        let points = [
+            P2::new(0., 0.),
+            P2::new(1., 0.1),
+            P2::new(2., -0.1),
+            P2::new(3., 5.),
+            P2::new(4., 6.),
+            P2::new(5., 7.),
+        ];
+
+        assert_eq!(simplify(&points, 0.), points.to_vec());
+        assert_eq!(
+            simplify(&points, 0.5),
+            vec![
+                P2::new(0., 0.),
+                P2::new(2., -0.1),
+                P2::new(3., 5.),
+                P2::new(5., 7.)
+            ]
+        );
+    }
+
+    #[test]
+    fn serialized_paths_round_trip() {
+        let mut events: Vec<PathEvent> = smooth_polyline(
+            &[P2::new(0., 0.), P2::new(1.5, -2.), P2::new(3., 1. / 3.)],
+            0.7,
+        )
+        .collect();
+        events.extend(polyline(&[
+            P2::new(f32::MIN_POSITIVE, 1e30),
+            P2::new(-0., 4.),
+        ]));
+        events.push(PathEvent::Quadratic {
+            from: P2::new(1., 2.),
+            ctrl: P2::new(3., 4.),
+            to: P2::new(5., 6.),
+        });
+        events.push(PathEvent::End {
+            last: P2::new(5., 6.),
+            first: P2::new(1., 2.),
+            close: true,
+        });
+        let bytes = serialize_path(&events);
+
+        assert_eq!(deserialize_path(&bytes).unwrap(), events);
+        assert!(deserialize_path(&bytes[..bytes.len() - 1]).is_err());
+        assert!(deserialize_path(&[bytes.as_slice(), &[0]].concat()).is_err());
+    }
+
+    #[test]
+    fn lissajous_closes_for_integer_frequencies() {
+        let end = |events: Vec<PathEvent>| match events.last() {
+            Some(PathEvent::End { last, close, .. }) => (events.len(), *last, *close),
+            _ => panic!("Path has no end"),
+        };
+
+        let (len, _, close) = end(lissajous(3., 2., std::f32::consts::FRAC_PI_2, 100).collect());
+        assert_eq!(len, 101);
+        assert!(close);
+
+        let (_, last, close) = end(lissajous(1.5, 1., 0., 100).collect());
+        assert!(!close);
+        assert!((last - P2::new(0.5, 0.5)).length() < 1e-5);
+    }
+
+    #[test]
+    fn spirographs_close_after_their_turns() {
+        assert_eq!(spirograph_turns(5., 3.), 5.);
+        assert_eq!(spirograph_turns(6., 4.), 3.);
+
+        let closes = |events: Vec<PathEvent>| match events.last() {
+            Some(PathEvent::End { close, .. }) => *close,
+            _ => panic!("Path has no end"),
+        };
+        assert!(closes(hypotrochoid(5., 3., 5., 5., 500).collect()));
+        assert!(closes(epitrochoid(6., 4., 1., 3., 500).collect()));
+        assert!(!closes(hypotrochoid(5., 3., 5., 2., 500).collect()));
+
+        // A circle rolling inside one twice its size draws a straight diameter.
+        let diameter: Vec<PathEvent> = hypotrochoid(2., 1., 1., 0.5, 4).collect();
+        assert!(diameter.iter().all(|event| match event {
+            PathEvent::Line { to, .. } => to.y.abs() < 1e-5,
+            _ => true,
+        }));
+    }
+
+    #[test]
+    fn hilbert_steps_between_neighboring_cells() {
+        let bounds = Rect {
+            bottom_left: P2::new(0., 0.),
+            width: 8.,
+            height: 8.,
+        };
+        let lines: Vec<(P2, P2)> = hilbert(3, bounds)
+            .filter_map(|event| match event {
+                PathEvent::Line { from, to } => Some((from, to)),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(lines.len(), 63);
+        assert!(lines.iter().all(|(from, to)| (*to - *from).length() == 1.));
+        let mut visited: Vec<(i32, i32)> = lines
+            .iter()
+            .map(|(_, to)| (to.x as i32, to.y as i32))
+            .chain(std::iter::once((0, 0)))
+            .collect();
+        visited.sort_unstable();
+        visited.dedup();
+        assert_eq!(visited.len(), 64);
+        assert_eq!(lines[0].0, P2::new(0.5, 0.5));
+        assert_eq!(lines[62].1, P2::new(7.5, 0.5));
+        assert_eq!(hilbert(20, bounds).count(), 4usize.pow(8) + 1);
+    }
+}
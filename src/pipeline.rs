@@ -0,0 +1,382 @@
+//! Multi-pass rendering through offscreen textures.
+
+use crate::{canvas::Canvas, gpu::Gpu, paint::Filled, Result, Shader, World};
+use failure::bail;
+use glium::{
+    texture::{texture2d::Texture2d, texture2d_multisample::Texture2dMultisample},
+    uniforms::UniformValue,
+    Surface,
+};
+use std::{collections::HashMap, path::Path, rc::Rc};
+
+/// Where a `RenderPass` draws.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PassTarget {
+    /// A texture of the pipeline, which later passes can read by this name.
+    Texture(String),
+    /// The canvas given to `Pipeline::draw()`.
+    Screen,
+}
+
+/// What a `RenderPass` paints.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum PassContent {
+    Scene,
+    Quad,
+}
+
+/// A stage of a `Pipeline`.
+#[derive(Clone)]
+pub struct RenderPass {
+    shader: Shader,
+    inputs: Vec<(String, String)>,
+    target: PassTarget,
+    content: PassContent,
+}
+
+impl RenderPass {
+    /// A pass which paints the scene given to `Pipeline::draw()`, with the shader as the default
+    /// shader of the canvas.
+    pub fn scene(shader: Shader, target: PassTarget) -> Self {
+        Self {
+            shader,
+            inputs: vec![],
+            target,
+            content: PassContent::Scene,
+        }
+    }
+
+    /// A pass which shades a quad covering the whole painting, such as a post-processing effect
+    /// over the outputs of earlier passes.
+    pub fn quad(shader: Shader, target: PassTarget) -> Self {
+        Self {
+            shader,
+            inputs: vec![],
+            target,
+            content: PassContent::Quad,
+        }
+    }
+
+    /// Binds the texture drawn by an earlier pass to the named `sampler2D` uniform of this pass.
+    pub fn input(mut self, uniform: &str, texture: &str) -> Self {
+        self.inputs
+            .push((String::from(uniform), String::from(texture)));
+        self
+    }
+}
+
+/// An offscreen texture which can be painted and then sampled by shaders.
+///
+/// Texture coordinates increase up and right, as painting coordinates do. Create one with
+/// `Pipeline::create_render_texture()`, and bind it to shaders with `Shader::with_texture()`.
+pub struct RenderTexture {
+    buffer: Texture2dMultisample,
+    texture: Rc<Texture2d>,
+}
+
+impl RenderTexture {
+    fn new(gpu: &Gpu, width: u32, height: u32) -> Result<Self> {
+        let buffer = gpu.build_texture(width, height)?;
+        buffer.as_surface().clear_color(0., 0., 0., 0.);
+        let texture = gpu.build_ram_texture(width, height)?;
+        texture.as_surface().clear_color(0., 0., 0., 0.);
+        Ok(Self {
+            buffer,
+            texture: Rc::new(texture),
+        })
+    }
+
+    /// The texture with everything painted so far, for binding to shaders.
+    pub fn texture(&self) -> Rc<Texture2d> {
+        self.texture.clone()
+    }
+
+    /// The width and height of the texture in pixels.
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.buffer.dimensions()
+    }
+
+    /// Clears the texture to transparent.
+    pub fn clear(&mut self) {
+        self.buffer.as_surface().clear_color(0., 0., 0., 0.);
+        self.texture.as_surface().clear_color(0., 0., 0., 0.);
+    }
+
+    /// Paints over the texture. The painting is scaled from coordinate space as the canvas given
+    /// to `paint` is.
+    fn paint(
+        &mut self,
+        gpu: &Gpu,
+        shader: Shader,
+        scale: f32,
+        paint: impl FnOnce(&mut Canvas),
+    ) -> Result<()> {
+        let mut canvas = Canvas::new(gpu.clone(), shader, scale);
+        paint(&mut canvas);
+
+        let (width, height) = self.dimensions();
+        gpu.upright()
+            .render(width, height, canvas, &mut self.buffer.as_surface())?;
+        gpu.resolve(&self.buffer, &self.texture);
+        Ok(())
+    }
+}
+
+/// A sequence of render passes, each of which draws into a named texture or the screen.
+///
+/// Passes run in order, with the textures drawn by earlier passes bound to the uniforms of later
+/// ones. This is the basis of blurs, bloom and other multi-stage effects.
+///
+/// Post-processing shaders added with `add_post_process()` run after every pass, over whatever the
+/// passes drew to the screen.
+pub struct Pipeline {
+    gpu: Gpu,
+    world: World,
+    passes: Vec<RenderPass>,
+    textures: HashMap<String, RenderTexture>,
+    post_processes: Vec<Shader>,
+    /// The textures post-processing passes alternate between reading and drawing.
+    post_textures: Vec<RenderTexture>,
+    /// The size in pixels of the textures of the passes and post-processing.
+    size: (u32, u32),
+    frame: usize,
+}
+
+impl Pipeline {
+    /// Creates a pipeline, allocating a texture the size of the output for each named target. A
+    /// pipeline without passes paints the scene straight to the screen.
+    ///
+    /// Fails if a pass reads a texture which no earlier pass draws.
+    pub fn new(gpu: &Gpu, world: World, mut passes: Vec<RenderPass>) -> Result<Self> {
+        let (width, height) = output_size(world);
+        if passes.is_empty() {
+            passes.push(RenderPass::scene(gpu.default_shader(), PassTarget::Screen));
+        }
+
+        let mut textures = HashMap::new();
+        for (i, pass) in passes.iter().enumerate() {
+            for (uniform, input) in &pass.inputs {
+                if !textures.contains_key(input) {
+                    bail!(
+                        "Render pass {} binds {} to texture {:?}, which no earlier pass draws",
+                        i,
+                        uniform,
+                        input
+                    );
+                }
+            }
+            if let PassTarget::Texture(name) = &pass.target {
+                if !textures.contains_key(name) {
+                    textures.insert(name.clone(), RenderTexture::new(gpu, width, height)?);
+                }
+            }
+        }
+
+        Ok(Self {
+            gpu: gpu.clone(),
+            world,
+            passes,
+            textures,
+            post_processes: vec![],
+            post_textures: vec![],
+            size: (width, height),
+            frame: 0,
+        })
+    }
+
+    /// Adds a shader run over a quad covering the whole painting after the passes and any
+    /// earlier post-processing. Each one reads the result so far from a `sampler2D` uniform
+    /// named `u_scene_texture`.
+    ///
+    /// Post-processing shaders also receive the time in seconds of the frame being drawn as
+    /// `u_time`, and the size of the output in pixels as the `vec2` `u_resolution`.
+    pub fn add_post_process(&mut self, shader: &Shader) -> Result<()> {
+        if self.post_textures.is_empty() {
+            let (width, height) = self.size;
+            for _ in 0..2 {
+                self.post_textures
+                    .push(RenderTexture::new(&self.gpu, width, height)?);
+            }
+        }
+
+        self.post_processes.push(shader.clone());
+        Ok(())
+    }
+
+    /// Replaces the shader of the pass at the index, keeping its inputs and target. The pass uses
+    /// it from the next call to `draw()`.
+    ///
+    /// Passes need not be given new shaders to pick up edits to shader files: shaders from a
+    /// `ShaderProgram` or `Shader::watch()` switch to the recompiled program in place.
+    pub fn set_pass_shader(&mut self, pass: usize, shader: Shader) -> Result<()> {
+        match self.passes.get_mut(pass) {
+            Some(pass) => pass.shader = shader,
+            None => bail!(
+                "Pipeline has {} passes; there is no pass {}",
+                self.passes.len(),
+                pass
+            ),
+        }
+        Ok(())
+    }
+
+    /// Reallocates the textures of the passes and post-processing at the given size in pixels,
+    /// such as the size of a resized window from `Context::resized`. Their contents are cleared.
+    ///
+    /// Painting is scaled to fill the textures whatever their size, as it is on screen.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<()> {
+        if width == 0 || height == 0 {
+            bail!("Pipeline size must be positive; got {}x{}", width, height);
+        }
+        if self.size == (width, height) {
+            return Ok(());
+        }
+
+        for texture in self.textures.values_mut() {
+            *texture = RenderTexture::new(&self.gpu, width, height)?;
+        }
+        for texture in &mut self.post_textures {
+            *texture = RenderTexture::new(&self.gpu, width, height)?;
+        }
+        self.size = (width, height);
+        Ok(())
+    }
+
+    /// Allocates a texture of the given size in pixels for painting with `draw_to()`.
+    pub fn create_render_texture(&self, width: u32, height: u32) -> Result<RenderTexture> {
+        RenderTexture::new(&self.gpu, width, height)
+    }
+
+    /// Paints into the texture without clearing it first, so painting can accumulate across
+    /// frames. Paths are scaled to output space as they are on the main canvas.
+    pub fn draw_to(
+        &self,
+        target: &mut RenderTexture,
+        paint: impl FnOnce(&mut Canvas),
+    ) -> Result<()> {
+        target.paint(
+            &self.gpu,
+            self.gpu.default_shader(),
+            self.world.scale,
+            paint,
+        )
+    }
+
+    /// Draws a single frame as `draw()` would onto a transparent canvas, and saves it to an
+    /// image file at the given path. The format is inferred from the path's extension.
+    ///
+    /// This needs no window or event loop, so it suits batch scripts with a headless GPU.
+    pub fn render_and_save(
+        &mut self,
+        path: impl AsRef<Path>,
+        scene: impl Fn(&mut Canvas),
+    ) -> Result<()> {
+        let (width, height) = output_size(self.world);
+        let buffer = self.gpu.build_texture(width, height)?;
+        buffer.as_surface().clear_color(0., 0., 0., 0.);
+
+        let mut canvas = Canvas::new(
+            self.gpu.clone(),
+            self.gpu.default_shader(),
+            self.world.scale,
+        );
+        self.draw(&mut canvas, scene)?;
+        self.gpu
+            .for_saving()
+            .render(width, height, canvas, &mut buffer.as_surface())?;
+        self.gpu.save_texture(&buffer, path)
+    }
+
+    /// Returns the named texture of the pipeline, as drawn by the last call to `draw()`.
+    pub fn texture(&self, name: &str) -> Option<Rc<Texture2d>> {
+        self.textures.get(name).map(RenderTexture::texture)
+    }
+
+    /// Runs every pass in order. Scene passes paint with `scene`, and passes targeting the screen
+    /// paint onto `canvas`, through any post-processing.
+    ///
+    /// The time post-processing receives advances by one frame at the world's framerate with
+    /// each call.
+    pub fn draw(&mut self, canvas: &mut Canvas, scene: impl Fn(&mut Canvas)) -> Result<()> {
+        if let Some(screen) = self.post_textures.first_mut() {
+            screen.clear();
+        }
+
+        for pass in &self.passes {
+            let shader =
+                pass.inputs
+                    .iter()
+                    .fold(pass.shader.clone(), |shader, (uniform, input)| {
+                        shader.with_texture(uniform, self.textures[input].texture())
+                    });
+
+            let world = self.world;
+            let paint = |canvas: &mut Canvas| match pass.content {
+                PassContent::Scene => scene(canvas),
+                PassContent::Quad => {
+                    canvas.paint(Filled(world));
+                }
+            };
+
+            match &pass.target {
+                PassTarget::Texture(name) => {
+                    let target = self
+                        .textures
+                        .get_mut(name)
+                        .expect("pipeline targets to be allocated");
+                    // Only the samples are cleared, so a pass may read the texture it replaces.
+                    target.buffer.as_surface().clear_color(0., 0., 0., 0.);
+                    target.paint(&self.gpu, shader, self.world.scale, paint)?;
+                }
+                PassTarget::Screen => match self.post_textures.first_mut() {
+                    Some(screen) => screen.paint(&self.gpu, shader, self.world.scale, paint)?,
+                    None => paint_with(canvas, shader, paint)?,
+                },
+            }
+        }
+
+        let (width, height) = self.size;
+        let time = self.frame as f32 / self.world.framerate as f32;
+        for (i, post_process) in self.post_processes.iter().enumerate() {
+            let shader = post_process
+                .with_texture("u_scene_texture", self.post_textures[i % 2].texture())
+                .with_value("u_time", UniformValue::Float(time))
+                .with_value(
+                    "u_resolution",
+                    UniformValue::Vec2([width as f32, height as f32]),
+                );
+
+            let world = self.world;
+            let paint = |canvas: &mut Canvas| {
+                canvas.paint(Filled(world));
+            };
+            if i + 1 == self.post_processes.len() {
+                paint_with(canvas, shader, paint)?;
+            } else {
+                let target = &mut self.post_textures[(i + 1) % 2];
+                target.clear();
+                target.paint(&self.gpu, shader, self.world.scale, paint)?;
+            }
+        }
+
+        self.frame += 1;
+        Ok(())
+    }
+}
+
+/// The size in pixels of the output of the world.
+fn output_size(world: World) -> (u32, u32) {
+    (
+        (world.width * world.scale) as u32,
+        (world.height * world.scale) as u32,
+    )
+}
+
+/// Paints onto the canvas with the shader, leaving the canvas state as it was.
+fn paint_with(canvas: &mut Canvas, shader: Shader, paint: impl FnOnce(&mut Canvas)) -> Result<()> {
+    canvas.save()?;
+    canvas.set_shader(shader);
+    paint(canvas);
+    canvas.restore()?;
+    Ok(())
+}
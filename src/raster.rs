@@ -1,10 +1,10 @@
 //! Path rasterization.
 
 use crate::{gpu::GpuVertex, Result, P2};
-use lyon_path::Builder;
+use lyon_path::Path;
 use lyon_tessellation::{
-    BuffersBuilder, FillAttributes, FillOptions, FillTessellator, LineJoin, StrokeAttributes,
-    StrokeOptions, StrokeTessellator, VertexBuffers,
+    BuffersBuilder, FillAttributes, FillOptions, FillTessellator, LineCap, LineJoin,
+    StrokeAttributes, StrokeOptions, StrokeTessellator, VertexBuffers,
 };
 use palette::LinSrgba;
 
@@ -18,11 +18,17 @@ pub enum Method {
     /// In stroke method, the rasterizer will treat the area immediately adjacent the path within
     /// the given width as part of the rastered area. In this method, paths are left open
     /// and no edge between the last and first vertex is assumed.
-    Stroke(f32),
+    Stroke {
+        width: f32,
+        start_cap: LineCap,
+        end_cap: LineCap,
+        join: LineJoin,
+        miter_limit: f32,
+    },
 }
 
 pub fn raster_path(
-    builder: Builder,
+    path: Path,
     method: Method,
     color: LinSrgba,
 ) -> Result<(Vec<GpuVertex>, Vec<u32>)> {
@@ -34,7 +40,7 @@ pub fn raster_path(
 
             let mut tessellator = FillTessellator::new();
             let result = tessellator.tessellate_path(
-                &builder.build(),
+                &path,
                 &FillOptions::default().with_tolerance(0.05),
                 &mut buffers_builder,
             );
@@ -60,7 +66,13 @@ pub fn raster_path(
                 buffers.indices,
             ))
         }
-        Method::Stroke(width) => {
+        Method::Stroke {
+            width,
+            start_cap,
+            end_cap,
+            join,
+            miter_limit,
+        } => {
             let ctor = |v: P2, _: StrokeAttributes| -> P2 { v };
             let mut buffers: VertexBuffers<P2, u32> = VertexBuffers::new();
             let mut buffers_builder = BuffersBuilder::new(&mut buffers, ctor);
@@ -68,10 +80,13 @@ pub fn raster_path(
             let mut tessellator = StrokeTessellator::new();
             tessellator
                 .tessellate_path(
-                    &builder.build(),
+                    &path,
                     &StrokeOptions::default()
-                        .with_line_join(LineJoin::MiterClip)
+                        .with_line_join(join)
+                        .with_miter_limit(miter_limit)
                         .with_line_width(width)
+                        .with_start_cap(start_cap)
+                        .with_end_cap(end_cap)
                         .with_tolerance(0.05),
                     &mut buffers_builder,
                 )
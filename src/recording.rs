@@ -0,0 +1,360 @@
+//! Recordings of canvas calls, for computing static geometry once and replaying it each frame.
+
+use crate::{
+    canvas::{BlendMode, Canvas, StrokeCap, StrokeJoin},
+    Angle, Paint, Rect, Result, Shader, Transform, P2, V2,
+};
+use palette::{Alpha, IntoColor, LinSrgba};
+use std::rc::Rc;
+
+/// A recorded call to the `Canvas` method of the same name. See `Canvas` for each.
+#[derive(Clone)]
+pub enum DrawCmd {
+    /// An element to paint, such as a form or a `Filled` form.
+    Paint(Rc<dyn Paint>),
+    SetColor(LinSrgba),
+    MoveTo(P2),
+    BeginSubpath(P2),
+    LineTo(P2),
+    QuadraticTo {
+        ctrl: P2,
+        end: P2,
+    },
+    CubicTo {
+        ctrl0: P2,
+        ctrl1: P2,
+        end: P2,
+    },
+    Arc {
+        center: P2,
+        radii: V2,
+        sweep: Angle,
+        phase: Angle,
+    },
+    ClosePath,
+    SetStrokeWidth(f32),
+    SetStrokeCaps {
+        start: StrokeCap,
+        end: StrokeCap,
+    },
+    SetStrokeJoin(StrokeJoin),
+    Fill,
+    Stroke,
+    SetShader(Shader),
+    PushTransform(Transform),
+    PopTransform,
+    PushClipRect(Rect),
+    PopClipRect,
+    Translate(V2),
+    Rotate(Angle),
+    Scale {
+        x: f32,
+        y: f32,
+    },
+    SetFillGradientLinear {
+        start: P2,
+        end: P2,
+        stops: Vec<(f32, LinSrgba)>,
+    },
+    SetFillGradientRadial {
+        center: P2,
+        radius: f32,
+        stops: Vec<(f32, LinSrgba)>,
+    },
+    SetBlendMode(BlendMode),
+    Save,
+    Restore,
+    PushLayer {
+        opacity: f32,
+        blend: BlendMode,
+    },
+    PopLayer,
+}
+
+/// Records canvas calls to replay later with `replay()`, such as a background computed once in
+/// `Artist::setup()` and painted every frame.
+///
+/// The recorder has the paint, path and state methods of `Canvas`, and chains the same way.
+/// Replaying skips whatever computed the calls, but the paths are still tessellated each time
+/// they are painted.
+#[derive(Clone, Default)]
+pub struct CanvasRecorder {
+    commands: Vec<DrawCmd>,
+}
+
+impl CanvasRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the recorded commands, in the order they were called.
+    pub fn into_recording(self) -> Vec<DrawCmd> {
+        self.commands
+    }
+
+    /// Records a command directly.
+    pub fn record(&mut self, command: DrawCmd) -> &mut Self {
+        self.commands.push(command);
+        self
+    }
+
+    // The rest each record a call to the `Canvas` method of the same name.
+
+    /// Records painting the element. The element is kept as it is, so whatever computed it is
+    /// skipped on replay, but it paints its paths again each time.
+    pub fn paint(&mut self, element: impl Paint + 'static) -> &mut Self {
+        self.record(DrawCmd::Paint(Rc::new(element)))
+    }
+
+    pub fn set_color(&mut self, color: impl IntoColor) -> &mut Self {
+        self.record(DrawCmd::SetColor(Alpha::from(color.into_rgb())))
+    }
+
+    pub fn set_color_alpha(&mut self, color: impl IntoColor, alpha: f32) -> &mut Self {
+        self.record(DrawCmd::SetColor(Alpha {
+            color: color.into_rgb(),
+            alpha,
+        }))
+    }
+
+    pub fn move_to(&mut self, dest: P2) -> &mut Self {
+        self.record(DrawCmd::MoveTo(dest))
+    }
+
+    pub fn begin_subpath(&mut self, dest: P2) -> &mut Self {
+        self.record(DrawCmd::BeginSubpath(dest))
+    }
+
+    pub fn line_to(&mut self, dest: P2) -> &mut Self {
+        self.record(DrawCmd::LineTo(dest))
+    }
+
+    pub fn quadratic_to(&mut self, ctrl: P2, end: P2) -> &mut Self {
+        self.record(DrawCmd::QuadraticTo { ctrl, end })
+    }
+
+    pub fn cubic_to(&mut self, ctrl0: P2, ctrl1: P2, end: P2) -> &mut Self {
+        self.record(DrawCmd::CubicTo { ctrl0, ctrl1, end })
+    }
+
+    pub fn arc(&mut self, center: P2, radii: V2, sweep: Angle, phase: Angle) -> &mut Self {
+        self.record(DrawCmd::Arc {
+            center,
+            radii,
+            sweep,
+            phase,
+        })
+    }
+
+    pub fn close_path(&mut self) -> &mut Self {
+        self.record(DrawCmd::ClosePath)
+    }
+
+    pub fn set_stroke_width(&mut self, stroke_width: f32) -> &mut Self {
+        self.record(DrawCmd::SetStrokeWidth(stroke_width))
+    }
+
+    pub fn set_stroke_caps(&mut self, start: StrokeCap, end: StrokeCap) -> &mut Self {
+        self.record(DrawCmd::SetStrokeCaps { start, end })
+    }
+
+    pub fn set_stroke_join(&mut self, join: StrokeJoin) -> &mut Self {
+        self.record(DrawCmd::SetStrokeJoin(join))
+    }
+
+    pub fn fill(&mut self) -> &mut Self {
+        self.record(DrawCmd::Fill)
+    }
+
+    pub fn stroke(&mut self) -> &mut Self {
+        self.record(DrawCmd::Stroke)
+    }
+
+    pub fn set_shader(&mut self, shader: Shader) -> &mut Self {
+        self.record(DrawCmd::SetShader(shader))
+    }
+
+    pub fn push_transform(&mut self, transform: Transform) -> &mut Self {
+        self.record(DrawCmd::PushTransform(transform))
+    }
+
+    pub fn pop_transform(&mut self) -> &mut Self {
+        self.record(DrawCmd::PopTransform)
+    }
+
+    pub fn push_clip_rect(&mut self, rect: Rect) -> &mut Self {
+        self.record(DrawCmd::PushClipRect(rect))
+    }
+
+    pub fn pop_clip_rect(&mut self) -> &mut Self {
+        self.record(DrawCmd::PopClipRect)
+    }
+
+    pub fn translate(&mut self, translation: V2) -> &mut Self {
+        self.record(DrawCmd::Translate(translation))
+    }
+
+    pub fn rotate(&mut self, theta: Angle) -> &mut Self {
+        self.record(DrawCmd::Rotate(theta))
+    }
+
+    pub fn scale(&mut self, x: f32, y: f32) -> &mut Self {
+        self.record(DrawCmd::Scale { x, y })
+    }
+
+    pub fn set_fill_gradient_linear_stops(
+        &mut self,
+        start: P2,
+        end: P2,
+        stops: Vec<(f32, LinSrgba)>,
+    ) -> &mut Self {
+        self.record(DrawCmd::SetFillGradientLinear { start, end, stops })
+    }
+
+    pub fn set_fill_gradient_radial_stops(
+        &mut self,
+        center: P2,
+        radius: f32,
+        stops: Vec<(f32, LinSrgba)>,
+    ) -> &mut Self {
+        self.record(DrawCmd::SetFillGradientRadial {
+            center,
+            radius,
+            stops,
+        })
+    }
+
+    pub fn set_blend_mode(&mut self, blend: BlendMode) -> &mut Self {
+        self.record(DrawCmd::SetBlendMode(blend))
+    }
+
+    pub fn save(&mut self) -> &mut Self {
+        self.record(DrawCmd::Save)
+    }
+
+    pub fn restore(&mut self) -> &mut Self {
+        self.record(DrawCmd::Restore)
+    }
+
+    pub fn push_layer(&mut self, opacity: f32, blend: BlendMode) -> &mut Self {
+        self.record(DrawCmd::PushLayer { opacity, blend })
+    }
+
+    pub fn pop_layer(&mut self) -> &mut Self {
+        self.record(DrawCmd::PopLayer)
+    }
+}
+
+/// Replays recorded commands onto the canvas, under its active transform and state.
+///
+/// Fails where the canvas method a command records would fail, such as popping more
+/// transforms than were pushed; the commands before it are left applied.
+pub fn replay(commands: &[DrawCmd], canvas: &mut Canvas) -> Result<()> {
+    for command in commands {
+        match command {
+            DrawCmd::Paint(element) => {
+                element.paint(canvas);
+                &mut *canvas
+            }
+            DrawCmd::SetColor(color) => canvas.set_color_alpha(color.color, color.alpha),
+            DrawCmd::MoveTo(dest) => canvas.move_to(*dest),
+            DrawCmd::BeginSubpath(dest) => canvas.begin_subpath(*dest),
+            DrawCmd::LineTo(dest) => canvas.line_to(*dest),
+            DrawCmd::QuadraticTo { ctrl, end } => canvas.quadratic_to(*ctrl, *end),
+            DrawCmd::CubicTo { ctrl0, ctrl1, end } => canvas.cubic_to(*ctrl0, *ctrl1, *end),
+            DrawCmd::Arc {
+                center,
+                radii,
+                sweep,
+                phase,
+            } => canvas.arc(*center, *radii, *sweep, *phase),
+            DrawCmd::ClosePath => canvas.close_path(),
+            DrawCmd::SetStrokeWidth(width) => canvas.set_stroke_width(*width),
+            DrawCmd::SetStrokeCaps { start, end } => canvas.set_stroke_caps(*start, *end),
+            DrawCmd::SetStrokeJoin(join) => canvas.set_stroke_join(*join),
+            DrawCmd::Fill => canvas.fill(),
+            DrawCmd::Stroke => canvas.stroke(),
+            DrawCmd::SetShader(shader) => canvas.set_shader(shader.clone()),
+            DrawCmd::PushTransform(transform) => canvas.push_transform(*transform),
+            DrawCmd::PopTransform => canvas.pop_transform()?,
+            DrawCmd::PushClipRect(rect) => canvas.push_clip_rect(*rect),
+            DrawCmd::PopClipRect => canvas.pop_clip_rect()?,
+            DrawCmd::Translate(translation) => canvas.translate(*translation),
+            DrawCmd::Rotate(theta) => canvas.rotate(*theta),
+            DrawCmd::Scale { x, y } => canvas.scale(*x, *y),
+            DrawCmd::SetFillGradientLinear { start, end, stops } => {
+                canvas.set_fill_gradient_linear_stops(*start, *end, stops.clone())
+            }
+            DrawCmd::SetFillGradientRadial {
+                center,
+                radius,
+                stops,
+            } => canvas.set_fill_gradient_radial_stops(*center, *radius, stops.clone()),
+            DrawCmd::SetBlendMode(blend) => canvas.set_blend_mode(*blend),
+            DrawCmd::Save => canvas.save()?,
+            DrawCmd::Restore => canvas.restore()?,
+            DrawCmd::PushLayer { opacity, blend } => canvas.push_layer(*opacity, *blend),
+            DrawCmd::PopLayer => canvas.pop_layer()?,
+        };
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        gpu::{Drawable, Element, Gpu},
+        raster::Method,
+        Ellipse, Filled, Ngon,
+    };
+    use palette::LinSrgb;
+
+    #[test]
+    fn records_calls_in_order() {
+        let mut recorder = CanvasRecorder::new();
+        recorder
+            .move_to(P2::new(0., 0.))
+            .line_to(P2::new(1., 0.))
+            .set_stroke_width(2.)
+            .stroke();
+        let recording = recorder.into_recording();
+
+        assert_eq!(recording.len(), 4);
+        assert!(matches!(recording[1], DrawCmd::LineTo(p) if p == P2::new(1., 0.)));
+        assert!(matches!(recording[2], DrawCmd::SetStrokeWidth(w) if w == 2.));
+        assert!(matches!(recording[3], DrawCmd::Stroke));
+    }
+
+    #[test]
+    #[ignore = "needs a GPU"]
+    fn replays_recorded_forms() {
+        let gpu = Gpu::new_headless().unwrap();
+        let mut recorder = CanvasRecorder::new();
+        recorder
+            .paint(Filled(Ellipse::circle(P2::new(1., 1.), 1.)))
+            .set_color(LinSrgb::new(1., 0., 0.))
+            .paint(Ngon::square(P2::new(4., 4.), 1.))
+            .stroke();
+        let recording = recorder.into_recording();
+        assert!(matches!(recording[0], DrawCmd::Paint(_)));
+
+        let mut canvas = Canvas::new(gpu.clone(), gpu.default_shader(), 1.);
+        replay(&recording, &mut canvas).unwrap();
+        let elements: Vec<Element> = canvas
+            .into_iter()
+            .filter_map(|drawable| match drawable {
+                Drawable::Element(element) => Some(element),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(elements.len(), 2);
+        assert!(matches!(elements[0].raster_method, Method::Fill));
+        assert!(matches!(elements[1].raster_method, Method::Stroke { .. }));
+        assert_eq!(elements[1].color, LinSrgba::new(1., 0., 0., 1.));
+        assert!(elements
+            .iter()
+            .all(|element| element.path.iter().count() > 0));
+    }
+}
@@ -1,20 +1,24 @@
 //! Canvas rendering.
 
-use crate::{canvas::*, gpu::*, paint::*, uniforms::*, Options, Result, World};
+use crate::{
+    canvas::*, gif_export::GifExporter, gpu::*, input::*, paint::*, sampling, uniforms::*, Options,
+    ResizeBehavior, Result, World, P2,
+};
 use glium::{
     glutin::event_loop::{ControlFlow, EventLoop},
     texture::{texture2d_multisample::Texture2dMultisample, Dimensions, MipmapsOption},
-    Frame, GlObject, Program,
+    uniforms::MagnifySamplerFilter,
+    Frame, GlObject, Program, Surface,
 };
-use glutin::platform::desktop::EventLoopExtDesktop;
+use glutin::{event::VirtualKeyCode, platform::desktop::EventLoopExtDesktop};
 use image::{ImageBuffer, Rgba};
-use palette::{
-    encoding::{srgb::Srgb, TransferFn},
-    Component,
+use rand::{random, rngs::StdRng, SeedableRng};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    rc::Rc,
+    time::{Duration, Instant},
 };
-use rand::{random, rngs::StdRng};
-use rayon::prelude::*;
-use std::{path::PathBuf, rc::Rc, time::Duration};
 
 /// The context of the current render frame.
 #[derive(Debug)]
@@ -29,6 +33,94 @@ pub struct Context<'a> {
     pub frame: usize,
     /// The elapsed time in the composition.
     pub time: Duration,
+    /// The wall-clock time in seconds since the previous frame began.
+    ///
+    /// This is zero on the first frame. Unlike `time`, it reflects how long frames actually take.
+    pub delta_seconds: f32,
+    /// The wall-clock time in seconds since the first frame began.
+    pub elapsed_seconds: f64,
+    /// The position of the mouse cursor in coordinate space, or `None` if it is outside the
+    /// window or has not moved over it yet. Always `None` when rendering to file.
+    pub mouse_position: Option<P2>,
+    /// The keys held down in the window. Always empty when rendering to file.
+    pub keys_held: &'a HashSet<VirtualKeyCode>,
+    /// The mouse events since the previous frame. Always empty when rendering to file.
+    pub mouse_events: &'a [MouseEvent],
+    /// The new size in pixels of the painting on screen, if the window was resized since the
+    /// previous frame. Offscreen buffers such as those of a `Pipeline` can be resized to match.
+    pub resized: Option<(u32, u32)>,
+    /// The lines scrolled since the previous frame, positive for scrolling up. Scrolls by
+    /// touchpad are converted to lines at `PIXELS_PER_SCROLL_LINE`.
+    pub scroll_delta: f32,
+    /// Measurements of rendering the previous frame, including any rendering done while painting
+    /// it. These are zero on the first frame.
+    pub last_frame_metrics: FrameMetrics,
+    named_rngs: &'a mut HashMap<String, StdRng>,
+}
+
+impl<'a> Context<'a> {
+    /// Returns a shorter-lived copy of the context sharing its rngs, so that it can be passed to
+    /// more than one painter in a frame.
+    pub fn reborrow(&mut self) -> Context<'_> {
+        Context {
+            rng: self.rng,
+            world: self.world,
+            frame: self.frame,
+            time: self.time,
+            delta_seconds: self.delta_seconds,
+            elapsed_seconds: self.elapsed_seconds,
+            mouse_position: self.mouse_position,
+            keys_held: self.keys_held,
+            mouse_events: self.mouse_events,
+            resized: self.resized,
+            scroll_delta: self.scroll_delta,
+            last_frame_metrics: self.last_frame_metrics,
+            named_rngs: self.named_rngs,
+        }
+    }
+
+    /// Returns a random number generator dedicated to the given name. Like `rng`, it is shared
+    /// between frames.
+    ///
+    /// Each named rng is seeded from the world seed and its name alone, so drawing from one does
+    /// not shift the sequence of any other, or of `rng`.
+    pub fn named_rng(&mut self, name: &str) -> &mut StdRng {
+        let seed = self.world.seed;
+        self.named_rngs
+            .entry(String::from(name))
+            .or_insert_with(|| StdRng::seed_from_u64(named_seed(seed, name)))
+    }
+
+    /// Samples a normal distribution from `rng`.
+    pub fn normal(&mut self, mean: f32, std_dev: f32) -> f32 {
+        sampling::normal(self.rng, mean, std_dev)
+    }
+
+    /// Samples an exponential distribution of the given rate, whose mean is `1 / rate`, from
+    /// `rng`.
+    pub fn exponential(&mut self, rate: f32) -> f32 {
+        sampling::exponential(self.rng, rate)
+    }
+
+    /// Samples uniformly from [lo, hi) from `rng`.
+    pub fn uniform(&mut self, lo: f32, hi: f32) -> f32 {
+        sampling::uniform(self.rng, lo, hi)
+    }
+
+    /// Samples a Poisson distribution of the given mean from `rng`.
+    pub fn poisson(&mut self, lambda: f64) -> u64 {
+        sampling::poisson(self.rng, lambda)
+    }
+}
+
+/// Mixes a seed with a name using FNV-1a, which unlike the std hasher is stable across builds.
+fn named_seed(seed: u64, name: &str) -> u64 {
+    seed.to_le_bytes()
+        .iter()
+        .chain(name.as_bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        })
 }
 
 pub enum Rebuild {
@@ -45,12 +137,16 @@ pub struct RenderReport {
 #[derive(Debug)]
 struct FrameUpdates {
     new_seed: Option<u64>,
+    toggle_pause: bool,
     wait: Option<Duration>,
     should_quit: bool,
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum RenderStrategy<F1, F2> {
     Screen {
+        /// The size in pixels of the window's framebuffer.
+        window_size: (u32, u32),
         get_frame: F1,
         events_loop: EventLoop<()>,
         wait: Duration,
@@ -69,6 +165,18 @@ pub struct Renderer<'a, F1, F2> {
     pub gpu: &'a Gpu,
     pub options: Options,
     pub rng: &'a mut StdRng,
+    pub named_rngs: HashMap<String, StdRng>,
+    /// Where to capture painted frames, if anywhere.
+    pub gif: Option<&'a mut GifExporter>,
+    /// The last position of the mouse cursor in coordinate space.
+    pub mouse_position: Option<P2>,
+    /// The keys held down in the window.
+    pub keys_held: HashSet<VirtualKeyCode>,
+    /// The mouse events since the last frame was painted.
+    pub mouse_events: Vec<MouseEvent>,
+    /// The size of the painting on screen, if the window was resized since the last frame was
+    /// painted.
+    pub resized: Option<(u32, u32)>,
     pub output_width: u32,
     pub output_height: u32,
 }
@@ -82,32 +190,77 @@ impl<'a, F1: Fn() -> Frame + 'a, F2: Fn(usize, u64) -> PathBuf> Renderer<'a, F1,
     ) -> Result<RenderReport> {
         let default_shader = self.gpu.default_shader();
 
-        let end_frame = self.options.world.frames.map(|f| f + self.options.delay);
-        for frame in std::iter::successors(Some(0), move |last| {
-            if let Some(end_frame) = end_frame {
-                if last + 1 <= end_frame {
-                    Some(last + 1)
-                } else {
-                    None
-                }
-            } else {
-                Some(last + 1)
-            }
-        }) {
-            let mut canvas = Canvas::new(default_shader.clone(), self.options.world.scale);
-            f(
-                Context {
-                    rng: self.rng,
-                    world: self.options.world,
-                    frame,
-                    time: Duration::from_secs_f32(
-                        frame as f32 / self.options.world.framerate as f32,
-                    ),
-                },
-                &mut canvas,
+        let end_frame = match &*self.strategy {
+            RenderStrategy::File { .. } => self
+                .options
+                .saved_frames()
+                .map(|f| f + self.options.save_delay()),
+            _ => self.options.world.frames.map(|f| f + self.options.delay),
+        };
+        let render_start = Instant::now();
+        let mut last_frame_start = render_start;
+        let mut frame = 0;
+        let mut paused = false;
+        // Rendering before the first frame, such as during setup, is not counted in any frame.
+        self.gpu.take_metrics();
+        let mut last_frame_metrics = FrameMetrics::default();
+        while end_frame
+            .map(|end_frame| frame <= end_frame)
+            .unwrap_or(true)
+        {
+            let frame_start = Instant::now();
+            let delta_seconds = frame_start.duration_since(last_frame_start).as_secs_f32();
+            let elapsed_seconds = frame_start.duration_since(render_start).as_secs_f64();
+            last_frame_start = frame_start;
+            self.gpu.reload_changed_programs();
+
+            // While paused, an empty canvas is rendered so the last painted frame stays on screen.
+            let painting = !paused;
+            let mut canvas = Canvas::new(
+                self.gpu.clone(),
+                default_shader.clone(),
+                self.options.world.scale,
             );
+            if painting {
+                let frame = self.options.loop_frame(frame);
+                f(
+                    Context {
+                        rng: self.rng,
+                        world: self.options.world,
+                        frame,
+                        time: Duration::from_secs_f32(
+                            frame as f32 / self.options.logical_framerate() as f32,
+                        ),
+                        delta_seconds,
+                        elapsed_seconds,
+                        mouse_position: self.mouse_position,
+                        keys_held: &self.keys_held,
+                        mouse_events: &self.mouse_events,
+                        resized: self.resized,
+                        scroll_delta: self
+                            .mouse_events
+                            .iter()
+                            .map(|event| match event {
+                                MouseEvent::ScrollDelta(delta) => *delta,
+                                _ => 0.,
+                            })
+                            .sum(),
+                        last_frame_metrics,
+                        named_rngs: &mut self.named_rngs,
+                    },
+                    &mut canvas,
+                );
+            }
+            if painting {
+                self.mouse_events.clear();
+                self.resized = None;
+            }
 
-            let updates = self.render_frame(self.options.world.seed, frame, canvas)?;
+            let updates = self.render_frame(self.options.world.seed, frame, painting, canvas)?;
+            let metrics = self.gpu.take_metrics();
+            if painting {
+                last_frame_metrics = metrics;
+            }
             if updates.should_quit {
                 return Ok(RenderReport {
                     explicit_quit: true,
@@ -116,7 +269,9 @@ impl<'a, F1: Fn() -> Frame + 'a, F2: Fn(usize, u64) -> PathBuf> Renderer<'a, F1,
             }
 
             if let Some(wait) = updates.wait {
-                std::thread::sleep(wait);
+                // Only sleep for whatever is left of the frame budget after painting and
+                // rendering, so the effective frame rate matches the requested one.
+                std::thread::sleep(wait.saturating_sub(frame_start.elapsed()));
             }
 
             if let Some(new_seed) = updates.new_seed {
@@ -125,6 +280,16 @@ impl<'a, F1: Fn() -> Frame + 'a, F2: Fn(usize, u64) -> PathBuf> Renderer<'a, F1,
                     rebuild: Some(Rebuild::NewSeed(new_seed)),
                 });
             }
+
+            if updates.toggle_pause {
+                paused = !paused;
+                self.gpu
+                    .set_title(if paused { "valora (paused)" } else { "valora" });
+            }
+
+            if painting {
+                frame += 1;
+            }
         }
 
         Ok(RenderReport {
@@ -137,16 +302,23 @@ impl<'a, F1: Fn() -> Frame + 'a, F2: Fn(usize, u64) -> PathBuf> Renderer<'a, F1,
         &mut self,
         current_seed: u64,
         frame_number: usize,
+        painting: bool,
         canvas: Canvas,
     ) -> Result<FrameUpdates> {
         match self.strategy {
             RenderStrategy::Screen {
+                window_size,
                 get_frame,
                 events_loop,
                 buffer,
                 texture_program,
                 wait,
             } => {
+                let viewport = screen_viewport(
+                    *window_size,
+                    (self.output_width, self.output_height),
+                    self.options.resize_behavior,
+                );
                 self.gpu.render(
                     self.output_width,
                     self.output_height,
@@ -180,25 +352,71 @@ impl<'a, F1: Fn() -> Frame + 'a, F2: Fn(usize, u64) -> PathBuf> Renderer<'a, F1,
                         },
                     },
                 );
-                let mut quad_canvas = Canvas::new(shader.clone(), self.options.world.scale);
+                let mut quad_canvas =
+                    Canvas::new(self.gpu.clone(), shader.clone(), self.options.world.scale);
                 quad_canvas.paint(Filled(self.options.world));
 
+                if painting && frame_number > self.options.delay {
+                    if let Some(gif) = self.gif.as_mut() {
+                        let (width, height) = buffer.dimensions();
+                        // Frames painted after the window is resized differ in size.
+                        if gif.fits(width, height) {
+                            // Windowed rendering is flipped relative to saved images.
+                            let rgba =
+                                flip_rows(&self.gpu.read_srgba8(buffer)?, width as usize * 4);
+                            gif.add_frame(&rgba, width, height)?;
+                        } else {
+                            gif.skip_frame(width, height);
+                        }
+                    }
+                }
+
                 let mut frame = get_frame();
                 frame.set_finish()?;
 
-                self.gpu.render(
-                    self.output_width,
-                    self.output_height,
-                    quad_canvas,
-                    &mut frame,
-                )?;
+                if buffer.dimensions() == *window_size {
+                    self.gpu.render(
+                        self.output_width,
+                        self.output_height,
+                        quad_canvas,
+                        &mut frame,
+                    )?;
+                } else {
+                    // A letterboxed painting is copied between black bars.
+                    let (width, height) = buffer.dimensions();
+                    let resolved = self.gpu.build_ram_texture(width, height)?;
+                    self.gpu.resolve(buffer, &resolved);
+                    frame.clear_color(0., 0., 0., 1.);
+                    resolved.as_surface().blit_color(
+                        &glium::Rect {
+                            left: 0,
+                            bottom: 0,
+                            width,
+                            height,
+                        },
+                        &frame,
+                        &glium::BlitTarget {
+                            left: viewport.left as u32,
+                            bottom: (window_size.1 as f64 - viewport.top - viewport.height) as u32,
+                            width: width as i32,
+                            height: height as i32,
+                        },
+                        MagnifySamplerFilter::Nearest,
+                    );
+                }
 
                 let mut new_seed = None;
+                let mut toggle_pause = false;
+                let mut save_frame = false;
                 let mut should_quit = false;
+                let mut mouse_position = self.mouse_position;
+                let keys_held = &mut self.keys_held;
+                let mouse_events = &mut self.mouse_events;
+                let mut new_window_size = None;
+                let world = self.options.world;
                 events_loop.run_return(|event, _, control_flow| {
                     use glutin::event::{
-                        DeviceEvent, ElementState, Event, KeyboardInput, VirtualKeyCode,
-                        WindowEvent,
+                        DeviceEvent, ElementState, Event, KeyboardInput, WindowEvent,
                     };
                     match event {
                         Event::DeviceEvent {
@@ -222,20 +440,125 @@ impl<'a, F1: Fn() -> Frame + 'a, F2: Fn(usize, u64) -> PathBuf> Renderer<'a, F1,
                         } => {
                             new_seed = Some(random());
                         }
+                        Event::DeviceEvent {
+                            event:
+                                DeviceEvent::Key(KeyboardInput {
+                                    state: ElementState::Released,
+                                    virtual_keycode: Some(VirtualKeyCode::Space),
+                                    ..
+                                }),
+                            ..
+                        } => {
+                            toggle_pause = true;
+                        }
+                        Event::DeviceEvent {
+                            event:
+                                DeviceEvent::Key(KeyboardInput {
+                                    state: ElementState::Released,
+                                    virtual_keycode: Some(VirtualKeyCode::S),
+                                    ..
+                                }),
+                            ..
+                        } => {
+                            save_frame = true;
+                        }
                         Event::WindowEvent {
                             event: WindowEvent::CloseRequested,
                             ..
                         } => {
                             should_quit = true;
                         }
+                        Event::WindowEvent {
+                            event: WindowEvent::CursorLeft { .. },
+                            ..
+                        } => {
+                            mouse_position = None;
+                        }
+                        Event::WindowEvent {
+                            event:
+                                WindowEvent::KeyboardInput {
+                                    input:
+                                        KeyboardInput {
+                                            state,
+                                            virtual_keycode: Some(key),
+                                            ..
+                                        },
+                                    ..
+                                },
+                            ..
+                        } => match state {
+                            ElementState::Pressed => {
+                                keys_held.insert(key);
+                            }
+                            ElementState::Released => {
+                                keys_held.remove(&key);
+                            }
+                        },
+                        Event::WindowEvent {
+                            event: WindowEvent::Focused(false),
+                            ..
+                        } => {
+                            // Releases are not reported to unfocused windows.
+                            keys_held.clear();
+                        }
+                        Event::WindowEvent {
+                            event: WindowEvent::Resized(size),
+                            ..
+                        } => {
+                            new_window_size = Some((size.width, size.height));
+                        }
+                        Event::WindowEvent { event, .. } => {
+                            if let Some(mouse_event) =
+                                mouse_event(&event, viewport, &world, mouse_position)
+                            {
+                                if let MouseEvent::Moved(position) = mouse_event {
+                                    mouse_position = Some(position);
+                                }
+                                mouse_events.push(mouse_event);
+                            }
+                        }
                         _ => {}
                     }
 
                     *control_flow = ControlFlow::Exit;
                 });
+                self.mouse_position = mouse_position;
+
+                match new_window_size {
+                    Some(size) if self.options.resizable && size != *window_size => {
+                        *window_size = (size.0.max(1), size.1.max(1));
+                        let viewport = screen_viewport(
+                            *window_size,
+                            (self.output_width, self.output_height),
+                            self.options.resize_behavior,
+                        );
+                        let size = (viewport.width as u32, viewport.height as u32);
+                        *buffer = self.gpu.build_texture(size.0, size.1)?;
+                        self.resized = Some(size);
+                    }
+                    _ => {}
+                }
+
+                if save_frame {
+                    // While paused, the buffer still holds the last painted frame.
+                    let shown_frame = if painting {
+                        frame_number
+                    } else {
+                        frame_number.saturating_sub(1)
+                    };
+                    let path = PathBuf::from(format!("{}_{:06}.png", current_seed, shown_frame));
+                    let (width, height) = buffer.dimensions();
+                    // Windowed rendering is flipped relative to saved images.
+                    let rgba = flip_rows(&self.gpu.read_srgba8(buffer)?, width as usize * 4);
+                    let image: ImageBuffer<Rgba<u8>, Vec<u8>> =
+                        ImageBuffer::from_raw(width, height, rgba).unwrap();
+                    image.save(&path)?;
+                    println!("Saved frame to {}", path.display());
+                }
 
                 Ok(FrameUpdates {
                     new_seed,
+                    toggle_pause,
                     wait: Some(*wait),
                     should_quit,
                 })
@@ -251,25 +574,18 @@ impl<'a, F1: Fn() -> Frame + 'a, F2: Fn(usize, u64) -> PathBuf> Renderer<'a, F1,
                     &mut buffer.as_surface(),
                 )?;
 
-                if frame_number > self.options.delay {
-                    let raw: glium::texture::RawImage2d<u8> = self.gpu.read_to_ram(&buffer)?;
-                    let image: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::from_raw(
-                        self.output_width,
-                        self.output_height,
-                        raw.data
-                            .into_par_iter()
-                            .map(|v| v.convert::<f32>())
-                            .map(|v: f32| <Srgb as TransferFn>::from_linear(v))
-                            .map(|v| v.convert::<u8>())
-                            .collect(),
-                    )
-                    .unwrap();
-
-                    image.save(output_path(frame_number, current_seed))?;
+                if painting && frame_number > self.options.save_delay() {
+                    self.gpu
+                        .save_texture(buffer, output_path(frame_number, current_seed))?;
+                    if let Some(gif) = self.gif.as_mut() {
+                        let (width, height) = buffer.dimensions();
+                        gif.add_frame(&self.gpu.read_srgba8(buffer)?, width, height)?;
+                    }
                 }
 
                 Ok(FrameUpdates {
                     new_seed: None,
+                    toggle_pause: false,
                     wait: None,
                     should_quit: false,
                 })
@@ -277,3 +593,37 @@ impl<'a, F1: Fn() -> Frame + 'a, F2: Fn(usize, u64) -> PathBuf> Renderer<'a, F1,
         }
     }
 }
+
+/// Reverses the order of the rows of an image.
+fn flip_rows(pixels: &[u8], row_length: usize) -> Vec<u8> {
+    pixels
+        .chunks_exact(row_length)
+        .rev()
+        .flatten()
+        .copied()
+        .collect()
+}
+
+/// Returns the region of a window of the given size the painting is shown in.
+fn screen_viewport(
+    window_size: (u32, u32),
+    output_size: (u32, u32),
+    resize_behavior: ResizeBehavior,
+) -> Viewport {
+    let (window_width, window_height) = (f64::from(window_size.0), f64::from(window_size.1));
+    match resize_behavior {
+        ResizeBehavior::Stretch => Viewport::window(window_size),
+        ResizeBehavior::Letterbox => {
+            let scale = (window_width / f64::from(output_size.0))
+                .min(window_height / f64::from(output_size.1));
+            let width = (f64::from(output_size.0) * scale).round().max(1.);
+            let height = (f64::from(output_size.1) * scale).round().max(1.);
+            Viewport {
+                left: ((window_width - width) / 2.).floor(),
+                top: ((window_height - height) / 2.).floor(),
+                width,
+                height,
+            }
+        }
+    }
+}
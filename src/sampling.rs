@@ -0,0 +1,46 @@
+//! Samplers of common distributions.
+
+use rand::Rng;
+use rand_distr::{Exp1, Poisson, StandardNormal};
+
+/// Samples uniformly from [lo, hi).
+pub fn uniform(rng: &mut impl Rng, lo: f32, hi: f32) -> f32 {
+    lo + (hi - lo) * rng.gen::<f32>()
+}
+
+/// Samples a normal distribution.
+pub fn normal(rng: &mut impl Rng, mean: f32, std_dev: f32) -> f32 {
+    mean + std_dev * rng.sample::<f32, _>(StandardNormal)
+}
+
+/// Samples an exponential distribution.
+pub fn exponential(rng: &mut impl Rng, rate: f32) -> f32 {
+    rng.sample::<f32, _>(Exp1) / rate
+}
+
+/// Samples a Poisson distribution, which is always zero if `lambda` is not positive and finite.
+pub fn poisson(rng: &mut impl Rng, lambda: f64) -> u64 {
+    if !lambda.is_finite() {
+        return 0;
+    }
+    Poisson::new(lambda).map_or(0, |poisson| rng.sample(poisson))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn sample_means() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let n = 20_000;
+        let mean = |f: &mut dyn FnMut() -> f64| (0..n).map(|_| f()).sum::<f64>() / n as f64;
+
+        assert!((mean(&mut || f64::from(normal(&mut rng, 3., 2.))) - 3.).abs() < 0.1);
+        assert!((mean(&mut || f64::from(exponential(&mut rng, 2.))) - 0.5).abs() < 0.02);
+        assert!((mean(&mut || poisson(&mut rng, 4.) as f64) - 4.).abs() < 0.1);
+        assert!((mean(&mut || poisson(&mut rng, 100.) as f64) - 100.).abs() < 0.5);
+        assert_eq!(poisson(&mut rng, 0.), 0);
+    }
+}
@@ -1,19 +1,38 @@
 //! Fragment shader API.
 
 use crate::{
-    gpu::{Gpu, Shader},
-    uniforms::OwnedUniforms,
+    gpu::{Gpu, Shader, SharedProgram, UniformBuffer},
+    uniforms::{OwnedUniforms, UniformsBuilder},
     Result,
 };
+use failure::bail;
 use glium::Program;
 use glslwatch::GLSLTree;
-use std::{path::Path, rc::Rc};
+use std::{
+    cell::RefCell,
+    path::Path,
+    rc::{Rc, Weak},
+};
+
+pub mod builtins;
+mod diagnostics;
+
+pub use self::diagnostics::*;
 
 /// A dynamically reloaded GLSL program.
+///
+/// Programs are recompiled when their source files change, which is checked each frame and each
+/// time they are bound. Every shader bound from the program switches to the recompiled program,
+/// including those bound earlier and those set on pipeline passes. If recompilation fails the
+/// error is printed and the last working program stays in use.
+///
+/// Compilation happens on the render thread, because GL contexts belong to a single thread.
 pub struct ShaderProgram {
-    tree: GLSLTree,
     gpu: Gpu,
-    last_program: Option<Rc<Program>>,
+    /// The sources of the program until it first compiles, after which the GPU handle watches
+    /// them instead.
+    sources: Option<ProgramSources>,
+    program: Option<SharedProgram>,
     last_shader: Shader,
 }
 
@@ -31,12 +50,41 @@ impl ShaderProgram {
         glsl: impl AsRef<Path>,
         include_directories: &[S],
     ) -> Result<Self> {
-        Ok(Self {
+        Ok(Self::from_sources(
+            gpu,
+            ProgramSources {
+                fragment: GLSLTree::new(glsl, include_directories)?,
+                vertex: None,
+            },
+        ))
+    }
+
+    /// Creates a dynamically reloaded glsl program from the given vertex and fragment shader
+    /// paths. The program is reloaded when either changes.
+    ///
+    /// The vertex shader receives the same inputs as the default vertex shader.
+    pub fn from_files(
+        gpu: &Gpu,
+        vertex: impl AsRef<Path>,
+        fragment: impl AsRef<Path>,
+    ) -> Result<Self> {
+        let include_paths: [String; 0] = [];
+        Ok(Self::from_sources(
+            gpu,
+            ProgramSources {
+                fragment: GLSLTree::new(fragment, &include_paths)?,
+                vertex: Some(GLSLTree::new(vertex, &include_paths)?),
+            },
+        ))
+    }
+
+    fn from_sources(gpu: &Gpu, sources: ProgramSources) -> Self {
+        Self {
             gpu: gpu.clone(),
-            tree: GLSLTree::new(glsl, include_directories)?,
+            sources: Some(sources),
+            program: None,
             last_shader: gpu.default_shader(),
-            last_program: None,
-        })
+        }
     }
 
     /// Create a usable shader by binding uniform values.
@@ -51,23 +99,117 @@ impl ShaderProgram {
     }
 
     pub fn try_bind(&mut self, uniforms: impl OwnedUniforms + 'static) -> Result<Shader> {
-        let program = if self.tree.expired()? {
-            let mut new_tree = self.tree.clone().refresh()?;
-            std::mem::swap(&mut self.tree, &mut new_tree);
-            let src = self.tree.render();
-            let program = self.gpu.compile_glsl(src)?;
-            self.last_program = Some(program.clone());
-            program
-        } else if let Some(program) = self.last_program.as_ref() {
-            program.clone()
-        } else {
-            let src = self.tree.render();
-            let program = self.gpu.compile_glsl(src)?;
-            self.last_program = Some(program.clone());
-            program
+        let program = self.shared_program()?;
+        self.last_shader = self.gpu.build_shader_shared(program, uniforms);
+        Ok(self.last_shader.clone())
+    }
+
+    /// Begins building uniforms to bind, which are checked against those the current program
+    /// declares.
+    pub fn uniforms(&mut self) -> UniformsBuilder {
+        UniformsBuilder::new(
+            self.shared_program()
+                .map(|program| program.borrow().clone()),
+        )
+    }
+
+    /// Returns the current program, recompiling it if its sources have changed.
+    fn shared_program(&mut self) -> Result<SharedProgram> {
+        if let Some(program) = self.program.as_ref() {
+            self.gpu.reload_changed_programs();
+            return Ok(program.clone());
+        }
+
+        let sources = match self.sources.as_mut() {
+            Some(sources) => sources,
+            None => bail!("Shader program has neither sources nor a compiled program"),
         };
+        if sources.expired()? {
+            sources.refresh()?;
+        }
+        let program = Rc::new(RefCell::new(sources.compile(&self.gpu)?));
+        if let Some(sources) = self.sources.take() {
+            self.gpu.watch_program(WatchedProgram {
+                sources,
+                program: Rc::downgrade(&program),
+            });
+        }
+        self.program = Some(program.clone());
+        Ok(program)
+    }
+}
 
-        self.last_shader = self.gpu.build_shader(program, uniforms);
-        Ok(self.last_shader.clone())
+impl Shader {
+    /// Compiles a shader from the given vertex and fragment shader paths, which is recompiled
+    /// when either changes for as long as the shader or a copy of it is in use. See
+    /// `ShaderProgram`, which also binds uniforms.
+    ///
+    /// Fails if the shader does not compile at first.
+    pub fn watch(
+        gpu: &Gpu,
+        vertex: impl AsRef<Path>,
+        fragment: impl AsRef<Path>,
+    ) -> Result<Shader> {
+        let program = ShaderProgram::from_files(gpu, vertex, fragment)?.shared_program()?;
+        Ok(gpu.build_shader_shared(program, UniformBuffer::default()))
+    }
+}
+
+/// The GLSL sources of a program.
+pub(crate) struct ProgramSources {
+    fragment: GLSLTree,
+    vertex: Option<GLSLTree>,
+}
+
+impl ProgramSources {
+    fn expired(&self) -> Result<bool> {
+        let vertex_expired = match self.vertex.as_ref() {
+            Some(tree) => tree.expired()?,
+            None => false,
+        };
+        Ok(self.fragment.expired()? || vertex_expired)
+    }
+
+    fn refresh(&mut self) -> Result<()> {
+        self.fragment = self.fragment.clone().refresh()?;
+        if let Some(tree) = self.vertex.as_ref() {
+            self.vertex = Some(tree.clone().refresh()?);
+        }
+        Ok(())
+    }
+
+    fn compile(&self, gpu: &Gpu) -> Result<Rc<Program>> {
+        let fragment = self.fragment.render();
+        match self.vertex.as_ref() {
+            Some(vertex) => gpu.compile_glsl_with_vertex_shader(vertex.render(), fragment),
+            None => gpu.compile_glsl(fragment),
+        }
+    }
+}
+
+/// A program whose sources are polled for changes by `Gpu::reload_changed_programs()`.
+pub(crate) struct WatchedProgram {
+    sources: ProgramSources,
+    program: Weak<RefCell<Rc<Program>>>,
+}
+
+impl WatchedProgram {
+    /// Whether any shader still uses the program.
+    pub(crate) fn in_use(&self) -> bool {
+        self.program.strong_count() > 0
+    }
+
+    /// Recompiles the program if its sources have changed, and swaps it in for the shaders
+    /// using it.
+    pub(crate) fn reload_if_changed(&mut self, gpu: &Gpu) -> Result<()> {
+        if !self.sources.expired()? {
+            return Ok(());
+        }
+        self.sources.refresh()?;
+        let program = self.sources.compile(gpu)?;
+        if let Some(shared) = self.program.upgrade() {
+            *shared.borrow_mut() = program;
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,44 @@
+//! Precompiled shaders for common fills.
+//!
+//! These return the same `Shader` type as custom programs, so they can be set on a canvas with
+//! `Canvas::set_shader()` and swapped with any other shader.
+
+use crate::{gpu::GradientGeometry, Gpu, Shader, World, P2};
+use glium::texture::Texture2d;
+use palette::LinSrgba;
+use std::rc::Rc;
+
+/// Returns a shader which fills paths with a single color, ignoring the canvas color.
+pub fn solid_color(gpu: &Gpu, r: f32, g: f32, b: f32, a: f32) -> Shader {
+    gpu.solid_shader(LinSrgba::new(r, g, b, a))
+}
+
+/// Returns a shader which fills paths with a texture stretched over the whole painting.
+pub fn texture(gpu: &Gpu, texture: Rc<Texture2d>) -> Shader {
+    gpu.image_shader(texture)
+}
+
+/// Returns a shader for `Canvas::draw_instanced()`, which shades instances with the canvas color.
+pub fn instanced(gpu: &Gpu) -> Shader {
+    gpu.instanced_shader()
+}
+
+/// Returns a shader which fills paths with a linear gradient from `start` to `end`, in the
+/// painting's coordinate space. Stops are `(offset, color)` pairs with offsets in [0, 1].
+///
+/// Only the first `MAX_GRADIENT_STOPS` stops by offset are used.
+pub fn linear_gradient(
+    gpu: &Gpu,
+    world: &World,
+    start: P2,
+    end: P2,
+    stops: Vec<(f32, LinSrgba)>,
+) -> Shader {
+    gpu.gradient_shader(
+        GradientGeometry::Linear {
+            start: start * world.scale,
+            end: end * world.scale,
+        },
+        stops,
+    )
+}
@@ -0,0 +1,45 @@
+#version 400
+
+out vec4 frag;
+
+uniform sampler2DMS layer;
+uniform sampler2DMS backdrop;
+uniform float opacity;
+uniform int blend_mode;
+
+const int NORMAL = 0;
+const int MULTIPLY = 1;
+const int SCREEN = 2;
+const int OVERLAY = 3;
+const int ADD = 4;
+
+vec3 blend(vec3 src, vec3 dst) {
+  switch (blend_mode) {
+    case MULTIPLY:
+      return src * dst;
+    case SCREEN:
+      return src + dst - src * dst;
+    case OVERLAY:
+      return mix(2. * src * dst, 1. - 2. * (1. - src) * (1. - dst), step(0.5, dst));
+    default:
+      return src;
+  }
+}
+
+void main() {
+  ivec2 texel = ivec2(floor(gl_FragCoord.x), floor(gl_FragCoord.y));
+  // Both textures hold premultiplied colors.
+  vec4 src = texelFetch(layer, texel, gl_SampleID) * opacity;
+  vec4 dst = texelFetch(backdrop, texel, gl_SampleID);
+
+  if (blend_mode == ADD) {
+    frag = vec4(src.rgb + dst.rgb, min(src.a + dst.a, 1.));
+    return;
+  }
+
+  vec3 src_color = src.a > 0. ? src.rgb / src.a : vec3(0.);
+  vec3 dst_color = dst.a > 0. ? dst.rgb / dst.a : vec3(0.);
+  vec3 color = src.rgb * (1. - dst.a) + dst.rgb * (1. - src.a)
+      + src.a * dst.a * blend(src_color, dst_color);
+  frag = vec4(color, src.a + dst.a - src.a * dst.a);
+}
@@ -8,6 +8,7 @@ in vec2 vpos;
 in vec4 vcol;
 
 out vec4 v_color;
+out vec2 v_position;
 
 vec2 _project_coordinates() {
   vec2 tmp = vpos;
@@ -18,4 +19,5 @@ vec2 _project_coordinates() {
 void main() {
   gl_Position = vec4(_project_coordinates(), 0., 1.);
   v_color = vcol;
+  v_position = vpos;
 }
\ No newline at end of file
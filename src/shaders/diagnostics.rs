@@ -0,0 +1,225 @@
+//! Structured GLSL compilation errors.
+
+use glium::program::{ProgramCreationError, ShaderType};
+use std::fmt;
+
+/// A stage of a GLSL program.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Geometry,
+    TessellationControl,
+    TessellationEvaluation,
+    Fragment,
+    Compute,
+}
+
+impl From<ShaderType> for ShaderStage {
+    fn from(ty: ShaderType) -> Self {
+        match ty {
+            ShaderType::Vertex => ShaderStage::Vertex,
+            ShaderType::Geometry => ShaderStage::Geometry,
+            ShaderType::TesselationControl => ShaderStage::TessellationControl,
+            ShaderType::TesselationEvaluation => ShaderStage::TessellationEvaluation,
+            ShaderType::Fragment => ShaderStage::Fragment,
+            ShaderType::Compute => ShaderStage::Compute,
+        }
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ShaderStage::Vertex => "vertex",
+            ShaderStage::Geometry => "geometry",
+            ShaderStage::TessellationControl => "tessellation control",
+            ShaderStage::TessellationEvaluation => "tessellation evaluation",
+            ShaderStage::Fragment => "fragment",
+            ShaderStage::Compute => "compute",
+        })
+    }
+}
+
+/// A single message from the driver's compilation log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShaderDiagnostic {
+    pub stage: ShaderStage,
+    /// The one-based line of the source the message refers to.
+    pub line: u32,
+    /// The one-based column, if the driver reports one.
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+/// A GLSL stage which failed to compile.
+///
+/// Drivers format their logs differently; the NVIDIA, Mesa, AMD and Apple formats are parsed
+/// into diagnostics. If no line of the log could be parsed, it is displayed raw.
+#[derive(Debug, Clone)]
+pub struct ShaderCompileError {
+    pub stage: ShaderStage,
+    pub diagnostics: Vec<ShaderDiagnostic>,
+    /// The unparsed driver log.
+    pub log: String,
+    source: String,
+}
+
+impl ShaderCompileError {
+    /// Parses the driver log of a stage which failed to compile from the given source.
+    pub fn new(stage: ShaderStage, log: &str, source: &str) -> Self {
+        Self {
+            stage,
+            diagnostics: log
+                .lines()
+                .filter_map(parse_log_line)
+                .map(|(line, column, message)| ShaderDiagnostic {
+                    stage,
+                    line,
+                    column,
+                    message,
+                })
+                .collect(),
+            log: String::from(log),
+            source: String::from(source),
+        }
+    }
+
+    /// Converts a program creation error into a `ShaderCompileError` if a stage failed to
+    /// compile, or passes it through otherwise.
+    pub(crate) fn from_program_error(
+        error: ProgramCreationError,
+        vertex: &str,
+        fragment: &str,
+    ) -> failure::Error {
+        match error {
+            ProgramCreationError::CompilationError(log, ty) => {
+                let stage = ShaderStage::from(ty);
+                let source = match stage {
+                    ShaderStage::Vertex => vertex,
+                    _ => fragment,
+                };
+                ShaderCompileError::new(stage, &log, source).into()
+            }
+            error => error.into(),
+        }
+    }
+}
+
+impl fmt::Display for ShaderCompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to compile {} shader", self.stage)?;
+        if self.diagnostics.is_empty() {
+            return write!(f, ":\n{}", self.log.trim_end());
+        }
+
+        for diagnostic in &self.diagnostics {
+            write!(f, "\n{}", diagnostic.line)?;
+            if let Some(column) = diagnostic.column {
+                write!(f, ":{}", column)?;
+            }
+            write!(f, ": {}", diagnostic.message)?;
+
+            let index = (diagnostic.line as usize).checked_sub(1);
+            if let Some(source_line) = index.and_then(|i| self.source.lines().nth(i)) {
+                write!(f, "\n{:>5} | {}", diagnostic.line, source_line)?;
+                if let Some(column) = diagnostic.column {
+                    let indent = (column as usize).saturating_sub(1);
+                    write!(f, "\n      | {:>width$}", "^", width = indent + 1)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ShaderCompileError {}
+
+/// Parses one line of a driver log into a line, column and message.
+///
+/// The recognized formats are:
+///
+///   * Mesa: `0:12(5): error: message`
+///   * NVIDIA: `0(12) : error C0000: message`
+///   * AMD and Apple: `ERROR: 0:12: message`
+fn parse_log_line(log_line: &str) -> Option<(u32, Option<u32>, String)> {
+    let log_line = log_line.trim();
+    let (severity, rest) = ["ERROR:", "WARNING:"]
+        .iter()
+        .find(|prefix| log_line.starts_with(*prefix))
+        .map(|prefix| {
+            (
+                Some(prefix.trim_end_matches(':').to_lowercase()),
+                log_line[prefix.len()..].trim_start(),
+            )
+        })
+        .unwrap_or((None, log_line));
+
+    // Every format leads with the index of the source string, which is always 0 here.
+    let (_, rest) = split_number(rest)?;
+    let (line, column, rest) = if let Some(rest) = rest.strip_prefix('(') {
+        let (line, rest) = split_number(rest)?;
+        (line, None, rest.strip_prefix(')')?)
+    } else {
+        let (line, rest) = split_number(rest.strip_prefix(':')?)?;
+        match rest.strip_prefix('(') {
+            Some(rest) => {
+                let (column, rest) = split_number(rest)?;
+                (line, Some(column), rest.strip_prefix(')')?)
+            }
+            None => (line, None, rest),
+        }
+    };
+
+    let message = rest.trim_start().strip_prefix(':')?.trim();
+    let message = match severity {
+        Some(severity) => format!("{}: {}", severity, message),
+        None => String::from(message),
+    };
+
+    Some((line, column, message))
+}
+
+fn split_number(s: &str) -> Option<(u32, &str)> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    Some((s[..end].parse().ok()?, &s[end..]))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_driver_formats() {
+        assert_eq!(
+            parse_log_line("0:12(5): error: `foo' undeclared"),
+            Some((12, Some(5), String::from("error: `foo' undeclared")))
+        );
+        assert_eq!(
+            parse_log_line("0(3) : error C0000: syntax error, unexpected '}'"),
+            Some((
+                3,
+                None,
+                String::from("error C0000: syntax error, unexpected '}'")
+            ))
+        );
+        assert_eq!(
+            parse_log_line("ERROR: 0:7: 'x' : undeclared identifier"),
+            Some((7, None, String::from("error: 'x' : undeclared identifier")))
+        );
+        assert_eq!(parse_log_line("1 error generated."), None);
+    }
+
+    #[test]
+    fn display_annotates_source() {
+        let error = ShaderCompileError::new(
+            ShaderStage::Fragment,
+            "0:2(3): error: `foo' undeclared\n",
+            "#version 400\nx = foo;\n",
+        );
+        assert_eq!(
+            error.to_string(),
+            "Failed to compile fragment shader\n2:3: error: `foo' undeclared\n    2 | x = foo;\n      |   ^"
+        );
+    }
+}
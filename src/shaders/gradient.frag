@@ -0,0 +1,59 @@
+#version 400
+
+const int MAX_STOPS = 16;
+
+const int LINEAR = 0;
+const int RADIAL = 1;
+
+uniform int gradient_kind;
+// The start point of linear gradients, or the center of radial gradients.
+uniform vec2 gradient_start;
+uniform vec2 gradient_end;
+uniform float gradient_radius;
+uniform int stop_count;
+uniform float stop_offsets[MAX_STOPS];
+uniform vec4 stop_colors[MAX_STOPS];
+
+in vec2 v_position;
+
+out vec4 frag;
+
+vec4 sample_stops(float t) {
+  t = clamp(t, 0., 1.);
+  if (t <= stop_offsets[0]) {
+    return stop_colors[0];
+  }
+
+  for (int i = 1; i < stop_count; ++i) {
+    if (t <= stop_offsets[i]) {
+      float span = stop_offsets[i] - stop_offsets[i - 1];
+      float f = span > 0. ? (t - stop_offsets[i - 1]) / span : 1.;
+      return mix(stop_colors[i - 1], stop_colors[i], f);
+    }
+  }
+
+  return stop_colors[stop_count - 1];
+}
+
+float linear_position() {
+  vec2 axis = gradient_end - gradient_start;
+  float axis_length_squared = dot(axis, axis);
+  if (axis_length_squared == 0.) {
+    return 0.;
+  }
+
+  return dot(v_position - gradient_start, axis) / axis_length_squared;
+}
+
+float radial_position() {
+  if (gradient_radius <= 0.) {
+    return 0.;
+  }
+
+  return distance(v_position, gradient_start) / gradient_radius;
+}
+
+void main() {
+  float t = gradient_kind == RADIAL ? radial_position() : linear_position();
+  frag = clamp(sample_stops(t), 0., 1.);
+}
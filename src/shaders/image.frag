@@ -0,0 +1,14 @@
+#version 400
+
+uniform sampler2D image;
+uniform float _valora_width;
+uniform float _valora_height;
+
+in vec4 v_color;
+in vec2 v_position;
+
+out vec4 frag;
+
+void main() {
+  frag = texture(image, v_position / vec2(_valora_width, _valora_height));
+}
@@ -0,0 +1,25 @@
+#version 400
+
+uniform float _valora_width;
+uniform float _valora_height;
+uniform float _valora_height_sign;
+
+in vec2 vpos;
+in vec4 vcol;
+in mat3 a_instance_transform;
+
+out vec4 v_color;
+out vec2 v_position;
+
+vec2 _project_coordinates(vec2 position) {
+  vec2 tmp = position;
+  tmp.y = _valora_height - tmp.y;
+  return vec2(tmp.x / _valora_width * 2. - 1., _valora_height_sign * (tmp.y / _valora_height * 2. - 1.));
+}
+
+void main() {
+  vec2 position = (a_instance_transform * vec3(vpos, 1.)).xy;
+  gl_Position = vec4(_project_coordinates(position), 0., 1.);
+  v_color = vcol;
+  v_position = position;
+}
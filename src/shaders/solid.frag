@@ -0,0 +1,9 @@
+#version 400
+
+uniform vec4 solid_color;
+
+out vec4 frag;
+
+void main() {
+  frag = clamp(solid_color, 0., 1.);
+}
@@ -0,0 +1,18 @@
+#version 400
+
+uniform sampler2D coverage;
+// The position in output space of the top left corner of the text bitmap, and the vectors along
+// its width and height.
+uniform vec2 text_origin;
+uniform vec2 text_x_axis;
+uniform vec2 text_y_axis;
+
+in vec4 v_color;
+in vec2 v_position;
+
+out vec4 frag;
+
+void main() {
+  vec2 uv = inverse(mat2(text_x_axis, text_y_axis)) * (v_position - text_origin);
+  frag = vec4(v_color.rgb, v_color.a * texture(coverage, uv).r);
+}
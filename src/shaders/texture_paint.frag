@@ -0,0 +1,13 @@
+#version 400
+
+uniform sampler2D image;
+uniform mat3 image_transform;
+
+in vec4 v_color;
+in vec2 v_position;
+
+out vec4 frag;
+
+void main() {
+  frag = texture(image, (image_transform * vec3(v_position, 1.)).xy);
+}
@@ -0,0 +1,180 @@
+//! Stipple sampling of images, for halftone paintings.
+
+use crate::P2;
+use image::GrayImage;
+use rand::{rngs::StdRng, Rng};
+use spade::{DelaunayTriangulation, Point2, Triangulation};
+
+/// Samples points from an image with probability proportional to the darkness of each pixel,
+/// with Vose's alias method so each sample takes constant time.
+///
+/// Points are in [0, 1] x [0, 1] with y pointing up as on a canvas, so the top row of the image
+/// is at the top of the square. Each point is placed uniformly within the pixel it samples. A
+/// white image has no darkness to sample, so it gives no points.
+pub fn stipple_image(image: &GrayImage, n_points: usize, rng: &mut StdRng) -> Vec<P2> {
+    let weights: Vec<f64> = image
+        .pixels()
+        .map(|pixel| 1. - f64::from(pixel[0]) / 255.)
+        .collect();
+    let table = match AliasTable::new(&weights) {
+        Some(table) => table,
+        None => return vec![],
+    };
+
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    (0..n_points)
+        .map(|_| {
+            let i = table.sample(rng);
+            let (col, row) = (i % width, i / width);
+            P2::new(
+                (col as f32 + rng.gen::<f32>()) / width as f32,
+                1. - (row as f32 + rng.gen::<f32>()) / height as f32,
+            )
+        })
+        .collect()
+}
+
+/// Samples points as `stipple_image()` does, then spreads them more evenly with iterations of
+/// Lloyd relaxation weighted by darkness, moving each point to the darkness weighted centroid of
+/// the pixels nearest to it.
+///
+/// Each iteration takes time in proportion to the number of pixels.
+pub fn stipple_image_relaxed(
+    image: &GrayImage,
+    n_points: usize,
+    relax_iterations: u32,
+    rng: &mut StdRng,
+) -> Vec<P2> {
+    let mut points = stipple_image(image, n_points, rng);
+    for _ in 0..relax_iterations {
+        points = relax(image, &points);
+    }
+    points
+}
+
+/// Moves each point to the darkness weighted centroid of the pixel centers nearest to it, or
+/// leaves it where it is if those pixels are white.
+fn relax(image: &GrayImage, points: &[P2]) -> Vec<P2> {
+    let mut triangulation = DelaunayTriangulation::<Point2<f64>>::new();
+    // The index in `points` of each vertex of the triangulation. Repeated points share a vertex,
+    // and stay where they are.
+    let mut indices = vec![];
+    for (i, p) in points.iter().enumerate() {
+        if let Ok(handle) = triangulation.insert(Point2::new(f64::from(p.x), f64::from(p.y))) {
+            if handle.index() == indices.len() {
+                indices.push(i);
+            }
+        }
+    }
+
+    let (width, height) = (f64::from(image.width()), f64::from(image.height()));
+    // The weighted sums of the x and y of pixels nearest to each vertex, and the weight.
+    let mut sums = vec![(0., 0., 0.); indices.len()];
+    for (col, row, pixel) in image.enumerate_pixels() {
+        let weight = 1. - f64::from(pixel[0]) / 255.;
+        if weight == 0. {
+            continue;
+        }
+        let center = Point2::new(
+            (f64::from(col) + 0.5) / width,
+            1. - (f64::from(row) + 0.5) / height,
+        );
+        if let Some(vertex) = triangulation.nearest_neighbor(center) {
+            let sum = &mut sums[vertex.fix().index()];
+            sum.0 += center.x * weight;
+            sum.1 += center.y * weight;
+            sum.2 += weight;
+        }
+    }
+
+    let mut relaxed = points.to_vec();
+    for (i, (x, y, weight)) in indices.into_iter().zip(sums) {
+        if weight > 0. {
+            relaxed[i] = P2::new((x / weight) as f32, (y / weight) as f32);
+        }
+    }
+    relaxed
+}
+
+/// A table for sampling indices in proportion to their weights in constant time.
+struct AliasTable {
+    /// The probability of keeping each index when it is drawn uniformly.
+    keep: Vec<f64>,
+    /// The index to sample instead of each index when it is not kept.
+    alias: Vec<usize>,
+}
+
+impl AliasTable {
+    /// Builds a table with Vose's method, or returns `None` if no weight is positive.
+    fn new(weights: &[f64]) -> Option<Self> {
+        let total: f64 = weights.iter().sum();
+        if total <= 0. || !total.is_finite() {
+            return None;
+        }
+
+        let n = weights.len();
+        let mut keep: Vec<f64> = weights.iter().map(|w| w * n as f64 / total).collect();
+        let mut alias: Vec<usize> = (0..n).collect();
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) = (0..n).partition(|i| keep[*i] < 1.);
+        while let (Some(s), Some(&l)) = (small.pop(), large.last()) {
+            alias[s] = l;
+            keep[l] -= 1. - keep[s];
+            if keep[l] < 1. {
+                large.pop();
+                small.push(l);
+            }
+        }
+        // What is left is kept always, but for rounding error.
+        for i in small.into_iter().chain(large) {
+            keep[i] = 1.;
+        }
+
+        Some(Self { keep, alias })
+    }
+
+    fn sample(&self, rng: &mut impl Rng) -> usize {
+        let i = rng.gen_range(0, self.keep.len());
+        if rng.gen::<f64>() < self.keep[i] {
+            i
+        } else {
+            self.alias[i]
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::Luma;
+    use rand::SeedableRng;
+
+    #[test]
+    fn alias_table_samples_in_proportion() {
+        let table = AliasTable::new(&[1., 0., 3.]).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut counts = [0; 3];
+        for _ in 0..40_000 {
+            counts[table.sample(&mut rng)] += 1;
+        }
+
+        assert_eq!(counts[1], 0);
+        assert!((counts[2] as f32 / counts[0] as f32 - 3.).abs() < 0.1);
+        assert!(AliasTable::new(&[0., 0.]).is_none());
+    }
+
+    #[test]
+    fn stipples_dark_pixels() {
+        // Black on the top left and bottom right, white elsewhere.
+        let image = GrayImage::from_fn(4, 4, |x, y| {
+            Luma([if (x < 2) == (y < 2) { 0 } else { 255 }])
+        });
+        let mut rng = StdRng::seed_from_u64(1);
+        let points = stipple_image_relaxed(&image, 200, 2, &mut rng);
+
+        assert_eq!(points.len(), 200);
+        assert!(points
+            .iter()
+            .all(|p| (p.x < 0.5 && p.y > 0.5) || (p.x > 0.5 && p.y < 0.5)));
+        assert!(stipple_image(&GrayImage::from_pixel(2, 2, Luma([255])), 10, &mut rng).is_empty());
+    }
+}
@@ -0,0 +1,211 @@
+//! Vector export of paintings as SVG.
+
+use crate::{
+    canvas::{BlendMode, Canvas},
+    gpu::{Drawable, Element},
+    raster::Method,
+    Result, Transform, World,
+};
+use lyon_path::PathEvent;
+use lyon_tessellation::{LineCap, LineJoin};
+use palette::{LinSrgba, Srgb};
+use std::{fmt::Write, path::Path};
+
+/// Collects what is painted on canvases into an SVG 1.1 document, for plotting, cutting and
+/// printing.
+///
+/// Filled paths become `<path>` elements with a fill, and stroked paths `<path>` elements with a
+/// stroke, in the color they were painted with. Shaders have no SVG equivalent and are not
+/// exported, so paths painted with custom shaders or gradients appear in their flat color. Layers
+/// become groups with their opacity; blend modes other than `Normal`, masks and clip rects are not
+/// exported.
+///
+/// SVG has a single `stroke-linecap` for both ends of a stroke, so strokes are exported with the
+/// cap of their end; a different cap at the start is lost.
+pub struct SvgExporter {
+    width: f32,
+    height: f32,
+    body: String,
+}
+
+impl SvgExporter {
+    /// Creates an exporter for paintings of the world. Coordinates are exported in output space,
+    /// so the document is the size in pixels of the rendered painting.
+    pub fn new(world: &World) -> Self {
+        Self {
+            width: world.width * world.scale,
+            height: world.height * world.scale,
+            body: String::new(),
+        }
+    }
+
+    /// Adds everything painted on the canvas so far, above anything added before. Layers which
+    /// are still open are not included.
+    pub fn add(&mut self, canvas: &Canvas) {
+        for drawable in canvas.drawables() {
+            self.add_drawable(drawable);
+        }
+    }
+
+    /// Returns the SVG document.
+    pub fn to_svg(&self) -> String {
+        format!(
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\" ",
+                "width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+                "<g transform=\"matrix(1 0 0 -1 0 {h})\">\n",
+                "{body}",
+                "</g>\n",
+                "</svg>\n"
+            ),
+            w = self.width,
+            h = self.height,
+            body = self.body,
+        )
+    }
+
+    /// Writes the SVG document to a file.
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, self.to_svg())?;
+        Ok(())
+    }
+
+    fn add_drawable(&mut self, drawable: &Drawable) {
+        match drawable {
+            Drawable::Element(element) => self.add_element(element, None),
+            Drawable::Layer(layer) => {
+                if layer.blend == BlendMode::Normal && layer.opacity >= 1. {
+                    self.body.push_str("<g>\n");
+                } else {
+                    writeln!(self.body, "<g opacity=\"{}\">", layer.opacity.clamp(0., 1.))
+                        .expect("writing to a string");
+                }
+                for drawable in &layer.drawables {
+                    self.add_drawable(drawable);
+                }
+                self.body.push_str("</g>\n");
+            }
+            Drawable::Instanced(instanced) => {
+                for transform in &instanced.transforms {
+                    self.add_element(&instanced.element, Some(transform));
+                }
+            }
+        }
+    }
+
+    fn add_element(&mut self, element: &Element, transform: Option<&Transform>) {
+        let data = path_data(element.path.iter());
+        if data.is_empty() {
+            return;
+        }
+
+        let (color, opacity) = svg_color(element.color);
+        let paint = match element.raster_method {
+            Method::Fill => format!(
+                "fill=\"{}\" fill-opacity=\"{}\" fill-rule=\"evenodd\" stroke=\"none\"",
+                color, opacity
+            ),
+            Method::Stroke {
+                width,
+                end_cap,
+                join,
+                miter_limit,
+                ..
+            } => format!(
+                concat!(
+                    "fill=\"none\" stroke=\"{}\" stroke-opacity=\"{}\" stroke-width=\"{}\" ",
+                    "stroke-linecap=\"{}\" stroke-linejoin=\"{}\" stroke-miterlimit=\"{}\""
+                ),
+                color,
+                opacity,
+                width,
+                match end_cap {
+                    LineCap::Butt => "butt",
+                    LineCap::Square => "square",
+                    LineCap::Round => "round",
+                },
+                match join {
+                    LineJoin::Round => "round",
+                    LineJoin::Bevel => "bevel",
+                    LineJoin::Miter | LineJoin::MiterClip => "miter",
+                },
+                miter_limit
+            ),
+        };
+        let transform = transform
+            .map(|t| {
+                format!(
+                    " transform=\"matrix({} {} {} {} {} {})\"",
+                    t.m11, t.m12, t.m21, t.m22, t.m31, t.m32
+                )
+            })
+            .unwrap_or_default();
+
+        writeln!(self.body, "<path d=\"{}\" {}{}/>", data, paint, transform)
+            .expect("writing to a string");
+    }
+}
+
+/// Formats path events as SVG path data.
+fn path_data(events: impl Iterator<Item = PathEvent>) -> String {
+    let mut data = String::new();
+    for event in events {
+        let result = match event {
+            PathEvent::Begin { at } => write!(data, "M{} {} ", at.x, at.y),
+            PathEvent::Line { to, .. } => write!(data, "L{} {} ", to.x, to.y),
+            PathEvent::Quadratic { ctrl, to, .. } => {
+                write!(data, "Q{} {} {} {} ", ctrl.x, ctrl.y, to.x, to.y)
+            }
+            PathEvent::Cubic {
+                ctrl1, ctrl2, to, ..
+            } => write!(
+                data,
+                "C{} {} {} {} {} {} ",
+                ctrl1.x, ctrl1.y, ctrl2.x, ctrl2.y, to.x, to.y
+            ),
+            PathEvent::End { close: true, .. } => write!(data, "Z "),
+            PathEvent::End { .. } => Ok(()),
+        };
+        result.expect("writing to a string");
+    }
+    data.trim_end().to_string()
+}
+
+/// Returns the sRGB hex color and opacity of a linear color.
+fn svg_color(color: LinSrgba) -> (String, f32) {
+    let srgb = Srgb::from_linear(color.color);
+    let byte = |c: f32| (c.clamp(0., 1.) * 255.).round() as u8;
+    (
+        format!(
+            "#{:02x}{:02x}{:02x}",
+            byte(srgb.red),
+            byte(srgb.green),
+            byte(srgb.blue)
+        ),
+        color.alpha.clamp(0., 1.),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{FlatIterPath, P2};
+    use palette::LinSrgb;
+
+    #[test]
+    fn path_data_closes_subpaths() {
+        let square = FlatIterPath::new(
+            vec![P2::new(0., 0.), P2::new(1., 0.), P2::new(1., 1.)].into_iter(),
+            /*closed=*/ true,
+        );
+        assert_eq!(path_data(square), "M0 0 L1 0 L1 1 Z");
+    }
+
+    #[test]
+    fn colors_are_srgb_encoded() {
+        let (color, opacity) = svg_color(LinSrgba::from(LinSrgb::new(1., 0.2158605, 0.)));
+        assert_eq!(color, "#ff8000");
+        assert_eq!(opacity, 1.);
+    }
+}
@@ -0,0 +1,180 @@
+//! Snapshot tests, which check that painting under a fixed seed stays the same.
+
+use crate::{run_fn, Artist, Canvas, Context, Gpu, Options, Result, World};
+use failure::{bail, format_err};
+use image::{ImageBuffer, Rgba, RgbaImage};
+use rand::{random, rngs::StdRng};
+use std::path::{Path, PathBuf};
+
+/// The environment variable which, when set to `1`, makes snapshot tests save their renders as
+/// the new snapshots instead of comparing against the old ones.
+pub const UPDATE_SNAPSHOTS_VAR: &str = "UPDATE_SNAPSHOTS";
+
+/// Renders paintings headlessly and compares them pixel for pixel against snapshots saved by
+/// earlier runs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SnapshotTester {
+    /// Whether to save renders as the new snapshots even where snapshots already exist.
+    pub update: bool,
+}
+
+impl Default for SnapshotTester {
+    fn default() -> Self {
+        Self {
+            update: std::env::var(UPDATE_SNAPSHOTS_VAR).is_ok_and(|v| v == "1"),
+        }
+    }
+}
+
+impl SnapshotTester {
+    /// Creates a tester which updates snapshots if `UPDATE_SNAPSHOTS=1` is set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Renders the first frame of an artist and compares it against the snapshot at the path.
+    /// See `render_fn_and_compare()`.
+    pub fn render_and_compare<A: Artist>(
+        &self,
+        options: Options,
+        snapshot_path: impl AsRef<Path>,
+    ) -> Result<()> {
+        self.render_fn_and_compare(options, snapshot_path, |gpu, world, rng| {
+            let mut artist = A::setup(gpu, world, rng)?;
+            Ok(move |ctx: Context, canvas: &mut Canvas| artist.paint(ctx, canvas))
+        })
+    }
+
+    /// Renders the first frame of an artist defined by raw functions and compares it against
+    /// the snapshot at the path.
+    ///
+    /// The artist is run with the options as given, except that only one frame is painted and
+    /// nothing is saved besides the snapshot. If there is no snapshot yet, or the tester
+    /// updates snapshots, the render is saved as the snapshot. Otherwise this fails unless every
+    /// pixel matches, after saving an image of the differences beside the snapshot. See
+    /// `diff_path()`.
+    pub fn render_fn_and_compare<F>(
+        &self,
+        options: Options,
+        snapshot_path: impl AsRef<Path>,
+        f: impl Fn(Gpu, World, &mut StdRng) -> Result<F>,
+    ) -> Result<()>
+    where
+        F: FnMut(Context, &mut Canvas),
+    {
+        let snapshot_path = snapshot_path.as_ref();
+        let actual = render_first_frame(options, f)?;
+
+        if self.update || !snapshot_path.exists() {
+            if let Some(parent) = snapshot_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            actual.save(snapshot_path)?;
+            return Ok(());
+        }
+
+        let expected = image::open(snapshot_path)?.to_rgba();
+        if expected.dimensions() != actual.dimensions() {
+            bail!(
+                "Render is {:?} but snapshot {} is {:?}",
+                actual.dimensions(),
+                snapshot_path.display(),
+                expected.dimensions()
+            );
+        }
+
+        let (mismatches, diff) = diff_images(&expected, &actual);
+        if mismatches > 0 {
+            let diff_path = diff_path(snapshot_path);
+            diff.save(&diff_path)?;
+            bail!(
+                "{} pixels differ from snapshot {}; see {}",
+                mismatches,
+                snapshot_path.display(),
+                diff_path.display()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the path a diff image is saved to when a render differs from the snapshot at the
+/// path: beside it, with `.diff` before the extension.
+pub fn diff_path(snapshot_path: &Path) -> PathBuf {
+    let stem = snapshot_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    snapshot_path.with_file_name(format!("{}.diff.png", stem))
+}
+
+/// Renders the first frame into a temporary directory and loads it.
+fn render_first_frame<F>(
+    options: Options,
+    f: impl Fn(Gpu, World, &mut StdRng) -> Result<F>,
+) -> Result<RgbaImage>
+where
+    F: FnMut(Context, &mut Canvas),
+{
+    let dir = std::env::temp_dir().join(format!("valora-snapshot-{:016x}", random::<u64>()));
+    let rendered = run_fn(
+        Options {
+            world: World {
+                frames: Some(1),
+                ..options.world
+            },
+            output: Some(dir.clone()),
+            brainstorm: false,
+            gif: None,
+            ..options
+        },
+        f,
+    )
+    .and_then(|()| {
+        let frame = std::fs::read_dir(&dir)?
+            .next()
+            .ok_or_else(|| format_err!("No frame was rendered"))??;
+        Ok(image::open(frame.path())?.to_rgba())
+    });
+    let _ = std::fs::remove_dir_all(&dir);
+    rendered
+}
+
+/// Counts the pixels which differ between two images of the same size, and returns an image
+/// showing the differences in red over a faded copy of the expected image.
+fn diff_images(expected: &RgbaImage, actual: &RgbaImage) -> (usize, RgbaImage) {
+    let mut mismatches = 0;
+    let diff = ImageBuffer::from_fn(expected.width(), expected.height(), |x, y| {
+        let (e, a) = (expected.get_pixel(x, y), actual.get_pixel(x, y));
+        if e == a {
+            let fade = |c: u8| 191 + c / 4;
+            Rgba([fade(e[0]), fade(e[1]), fade(e[2]), 255])
+        } else {
+            mismatches += 1;
+            Rgba([255, 0, 0, 255])
+        }
+    });
+    (mismatches, diff)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn diffs_mark_mismatched_pixels() {
+        let expected = ImageBuffer::from_pixel(2, 2, Rgba([0, 0, 0, 255]));
+        let mut actual = expected.clone();
+        actual.put_pixel(1, 0, Rgba([0, 0, 1, 255]));
+        let (mismatches, diff) = diff_images(&expected, &actual);
+
+        assert_eq!(mismatches, 1);
+        assert_eq!(*diff.get_pixel(1, 0), Rgba([255, 0, 0, 255]));
+        assert_eq!(*diff.get_pixel(0, 0), Rgba([191, 191, 191, 255]));
+        assert_eq!(
+            diff_path(Path::new("snapshots/rings.png")),
+            Path::new("snapshots/rings.diff.png")
+        );
+    }
+}
@@ -0,0 +1,72 @@
+//! Fonts for painting text.
+
+use crate::Result;
+use rusttype::{point, Scale};
+use std::path::Path;
+
+/// A TrueType or OpenType font, for painting text with `Canvas::draw_text()`.
+#[derive(Clone)]
+pub struct Font {
+    font: rusttype::Font<'static>,
+}
+
+/// A line of text rasterized into a coverage bitmap.
+pub(crate) struct Coverage {
+    /// One coverage value per pixel, in rows from the top of the bitmap.
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    /// The offset in pixels of the bitmap's top left corner from the start of the baseline,
+    /// with y pointing down.
+    pub left: i32,
+    pub top: i32,
+}
+
+impl Font {
+    /// Loads a font from a font file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    /// Loads a font from the contents of a font file.
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Result<Self> {
+        Ok(Self {
+            font: rusttype::Font::from_bytes(bytes.into())?,
+        })
+    }
+
+    /// Rasterizes a line of text with the given line height in pixels. Returns `None` if no
+    /// glyph in the text covers any pixels.
+    pub(crate) fn rasterize(&self, text: &str, size: f32) -> Option<Coverage> {
+        let glyphs: Vec<_> = self
+            .font
+            .layout(text, Scale::uniform(size), point(0., 0.))
+            .filter_map(|glyph| glyph.pixel_bounding_box().map(|bounds| (glyph, bounds)))
+            .collect();
+
+        let left = glyphs.iter().map(|(_, bounds)| bounds.min.x).min()?;
+        let top = glyphs.iter().map(|(_, bounds)| bounds.min.y).min()?;
+        let right = glyphs.iter().map(|(_, bounds)| bounds.max.x).max()?;
+        let bottom = glyphs.iter().map(|(_, bounds)| bounds.max.y).max()?;
+        let (width, height) = ((right - left) as u32, (bottom - top) as u32);
+
+        let mut data = vec![0u8; (width * height) as usize];
+        for (glyph, bounds) in &glyphs {
+            glyph.draw(|x, y, coverage| {
+                let x = (bounds.min.x - left) as u32 + x;
+                let y = (bounds.min.y - top) as u32 + y;
+                let pixel = &mut data[(y * width + x) as usize];
+                // Glyphs may overlap, as with kerned pairs.
+                *pixel = pixel.saturating_add((coverage * 255.).round() as u8);
+            });
+        }
+
+        Some(Coverage {
+            data,
+            width,
+            height,
+            left,
+            top,
+        })
+    }
+}
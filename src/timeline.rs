@@ -0,0 +1,101 @@
+//! Keyframe timelines for animating values by frame.
+
+use crate::easing::Easing;
+
+/// Values which can be interpolated linearly.
+pub trait Lerp {
+    /// Interpolates from `self` at `t = 0` to `other` at `t = 1`.
+    fn lerp(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl<const N: usize> Lerp for [f32; N] {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        let mut result = *self;
+        for (r, o) in result.iter_mut().zip(other) {
+            *r = r.lerp(o, t);
+        }
+        result
+    }
+}
+
+/// A value animated by keyframes at frame numbers.
+///
+/// Between two keyframes the value is interpolated with the easing of the later keyframe. Before
+/// the first keyframe and after the last the value holds still.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timeline<T> {
+    /// Sorted by frame, with at most one keyframe per frame.
+    keyframes: Vec<(usize, T, Easing)>,
+}
+
+impl<T: Lerp + Clone> Timeline<T> {
+    /// Creates a timeline with one keyframe.
+    pub fn new(frame: usize, value: T) -> Self {
+        Self {
+            keyframes: vec![(frame, value, Easing::Linear)],
+        }
+    }
+
+    /// Adds a keyframe interpolated linearly from the one before it, replacing any at the same
+    /// frame.
+    pub fn key(self, frame: usize, value: T) -> Self {
+        self.key_eased(frame, value, Easing::Linear)
+    }
+
+    /// Adds a keyframe interpolated with the easing from the one before it, replacing any at the
+    /// same frame.
+    pub fn key_eased(mut self, frame: usize, value: T, easing: Easing) -> Self {
+        match self.keyframes.binary_search_by_key(&frame, |(f, ..)| *f) {
+            Ok(i) => self.keyframes[i] = (frame, value, easing),
+            Err(i) => self.keyframes.insert(i, (frame, value, easing)),
+        }
+        self
+    }
+
+    /// The keyframes, sorted by frame.
+    pub fn keyframes(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.keyframes
+            .iter()
+            .map(|(frame, value, _)| (*frame, value))
+    }
+
+    /// Returns the value at the frame.
+    pub fn sample(&self, frame: usize) -> T {
+        let next = self.keyframes.partition_point(|(f, ..)| *f <= frame);
+        if next == 0 {
+            return self.keyframes[0].1.clone();
+        }
+        let (start, from, _) = &self.keyframes[next - 1];
+        match self.keyframes.get(next) {
+            Some((end, to, easing)) => {
+                let t = (frame - start) as f32 / (end - start) as f32;
+                from.lerp(to, easing.apply(t))
+            }
+            None => from.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn samples_between_keyframes() {
+        let timeline =
+            Timeline::new(10, [0., 4.])
+                .key(0, [2., 2.])
+                .key_eased(20, [1., 1.], Easing::InQuad);
+
+        assert_eq!(timeline.sample(0), [2., 2.]);
+        assert_eq!(timeline.sample(5), [1., 3.]);
+        assert_eq!(timeline.sample(15), [0.25, 3.25]);
+        assert_eq!(timeline.sample(30), [1., 1.]);
+    }
+}
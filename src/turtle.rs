@@ -0,0 +1,280 @@
+//! Turtle graphics and L-systems.
+
+use crate::{P2, V2};
+use lyon_path::PathEvent;
+use std::collections::HashMap;
+
+/// A Lindenmayer system: an axiom string and rules rewriting characters into strings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LSystem {
+    pub axiom: String,
+    /// Characters without a rule are kept as they are.
+    pub rules: HashMap<char, String>,
+}
+
+impl LSystem {
+    /// Rewrites the axiom `n` times, applying the rules to every character at once each time.
+    pub fn expand(&self, n: u32) -> String {
+        (0..n).fold(self.axiom.clone(), |s, _| {
+            let mut next = String::with_capacity(s.len());
+            for c in s.chars() {
+                match self.rules.get(&c) {
+                    Some(replacement) => next.push_str(replacement),
+                    None => next.push(c),
+                }
+            }
+            next
+        })
+    }
+}
+
+/// How `turtle_interpret()` moves the turtle.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TurtleCfg {
+    /// Where the turtle starts.
+    pub start: P2,
+    /// The distance the turtle moves for each step.
+    pub step_length: f32,
+    /// The degrees the turtle turns for each `+` or `-`.
+    pub angle_deg: f32,
+    /// The direction the turtle starts facing, in degrees counter-clockwise from the positive x
+    /// axis.
+    pub initial_angle_deg: f32,
+}
+
+/// Interprets a string as turtle commands, returning the path the turtle draws as open subpaths.
+///
+/// The commands are:
+///
+///   * `F` and `G`: move forward one step, drawing.
+///   * `f`: move forward one step without drawing.
+///   * `+` and `-`: turn left (counter-clockwise) and right.
+///   * `|`: turn around.
+///   * `[` and `]`: push and pop the position and heading of the turtle.
+///
+/// Other characters are ignored, so they can stand for rules of an `LSystem` without drawing.
+pub fn turtle_interpret(s: &str, cfg: &TurtleCfg) -> Vec<PathEvent> {
+    let mut turtle = TurtleBuilder::new(cfg.start, cfg.initial_angle_deg);
+    for c in s.chars() {
+        match c {
+            'F' | 'G' => {
+                turtle.forward(cfg.step_length);
+            }
+            'f' => {
+                turtle.pen_up().forward(cfg.step_length).pen_down();
+            }
+            '+' => {
+                turtle.turn(cfg.angle_deg);
+            }
+            '-' => {
+                turtle.turn(-cfg.angle_deg);
+            }
+            '|' => {
+                turtle.turn(180.);
+            }
+            '[' => {
+                turtle.push();
+            }
+            ']' => {
+                turtle.pop();
+            }
+            _ => {}
+        }
+    }
+    turtle.into_path_events()
+}
+
+/// The position, heading and pen of a turtle.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct TurtleState {
+    position: P2,
+    heading_deg: f32,
+    pen_down: bool,
+}
+
+/// Builds paths by steering a turtle, which draws wherever it moves while its pen is down.
+///
+/// Each unbroken run of drawn moves becomes an open subpath. Lifting the pen, or jumping back to
+/// a state saved with `push()`, starts a new one.
+#[derive(Debug, Clone)]
+pub struct TurtleBuilder {
+    state: TurtleState,
+    stack: Vec<TurtleState>,
+    events: Vec<PathEvent>,
+    /// The first point of the subpath being drawn, if any.
+    subpath: Option<P2>,
+}
+
+impl TurtleBuilder {
+    /// Creates a turtle at the position with its pen down, facing the heading in degrees
+    /// counter-clockwise from the positive x axis.
+    pub fn new(position: P2, heading_deg: f32) -> Self {
+        Self {
+            state: TurtleState {
+                position,
+                heading_deg,
+                pen_down: true,
+            },
+            stack: vec![],
+            events: vec![],
+            subpath: None,
+        }
+    }
+
+    /// Where the turtle is.
+    pub fn position(&self) -> P2 {
+        self.state.position
+    }
+
+    /// The direction the turtle faces, in degrees counter-clockwise from the positive x axis.
+    pub fn heading(&self) -> f32 {
+        self.state.heading_deg
+    }
+
+    /// Moves the turtle forward along its heading.
+    pub fn forward(&mut self, dist: f32) -> &mut Self {
+        let (sin, cos) = self.state.heading_deg.to_radians().sin_cos();
+        let from = self.state.position;
+        let to = from + V2::new(cos, sin) * dist;
+        if self.state.pen_down {
+            if self.subpath.is_none() {
+                self.subpath = Some(from);
+                self.events.push(PathEvent::Begin { at: from });
+            }
+            self.events.push(PathEvent::Line { from, to });
+        }
+        self.state.position = to;
+        self
+    }
+
+    /// Moves the turtle backward, keeping its heading.
+    pub fn backward(&mut self, dist: f32) -> &mut Self {
+        self.forward(-dist)
+    }
+
+    /// Turns the turtle counter-clockwise by the degrees, or clockwise if they are negative.
+    pub fn turn(&mut self, angle_deg: f32) -> &mut Self {
+        self.state.heading_deg += angle_deg;
+        self
+    }
+
+    /// Stops drawing as the turtle moves.
+    pub fn pen_up(&mut self) -> &mut Self {
+        self.end_subpath();
+        self.state.pen_down = false;
+        self
+    }
+
+    /// Resumes drawing as the turtle moves.
+    pub fn pen_down(&mut self) -> &mut Self {
+        self.state.pen_down = true;
+        self
+    }
+
+    /// Saves the position, heading and pen of the turtle.
+    pub fn push(&mut self) -> &mut Self {
+        self.stack.push(self.state);
+        self
+    }
+
+    /// Restores the last saved state of the turtle. Does nothing if no state is saved.
+    pub fn pop(&mut self) -> &mut Self {
+        if let Some(state) = self.stack.pop() {
+            self.end_subpath();
+            self.state = state;
+        }
+        self
+    }
+
+    /// Returns the path the turtle drew.
+    pub fn into_path_events(mut self) -> Vec<PathEvent> {
+        self.end_subpath();
+        self.events
+    }
+
+    fn end_subpath(&mut self) {
+        if let Some(first) = self.subpath.take() {
+            self.events.push(PathEvent::End {
+                last: self.state.position,
+                first,
+                close: false,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn expands_rules_simultaneously() {
+        let system = LSystem {
+            axiom: String::from("A"),
+            rules: vec![('A', String::from("AB")), ('B', String::from("A"))]
+                .into_iter()
+                .collect(),
+        };
+        assert_eq!(system.expand(4), "ABAABABA");
+    }
+
+    #[test]
+    fn branches_start_new_subpaths() {
+        let cfg = TurtleCfg {
+            start: P2::new(0., 0.),
+            step_length: 1.,
+            angle_deg: 90.,
+            initial_angle_deg: 0.,
+        };
+        let events = turtle_interpret("F[+F]F", &cfg);
+        let begins: Vec<P2> = events
+            .iter()
+            .filter_map(|event| match event {
+                PathEvent::Begin { at } => Some(*at),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(begins, vec![P2::new(0., 0.), P2::new(1., 0.)]);
+        assert_eq!(events.len(), 7);
+    }
+
+    #[test]
+    fn pen_up_breaks_subpaths() {
+        let mut turtle = TurtleBuilder::new(P2::new(0., 0.), 0.);
+        turtle
+            .forward(2.)
+            .pen_up()
+            .backward(1.)
+            .pen_down()
+            .forward(3.);
+        assert_eq!(
+            turtle.into_path_events(),
+            vec![
+                PathEvent::Begin {
+                    at: P2::new(0., 0.)
+                },
+                PathEvent::Line {
+                    from: P2::new(0., 0.),
+                    to: P2::new(2., 0.)
+                },
+                PathEvent::End {
+                    last: P2::new(2., 0.),
+                    first: P2::new(0., 0.),
+                    close: false
+                },
+                PathEvent::Begin {
+                    at: P2::new(1., 0.)
+                },
+                PathEvent::Line {
+                    from: P2::new(1., 0.),
+                    to: P2::new(4., 0.)
+                },
+                PathEvent::End {
+                    last: P2::new(4., 0.),
+                    first: P2::new(1., 0.),
+                    close: false
+                },
+            ]
+        );
+    }
+}
@@ -2,10 +2,13 @@
 
 pub use valora_derive::UniformSet;
 
+use crate::Result;
 use glium::{
     texture::{texture2d::Texture2d, texture2d_multisample::Texture2dMultisample},
     uniforms::{SamplerBehavior, UniformValue},
+    Program,
 };
+use std::rc::Rc;
 
 /// A trait proxying `glium::Uniforms` for types which own their uniforms.
 pub trait OwnedUniforms {
@@ -14,6 +17,7 @@ pub trait OwnedUniforms {
 
 /// A trait for types which can represent themselves as `glium::UniformValue`.
 pub trait IntoUniformValue {
+    #[allow(clippy::wrong_self_convention)]
     fn into_uniform_value<'a>(&'a self) -> UniformValue<'a>;
 }
 
@@ -37,7 +41,7 @@ macro_rules! referenced_uniform_value {
     };
 }
 
-primitive_uniform_value!(f32, |v| UniformValue::Float(v));
+primitive_uniform_value!(f32, UniformValue::Float);
 primitive_uniform_value!((f32, f32), |v: (f32, f32)| UniformValue::Vec2([v.0, v.1]));
 primitive_uniform_value!((f32, f32, f32), |v: (f32, f32, f32)| UniformValue::Vec3([
     v.0, v.1, v.2
@@ -45,14 +49,14 @@ primitive_uniform_value!((f32, f32, f32), |v: (f32, f32, f32)| UniformValue::Vec
 primitive_uniform_value!((f32, f32, f32, f32), |v: (f32, f32, f32, f32)| {
     UniformValue::Vec4([v.0, v.1, v.2, v.3])
 });
-primitive_uniform_value!([f32; 2], |v| UniformValue::Vec2(v));
-primitive_uniform_value!([f32; 3], |v| UniformValue::Vec3(v));
-primitive_uniform_value!([f32; 4], |v| UniformValue::Vec4(v));
-primitive_uniform_value!([[f32; 2]; 2], |v| UniformValue::Mat2(v));
-primitive_uniform_value!([[f32; 3]; 3], |v| UniformValue::Mat3(v));
-primitive_uniform_value!([[f32; 4]; 4], |v| UniformValue::Mat4(v));
-
-primitive_uniform_value!(f64, |v| UniformValue::Double(v));
+primitive_uniform_value!([f32; 2], UniformValue::Vec2);
+primitive_uniform_value!([f32; 3], UniformValue::Vec3);
+primitive_uniform_value!([f32; 4], UniformValue::Vec4);
+primitive_uniform_value!([[f32; 2]; 2], UniformValue::Mat2);
+primitive_uniform_value!([[f32; 3]; 3], UniformValue::Mat3);
+primitive_uniform_value!([[f32; 4]; 4], UniformValue::Mat4);
+
+primitive_uniform_value!(f64, UniformValue::Double);
 primitive_uniform_value!((f64, f64), |v: (f64, f64)| UniformValue::DoubleVec2([
     v.0, v.1
 ]));
@@ -62,14 +66,14 @@ primitive_uniform_value!((f64, f64, f64), |v: (f64, f64, f64)| {
 primitive_uniform_value!((f64, f64, f64, f64), |v: (f64, f64, f64, f64)| {
     UniformValue::DoubleVec4([v.0, v.1, v.2, v.3])
 });
-primitive_uniform_value!([f64; 2], |v| UniformValue::DoubleVec2(v));
-primitive_uniform_value!([f64; 3], |v| UniformValue::DoubleVec3(v));
-primitive_uniform_value!([f64; 4], |v| UniformValue::DoubleVec4(v));
-primitive_uniform_value!([[f64; 2]; 2], |v| UniformValue::DoubleMat2(v));
-primitive_uniform_value!([[f64; 3]; 3], |v| UniformValue::DoubleMat3(v));
-primitive_uniform_value!([[f64; 4]; 4], |v| UniformValue::DoubleMat4(v));
-
-primitive_uniform_value!(i32, |v| UniformValue::SignedInt(v));
+primitive_uniform_value!([f64; 2], UniformValue::DoubleVec2);
+primitive_uniform_value!([f64; 3], UniformValue::DoubleVec3);
+primitive_uniform_value!([f64; 4], UniformValue::DoubleVec4);
+primitive_uniform_value!([[f64; 2]; 2], UniformValue::DoubleMat2);
+primitive_uniform_value!([[f64; 3]; 3], UniformValue::DoubleMat3);
+primitive_uniform_value!([[f64; 4]; 4], UniformValue::DoubleMat4);
+
+primitive_uniform_value!(i32, UniformValue::SignedInt);
 primitive_uniform_value!((i32, i32), |v: (i32, i32)| UniformValue::IntVec2([
     v.0, v.1
 ]));
@@ -79,11 +83,11 @@ primitive_uniform_value!((i32, i32, i32), |v: (i32, i32, i32)| UniformValue::Int
 primitive_uniform_value!((i32, i32, i32, i32), |v: (i32, i32, i32, i32)| {
     UniformValue::IntVec4([v.0, v.1, v.2, v.3])
 });
-primitive_uniform_value!([i32; 2], |v| UniformValue::IntVec2(v));
-primitive_uniform_value!([i32; 3], |v| UniformValue::IntVec3(v));
-primitive_uniform_value!([i32; 4], |v| UniformValue::IntVec4(v));
+primitive_uniform_value!([i32; 2], UniformValue::IntVec2);
+primitive_uniform_value!([i32; 3], UniformValue::IntVec3);
+primitive_uniform_value!([i32; 4], UniformValue::IntVec4);
 
-primitive_uniform_value!(i64, |v| UniformValue::Int64(v));
+primitive_uniform_value!(i64, UniformValue::Int64);
 primitive_uniform_value!((i64, i64), |v: (i64, i64)| UniformValue::Int64Vec2([
     v.0, v.1
 ]));
@@ -93,11 +97,11 @@ primitive_uniform_value!((i64, i64, i64), |v: (i64, i64, i64)| {
 primitive_uniform_value!((i64, i64, i64, i64), |v: (i64, i64, i64, i64)| {
     UniformValue::Int64Vec4([v.0, v.1, v.2, v.3])
 });
-primitive_uniform_value!([i64; 2], |v| UniformValue::Int64Vec2(v));
-primitive_uniform_value!([i64; 3], |v| UniformValue::Int64Vec3(v));
-primitive_uniform_value!([i64; 4], |v| UniformValue::Int64Vec4(v));
+primitive_uniform_value!([i64; 2], UniformValue::Int64Vec2);
+primitive_uniform_value!([i64; 3], UniformValue::Int64Vec3);
+primitive_uniform_value!([i64; 4], UniformValue::Int64Vec4);
 
-primitive_uniform_value!(u64, |v| UniformValue::UnsignedInt64(v));
+primitive_uniform_value!(u64, UniformValue::UnsignedInt64);
 primitive_uniform_value!((u64, u64), |v: (u64, u64)| UniformValue::UnsignedInt64Vec2(
     [v.0, v.1]
 ));
@@ -107,11 +111,11 @@ primitive_uniform_value!((u64, u64, u64), |v: (u64, u64, u64)| {
 primitive_uniform_value!((u64, u64, u64, u64), |v: (u64, u64, u64, u64)| {
     UniformValue::UnsignedInt64Vec4([v.0, v.1, v.2, v.3])
 });
-primitive_uniform_value!([u64; 2], |v| UniformValue::UnsignedInt64Vec2(v));
-primitive_uniform_value!([u64; 3], |v| UniformValue::UnsignedInt64Vec3(v));
-primitive_uniform_value!([u64; 4], |v| UniformValue::UnsignedInt64Vec4(v));
+primitive_uniform_value!([u64; 2], UniformValue::UnsignedInt64Vec2);
+primitive_uniform_value!([u64; 3], UniformValue::UnsignedInt64Vec3);
+primitive_uniform_value!([u64; 4], UniformValue::UnsignedInt64Vec4);
 
-primitive_uniform_value!(u32, |v| UniformValue::UnsignedInt(v));
+primitive_uniform_value!(u32, UniformValue::UnsignedInt);
 primitive_uniform_value!((u32, u32), |v: (u32, u32)| UniformValue::UnsignedIntVec2([
     v.0, v.1
 ]));
@@ -121,11 +125,11 @@ primitive_uniform_value!((u32, u32, u32), |v: (u32, u32, u32)| {
 primitive_uniform_value!((u32, u32, u32, u32), |v: (u32, u32, u32, u32)| {
     UniformValue::UnsignedIntVec4([v.0, v.1, v.2, v.3])
 });
-primitive_uniform_value!([u32; 2], |v| UniformValue::UnsignedIntVec2(v));
-primitive_uniform_value!([u32; 3], |v| UniformValue::UnsignedIntVec3(v));
-primitive_uniform_value!([u32; 4], |v| UniformValue::UnsignedIntVec4(v));
+primitive_uniform_value!([u32; 2], UniformValue::UnsignedIntVec2);
+primitive_uniform_value!([u32; 3], UniformValue::UnsignedIntVec3);
+primitive_uniform_value!([u32; 4], UniformValue::UnsignedIntVec4);
 
-primitive_uniform_value!(bool, |v| UniformValue::Bool(v));
+primitive_uniform_value!(bool, UniformValue::Bool);
 primitive_uniform_value!((bool, bool), |v: (bool, bool)| UniformValue::BoolVec2([
     v.0, v.1
 ]));
@@ -135,9 +139,9 @@ primitive_uniform_value!((bool, bool, bool), |v: (bool, bool, bool)| {
 primitive_uniform_value!((bool, bool, bool, bool), |v: (bool, bool, bool, bool)| {
     UniformValue::BoolVec4([v.0, v.1, v.2, v.3])
 });
-primitive_uniform_value!([bool; 2], |v| UniformValue::BoolVec2(v));
-primitive_uniform_value!([bool; 3], |v| UniformValue::BoolVec3(v));
-primitive_uniform_value!([bool; 4], |v| UniformValue::BoolVec4(v));
+primitive_uniform_value!([bool; 2], UniformValue::BoolVec2);
+primitive_uniform_value!([bool; 3], UniformValue::BoolVec3);
+primitive_uniform_value!([bool; 4], UniformValue::BoolVec4);
 
 referenced_uniform_value!(Texture2d, |t| UniformValue::Texture2d(t, None));
 referenced_uniform_value!(
@@ -145,6 +149,133 @@ referenced_uniform_value!(
     |t| UniformValue::Texture2dMultisample(t, Some(SamplerBehavior::default()))
 );
 
+/// Builds a set of uniforms by name, checked against the uniforms a program declares. See
+/// `ShaderProgram::uniforms()`.
+pub struct UniformsBuilder {
+    program: Result<Rc<Program>>,
+    values: Vec<(String, BoundValue)>,
+}
+
+/// Uniforms built by `UniformsBuilder`, which can be bound to a `ShaderProgram`.
+#[derive(Clone)]
+pub struct BoundUniforms {
+    values: Vec<(String, BoundValue)>,
+}
+
+#[derive(Clone)]
+enum BoundValue {
+    Value(UniformValue<'static>),
+    Texture(Rc<Texture2d>),
+}
+
+/// The ways uniforms given to a `UniformsBuilder` did not match the program.
+#[derive(Debug)]
+pub struct UniformsError {
+    /// Names which the program does not declare or does not use.
+    pub unknown: Vec<String>,
+    /// Names which the program declares with a different type.
+    pub mismatched: Vec<String>,
+}
+
+impl std::fmt::Display for UniformsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Uniforms do not match the program; unknown: {:?}, mismatched types: {:?}",
+            self.unknown, self.mismatched
+        )
+    }
+}
+
+impl std::error::Error for UniformsError {}
+
+impl UniformsBuilder {
+    pub(crate) fn new(program: Result<Rc<Program>>) -> Self {
+        Self {
+            program,
+            values: vec![],
+        }
+    }
+
+    pub fn float(self, name: &str, value: f32) -> Self {
+        self.value(name, UniformValue::Float(value))
+    }
+
+    pub fn vec2(self, name: &str, value: [f32; 2]) -> Self {
+        self.value(name, UniformValue::Vec2(value))
+    }
+
+    pub fn vec3(self, name: &str, value: [f32; 3]) -> Self {
+        self.value(name, UniformValue::Vec3(value))
+    }
+
+    pub fn vec4(self, name: &str, value: [f32; 4]) -> Self {
+        self.value(name, UniformValue::Vec4(value))
+    }
+
+    pub fn mat3(self, name: &str, value: [[f32; 3]; 3]) -> Self {
+        self.value(name, UniformValue::Mat3(value))
+    }
+
+    pub fn texture(mut self, name: &str, texture: Rc<Texture2d>) -> Self {
+        self.values
+            .push((String::from(name), BoundValue::Texture(texture)));
+        self
+    }
+
+    fn value(mut self, name: &str, value: UniformValue<'static>) -> Self {
+        self.values
+            .push((String::from(name), BoundValue::Value(value)));
+        self
+    }
+
+    /// Checks the uniforms against the program, failing with a `UniformsError` listing every
+    /// name which is unknown to the program or has the wrong type.
+    pub fn build(self) -> Result<BoundUniforms> {
+        let program = self.program?;
+        let mut unknown = vec![];
+        let mut mismatched = vec![];
+        for (name, value) in &self.values {
+            match program.get_uniform(name) {
+                None => unknown.push(name.clone()),
+                Some(uniform) if !value.into_uniform_value().is_usable_with(&uniform.ty) => {
+                    mismatched.push(name.clone())
+                }
+                Some(_) => {}
+            }
+        }
+
+        if !unknown.is_empty() || !mismatched.is_empty() {
+            return Err(UniformsError {
+                unknown,
+                mismatched,
+            }
+            .into());
+        }
+
+        Ok(BoundUniforms {
+            values: self.values,
+        })
+    }
+}
+
+impl IntoUniformValue for BoundValue {
+    fn into_uniform_value<'a>(&'a self) -> UniformValue<'a> {
+        match self {
+            BoundValue::Value(value) => *value,
+            BoundValue::Texture(texture) => texture.as_ref().into_uniform_value(),
+        }
+    }
+}
+
+impl OwnedUniforms for BoundUniforms {
+    fn visit_owned_values(&self, f: &mut dyn FnMut(&str, &dyn IntoUniformValue)) {
+        for (name, value) in &self.values {
+            f(name, value);
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -168,7 +299,7 @@ mod test {
             let value = value.into_uniform_value();
             match i {
                 0 => match (name, value) {
-                    ("camera", UniformValue::Vec3(pos)) if pos == [0., 0., 0.] => {}
+                    ("camera", UniformValue::Vec3([0., 0., 0.])) => {}
                     _ => panic!("Wrong camera values"),
                 },
                 1 => match (name, value) {
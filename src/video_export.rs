@@ -0,0 +1,132 @@
+//! Video export of rendered frames through ffmpeg.
+
+use crate::Result;
+use failure::bail;
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+    thread::JoinHandle,
+};
+
+/// The trade between encoding speed and quality of H.264 video. Slower presets compress better,
+/// so they give higher quality at the same bitrate.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum FfmpegPreset {
+    Fast,
+    #[default]
+    Medium,
+    Slow,
+}
+
+impl FfmpegPreset {
+    fn name(self) -> &'static str {
+        match self {
+            FfmpegPreset::Fast => "fast",
+            FfmpegPreset::Medium => "medium",
+            FfmpegPreset::Slow => "slow",
+        }
+    }
+}
+
+/// Encodes frames into an H.264 video by piping them to an `ffmpeg` process, which must be in
+/// `PATH`.
+pub struct FfmpegEncoder {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stderr: Option<JoinHandle<String>>,
+    frame_size: usize,
+}
+
+impl FfmpegEncoder {
+    /// Starts encoding a video of the given size in pixels to the output path, played back at
+    /// `fps` with a target bitrate of `bitrate_kbps`. The container is inferred from the path's
+    /// extension.
+    pub fn new(
+        output_path: &Path,
+        width: u32,
+        height: u32,
+        fps: u32,
+        bitrate_kbps: u32,
+        preset: FfmpegPreset,
+    ) -> Result<FfmpegEncoder> {
+        let mut child = match Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error", "-nostats"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-s", &format!("{}x{}", width, height)])
+            .args(["-r", &fps.max(1).to_string()])
+            .args(["-i", "-"])
+            .args(["-c:v", "libx264", "-preset", preset.name()])
+            .args(["-b:v", &format!("{}k", bitrate_kbps)])
+            // H.264 in yuv420p needs even dimensions.
+            .args([
+                "-vf",
+                "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+                "-pix_fmt",
+                "yuv420p",
+            ])
+            .arg(output_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                bail!("ffmpeg was not found in PATH; it is needed to encode video")
+            }
+            Err(e) => bail!("Failed to start ffmpeg: {}", e),
+        };
+
+        // Drain stderr as ffmpeg runs, so it never blocks on a full pipe.
+        let mut stderr_pipe = child.stderr.take().expect("stderr to be piped");
+        let stderr = std::thread::spawn(move || {
+            let mut stderr = String::new();
+            let _ = stderr_pipe.read_to_string(&mut stderr);
+            stderr
+        });
+
+        Ok(FfmpegEncoder {
+            stdin: child.stdin.take(),
+            child,
+            stderr: Some(stderr),
+            frame_size: width as usize * height as usize * 4,
+        })
+    }
+
+    /// Appends a frame of sRGB encoded RGBA bytes, with rows from top to bottom, of the size the
+    /// encoder was created with.
+    pub fn write_frame(&mut self, rgba: &[u8]) -> Result<()> {
+        if rgba.len() != self.frame_size {
+            bail!(
+                "Video frame needs {} bytes of RGBA; got {}",
+                self.frame_size,
+                rgba.len()
+            );
+        }
+
+        let stdin = self.stdin.as_mut().expect("stdin to be open until finish");
+        if let Err(e) = stdin.write_all(rgba) {
+            bail!("Failed to write frame to ffmpeg: {}", e);
+        }
+        Ok(())
+    }
+
+    /// Completes the video, waiting for ffmpeg to finish encoding. Fails with ffmpeg's error
+    /// output if it did not succeed.
+    pub fn finish(mut self) -> Result<()> {
+        // Closing stdin signals the end of the stream.
+        self.stdin.take();
+        let status = self.child.wait()?;
+        let stderr = self
+            .stderr
+            .take()
+            .and_then(|handle| handle.join().ok())
+            .unwrap_or_default();
+
+        if !status.success() {
+            bail!("ffmpeg failed ({}):\n{}", status, stderr.trim_end());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+//! Voronoi diagrams and Delaunay triangulations.
+
+use crate::{Polygon, Rect, Result, P2};
+use failure::bail;
+use spade::{DelaunayTriangulation, Point2, Triangulation};
+
+/// Returns the Voronoi cell of each point clipped to the bounds, in the order of the points. The
+/// cells tile the bounds.
+///
+/// The cell of a point is the region closer to it than to any other point, so cells of points
+/// outside the bounds may be empty, in which case their polygon has no vertices.
+///
+/// Fails if any point is repeated or not finite, or if the bounds have no area.
+pub fn voronoi(points: &[P2], bounds: Rect) -> Result<Vec<Polygon>> {
+    if bounds.width <= 0. || bounds.height <= 0. {
+        bail!(
+            "Voronoi bounds must have area; got {}x{}",
+            bounds.width,
+            bounds.height
+        );
+    }
+    let triangulation = triangulate(points)?;
+
+    Ok(triangulation
+        .vertices()
+        .map(|vertex| {
+            let site = vertex.position();
+            let mut cell: Vec<Point2<f64>> = bounds
+                .vertices()
+                .map(|v| Point2::new(f64::from(v.x), f64::from(v.y)))
+                .collect();
+            // Delaunay neighbors are the only points which share an edge with the cell.
+            for edge in vertex.out_edges() {
+                cell = clip_to_nearer(&cell, site, edge.to().position());
+            }
+            cell.into_iter()
+                .map(|p| P2::new(p.x as f32, p.y as f32))
+                .collect()
+        })
+        .collect())
+}
+
+/// Returns the Delaunay triangulation of the points, as triples of indices into the slice whose
+/// vertices wind counter-clockwise.
+///
+/// Repeated points are triangulated once, by the index of their first occurrence. Points which
+/// are not finite are left out.
+pub fn delaunay(points: &[P2]) -> Vec<[usize; 3]> {
+    let mut triangulation = DelaunayTriangulation::<Point2<f64>>::new();
+    // The index in `points` of each vertex of the triangulation.
+    let mut indices = vec![];
+    for (i, p) in points.iter().enumerate() {
+        if let Ok(handle) = triangulation.insert(Point2::new(f64::from(p.x), f64::from(p.y))) {
+            if handle.index() == indices.len() {
+                indices.push(i);
+            }
+        }
+    }
+
+    triangulation
+        .inner_faces()
+        .map(|face| {
+            let [a, b, c] = face.vertices();
+            [
+                indices[a.fix().index()],
+                indices[b.fix().index()],
+                indices[c.fix().index()],
+            ]
+        })
+        .collect()
+}
+
+/// Returns the triangles of the Delaunay triangulation of the points as polygons. See
+/// `delaunay()`.
+pub fn delaunay_paths(points: &[P2]) -> Vec<Polygon> {
+    delaunay(points)
+        .into_iter()
+        .map(|triangle| triangle.iter().map(|i| points[*i]).collect())
+        .collect()
+}
+
+/// Triangulates the points, with each vertex of the triangulation indexed as its point is.
+fn triangulate(points: &[P2]) -> Result<DelaunayTriangulation<Point2<f64>>> {
+    let mut triangulation = DelaunayTriangulation::<Point2<f64>>::new();
+    for (i, p) in points.iter().enumerate() {
+        let handle = match triangulation.insert(Point2::new(f64::from(p.x), f64::from(p.y))) {
+            Ok(handle) => handle,
+            Err(e) => bail!("Point {} ({:?}) cannot be triangulated: {:?}", i, p, e),
+        };
+        if handle.index() != i {
+            bail!("Point {} ({:?}) repeats point {}", i, p, handle.index());
+        }
+    }
+    Ok(triangulation)
+}
+
+/// Clips a convex polygon to the half plane of points nearer to `site` than to `other`.
+fn clip_to_nearer(
+    polygon: &[Point2<f64>],
+    site: Point2<f64>,
+    other: Point2<f64>,
+) -> Vec<Point2<f64>> {
+    let (nx, ny) = (other.x - site.x, other.y - site.y);
+    let (mx, my) = ((site.x + other.x) / 2., (site.y + other.y) / 2.);
+    // Positive on the side of `other`.
+    let side = |p: Point2<f64>| (p.x - mx) * nx + (p.y - my) * ny;
+
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (i, &a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        let (side_a, side_b) = (side(a), side(b));
+        if side_a <= 0. {
+            clipped.push(a);
+        }
+        if (side_a < 0. && side_b > 0.) || (side_a > 0. && side_b < 0.) {
+            let t = side_a / (side_a - side_b);
+            clipped.push(Point2::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t));
+        }
+    }
+    clipped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cells_tile_bounds() {
+        let points = [
+            P2::new(1., 1.),
+            P2::new(3., 1.),
+            P2::new(2., 3.),
+            P2::new(0.5, 3.5),
+        ];
+        let bounds = Rect {
+            bottom_left: P2::new(0., 0.),
+            width: 4.,
+            height: 4.,
+        };
+        let cells = voronoi(&points, bounds).unwrap();
+
+        assert_eq!(cells.len(), points.len());
+        let total: f32 = cells.iter().map(Polygon::area).sum();
+        assert!((total - 16.).abs() < 1e-4);
+        // The first two points split the bottom edge of the bounds halfway between them.
+        assert!(cells[0].vertices().any(|v| v == P2::new(2., 0.)));
+        assert!(cells[1].vertices().any(|v| v == P2::new(2., 0.)));
+    }
+
+    #[test]
+    fn triangulates_by_input_index() {
+        let points = [
+            P2::new(0., 0.),
+            P2::new(1., 0.),
+            P2::new(0., 0.),
+            P2::new(1., 1.),
+            P2::new(0., 1.),
+        ];
+        let triangles = delaunay(&points);
+
+        assert_eq!(triangles.len(), 2);
+        assert!(triangles.iter().flatten().all(|i| *i != 2));
+        let area: f32 = delaunay_paths(&points)
+            .iter()
+            .map(Polygon::signed_area)
+            .sum();
+        assert_eq!(area, 1.);
+    }
+
+    #[test]
+    fn rejects_repeated_points() {
+        let bounds = Rect {
+            bottom_left: P2::new(0., 0.),
+            width: 1.,
+            height: 1.,
+        };
+        assert!(voronoi(&[P2::new(0.5, 0.5), P2::new(0.5, 0.5)], bounds).is_err());
+    }
+}
@@ -43,3 +43,110 @@ fn impl_uniform_set(ident: Ident, fields: syn::FieldsNamed) -> proc_macro::Token
     )
     .into()
 }
+
+/// Generates `fn main()` running an artist. See `valora::main`.
+#[proc_macro_attribute]
+pub fn main(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let args = parse_macro_input!(attr as AttributeArgs);
+    let item = parse_macro_input!(item as Item);
+
+    let artist: Type = match &item {
+        Item::Struct(ItemStruct {
+            ident, generics, ..
+        })
+        | Item::Enum(ItemEnum {
+            ident, generics, ..
+        }) if generics.params.is_empty() => parse_quote!(#ident),
+        Item::Impl(ItemImpl {
+            self_ty, generics, ..
+        }) if generics.params.is_empty() => (**self_ty).clone(),
+        _ => return Error::new(
+            item.span(),
+            "valora::main may only be attached to a struct, enum or impl block without generics.",
+        )
+        .to_compile_error()
+        .into(),
+    };
+
+    let mut defaults = vec![];
+    for arg in args {
+        let (name, value) = match default_arg(&arg) {
+            Ok(default) => default,
+            Err(e) => return e.to_compile_error().into(),
+        };
+        // A size is given as a width and height, so that either can still be overridden alone.
+        let flags: &[&str] = match name.as_str() {
+            "size" => &["--width", "--height"],
+            "width" => &["--width"],
+            "height" => &["--height"],
+            "scale" => &["--scale"],
+            "seed" => &["--seed"],
+            "frames" => &["--frames"],
+            "fps" => &["--fps"],
+            _ => {
+                return Error::new(
+                    arg.span(),
+                    "Unknown option; expected size, width, height, scale, seed, frames or fps.",
+                )
+                .to_compile_error()
+                .into()
+            }
+        };
+        for flag in flags {
+            defaults.push(quote!((#flag, #value)));
+        }
+    }
+
+    quote!(
+        #item
+
+        fn main() -> ::valora::Result<()> {
+            let mut args = std::env::args();
+            let program = args.next().unwrap_or_else(|| String::from("valora"));
+            let defaults: &[(&str, &str)] = &[#(#defaults),*];
+            let options = ::valora::Options::from_iter_with_extra(
+                std::iter::once(program)
+                    .chain(
+                        defaults
+                            .iter()
+                            .flat_map(|(flag, value)| vec![flag.to_string(), value.to_string()]),
+                    )
+                    .chain(args),
+            )?;
+            ::valora::run::<#artist>(options)
+        }
+    )
+    .into()
+}
+
+/// Returns the name and value of an option such as `size = 512`, with the value as it would be
+/// written on the command line.
+fn default_arg(arg: &NestedMeta) -> Result<(String, String)> {
+    let name_value = match arg {
+        NestedMeta::Meta(Meta::NameValue(name_value)) => name_value,
+        _ => {
+            return Err(Error::new(
+                arg.span(),
+                "Expected an option such as `size = 512`.",
+            ))
+        }
+    };
+    let name = match name_value.path.get_ident() {
+        Some(ident) => ident.to_string(),
+        None => {
+            return Err(Error::new(
+                name_value.path.span(),
+                "Expected an option name.",
+            ))
+        }
+    };
+    let value = match &name_value.lit {
+        Lit::Int(int) => int.base10_digits().to_string(),
+        Lit::Float(float) => float.base10_digits().to_string(),
+        lit => return Err(Error::new(lit.span(), "Expected a number.")),
+    };
+    Ok((name, value))
+}